@@ -1,43 +1,164 @@
+use std::str::FromStr;
+
 use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 use apollo_utils::assets::receive_assets;
 
 use apollo_utils::responses::merge_responses;
 use astroport_v3::asset::AssetInfo as AstroV3AssetInfo;
+use astroport_v3::pair::StablePoolConfig;
+use astroport_v3::pair_concentrated::ConcentratedPoolConfig;
 use astroport_v3::pair_xyk_sale_tax::{SaleTaxInitParams, TaxConfigs};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    from_json, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env, Event,
-    MessageInfo, Response, StdResult, Uint128,
+    coins, from_json, to_json_binary, Addr, Api, BankMsg, Binary, CosmosMsg, Decimal, Deps,
+    DepsMut, Env, MessageInfo, Reply, Response, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use cw_dex_astroport::astroport::asset::{Asset as AstroAsset, PairInfo};
+use cw_dex_astroport::astroport::factory::{PairType, QueryMsg as FactoryQueryMsg};
+use cw_dex_astroport::astroport::liquidity_manager::QueryMsg as LiquidityManagerQueryMsg;
+use cw_dex_astroport::astroport::pair::{
+    ConfigResponse, CumulativePricesResponse, ExecuteMsg as PairExecuteMsg,
+    QueryMsg as PairQueryMsg, SimulationResponse, MAX_ALLOWED_SLIPPAGE, TWAP_PRECISION,
+};
+use cw_dex_astroport::astroport::querier::{query_fee_info, query_token_precision, FeeInfo};
+use cw_dex_astroport::astroport_v5::incentives::{
+    Cw20Msg as IncentivesCw20Msg, ExecuteMsg as IncentivesExecuteMsg,
 };
-use cw2::set_contract_version;
-use cw_dex_astroport::astroport::factory::PairType;
-use cw_dex_astroport::astroport::pair::{ConfigResponse, QueryMsg as PairQueryMsg};
-use cw_dex_astroport::astroport::querier::query_fee_info;
 use cw_dex_astroport::AstroportPool;
 
 use cw_dex::traits::Pool;
+use liquidity_helper::contract::{
+    query_balances, reply_on_last_message, BalancingProvideLiquidityArgs,
+};
+use liquidity_helper::msg::{
+    BalancingSwap, PoolIdentifier, PoolInput, PostProvideAction, ProvideLiquidityResponse,
+    QueryMsg as BaseQueryMsg, SimulateBalancingProvideLiquidityResponse,
+};
+use liquidity_helper::{LiquidityHelperContract, LiquidityHelperError};
 
 use crate::error::ContractError;
-use crate::math::calc_xyk_balancing_swap;
-use crate::msg::{CallbackMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
-use crate::state::ASTROPORT_FACTORY;
+use crate::math::{
+    calc_initial_provide_amounts, calc_pcl_balancing_swap, calc_pcl_initial_provide_liquidity,
+    calc_pcl_provide_value, calc_stable_balancing_swap, calc_stable_provide_liquidity,
+    calc_xyk_balancing_swap, calc_xyk_provide_liquidity,
+};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::state::{
+    PriceObservation, PriceObservations, ProvideLiquidityState, ASTROPORT_FACTORY,
+    ASTROPORT_INCENTIVES, ASTROPORT_LIQUIDITY_MANAGER, PRICE_OBSERVATIONS, PROVIDE_LIQUIDITY_STATE,
+};
+
+const PROVIDE_LIQUIDITY_REPLY_ID: u64 = 1;
+
+/// The minimum number of seconds that the TWAP `max_price_deviation` is
+/// checked against is calculated over
+pub const TWAP_WINDOW: u64 = 600;
+/// The maximum age in seconds of the price observation that the TWAP
+/// `max_price_deviation` is checked against is calculated from. Older
+/// observations are not used, so that the TWAP is over a recent window.
+pub const MAX_OBSERVATION_AGE: u64 = 3600;
+
+/// The Astroport liquidity helper
+pub struct AstroportLiquidityHelper;
+
+impl LiquidityHelperContract for AstroportLiquidityHelper {
+    type Pool = AstroportPool;
+    type Error = ContractError;
+
+    const CONTRACT_NAME: &'static str = "astroport-liquidity-helper";
+    const CONTRACT_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    fn parse_pool(deps: Deps, pool: PoolInput) -> Result<AstroportPool, ContractError> {
+        parse_pool(deps, pool)
+    }
+
+    fn validate_pool_identifier(
+        api: &dyn Api,
+        identifier: PoolIdentifier,
+    ) -> Result<PoolIdentifier, ContractError> {
+        match identifier {
+            PoolIdentifier::AstroportPair { address } => Ok(PoolIdentifier::AstroportPair {
+                address: api.addr_validate(&address)?.to_string(),
+            }),
+            identifier => {
+                Err(LiquidityHelperError::UnsupportedPoolIdentifier { identifier }.into())
+            }
+        }
+    }
+
+    fn balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        args: BalancingProvideLiquidityArgs,
+    ) -> Result<Response, ContractError> {
+        let pool = parse_pool(deps.as_ref(), args.pool)?;
+        execute_balancing_provide_liquidity(
+            deps,
+            env,
+            info,
+            args.assets,
+            args.min_out,
+            pool,
+            args.recipient,
+            args.max_swap_slippage,
+            args.initial_price,
+            args.max_price_deviation,
+            args.post_provide,
+            args.assets_held,
+        )
+    }
+
+    fn reply_balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        msg: Reply,
+    ) -> Result<Response, ContractError> {
+        match msg.id {
+            PROVIDE_LIQUIDITY_REPLY_ID => reply_provide_liquidity(deps, env),
+            id => Err(LiquidityHelperError::UnknownReplyId { id }.into()),
+        }
+    }
 
-// version info for migration info
-const CONTRACT_NAME: &str = "crates.io:astroport-liquidity-helper";
-const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+    fn simulate_balancing_provide_liquidity(
+        deps: Deps,
+        env: Env,
+        assets: AssetList,
+        pool: AstroportPool,
+        initial_price: Option<Decimal>,
+    ) -> Result<SimulateBalancingProvideLiquidityResponse, ContractError> {
+        query_simulate_balancing_provide_liquidity(deps, env, assets, pool, initial_price)
+    }
+
+    fn record_price_observation(
+        deps: DepsMut,
+        env: Env,
+        pool: AstroportPool,
+    ) -> Result<Response, ContractError> {
+        record_price_observation(deps, &env, &pool)?;
+        Ok(Response::default())
+    }
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     _env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    AstroportLiquidityHelper::instantiate(deps.branch(), &msg.owner)?;
 
     let astroport_factory = deps.api.addr_validate(&msg.astroport_factory)?;
     ASTROPORT_FACTORY.save(deps.storage, &astroport_factory)?;
+    let astroport_liquidity_manager = deps.api.addr_validate(&msg.astroport_liquidity_manager)?;
+    ASTROPORT_LIQUIDITY_MANAGER.save(deps.storage, &astroport_liquidity_manager)?;
+    if let Some(astroport_incentives) = msg.astroport_incentives {
+        let astroport_incentives = deps.api.addr_validate(&astroport_incentives)?;
+        ASTROPORT_INCENTIVES.save(deps.storage, &astroport_incentives)?;
+    }
 
     Ok(Response::default())
 }
@@ -49,144 +170,165 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    match msg {
-        ExecuteMsg::BalancingProvideLiquidity {
-            assets,
-            min_out,
-            pool,
-            recipient,
-        } => {
-            let assets = assets.check(deps.api)?;
-            let pool: AstroportPool = from_json(pool)?;
-            execute_balancing_provide_liquidity(deps, env, info, assets, min_out, pool, recipient)
-        }
-        ExecuteMsg::Callback(msg) => {
-            // Only contract can call callbacks
-            if info.sender != env.contract.address {
-                return Err(ContractError::Unauthorized {});
-            }
+    AstroportLiquidityHelper::execute(deps, env, info, msg)
+}
 
-            match msg {
-                CallbackMsg::ReturnLpTokens {
-                    pool,
-                    balance_before,
-                    recipient,
-                } => execute_callback_return_lp_tokens(
-                    deps,
-                    env,
-                    info,
-                    pool,
-                    balance_before,
-                    recipient,
-                ),
-            }
+/// Deserialises the pool or looks it up by its pair address, and validates it
+/// against the Astroport factory.
+fn parse_pool(deps: Deps, pool: PoolInput) -> Result<AstroportPool, ContractError> {
+    let pool = match pool {
+        PoolInput::Serialized(pool) => from_json(pool)?,
+        PoolInput::Identifier(PoolIdentifier::AstroportPair { address }) => AstroportPool::new(
+            deps,
+            deps.api.addr_validate(&address)?,
+            ASTROPORT_LIQUIDITY_MANAGER.load(deps.storage)?,
+        )?,
+        PoolInput::Identifier(identifier) => {
+            return Err(LiquidityHelperError::UnsupportedPoolIdentifier { identifier }.into())
         }
+    };
+    validate_pool(deps, &pool)?;
+
+    Ok(pool)
+}
+
+/// Verifies that `pool` matches a pair registered in the Astroport factory,
+/// so that a caller can't pass a pool that lies about its pair address, LP
+/// token or pair type, and that its liquidity manager, which provided funds and
+/// withdrawn LP tokens are sent to, is the configured one.
+fn validate_pool(deps: Deps, pool: &AstroportPool) -> Result<(), ContractError> {
+    let factory_pair_info: PairInfo = deps.querier.query_wasm_smart(
+        ASTROPORT_FACTORY.load(deps.storage)?,
+        &FactoryQueryMsg::Pair {
+            asset_infos: pool.pool_assets.iter().cloned().map(Into::into).collect(),
+        },
+    )?;
+    if factory_pair_info.contract_addr != pool.pair_addr {
+        return Err(ContractError::PairNotRegistered {
+            pair_addr: pool.pair_addr.clone(),
+        });
+    }
+
+    let pair_info: PairInfo = deps
+        .querier
+        .query_wasm_smart(&pool.pair_addr, &PairQueryMsg::Pair {})?;
+    let lp_token = AssetInfo::from_str(deps.api, pair_info.liquidity_token.as_str());
+    if lp_token != pool.lp_token {
+        return Err(ContractError::LpTokenMismatch {
+            expected: lp_token,
+            received: pool.lp_token.clone(),
+        });
+    }
+    if pair_info.pair_type != pool.pair_type {
+        return Err(ContractError::PairTypeMismatch {
+            expected: pair_info.pair_type,
+            received: pool.pair_type.clone(),
+        });
     }
+
+    let liquidity_manager = ASTROPORT_LIQUIDITY_MANAGER.load(deps.storage)?;
+    if liquidity_manager != pool.liquidity_manager {
+        return Err(ContractError::LiquidityManagerMismatch {
+            expected: liquidity_manager,
+            received: pool.liquidity_manager.clone(),
+        });
+    }
+
+    Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_balancing_provide_liquidity(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     mut assets: AssetList,
     min_out: Uint128,
     pool: AstroportPool,
-    recipient: Option<String>,
+    recipient: Addr,
+    max_swap_slippage: Option<Decimal>,
+    initial_price: Option<Decimal>,
+    max_price_deviation: Option<Decimal>,
+    post_provide: Option<PostProvideAction>,
+    assets_held: bool,
 ) -> Result<Response, ContractError> {
-    // Get response with message to do TransferFrom on any Cw20s and assert that
-    // native tokens have been received already.
-    let receive_res = receive_assets(&info, &env, &assets)?;
+    AstroportLiquidityHelper::assert_not_paused(
+        deps.as_ref(),
+        &PoolIdentifier::AstroportPair {
+            address: pool.pair_addr.to_string(),
+        },
+    )?;
+    match post_provide {
+        Some(PostProvideAction::Stake {}) if !ASTROPORT_INCENTIVES.exists(deps.storage) => {
+            return Err(ContractError::NoAstroportIncentives {});
+        }
+        None | Some(PostProvideAction::Stake {} | PostProvideAction::VaultDeposit { .. }) => {}
+        Some(action) => {
+            return Err(LiquidityHelperError::UnsupportedPostProvideAction { action }.into())
+        }
+    }
 
-    // Unwrap recipient or use caller's address
-    let recipient = recipient.map_or(Ok(info.sender.clone()), |x| deps.api.addr_validate(&x))?;
+    // Get response with message to do TransferFrom on any Cw20s and assert that
+    // native tokens have been received already. Assets routed into pool assets
+    // first are already held by the contract.
+    let receive_res = if assets_held {
+        Response::new()
+    } else {
+        receive_assets(&info, &env, &assets)?
+    };
+    let received: AssetList = if assets_held {
+        assets.clone()
+    } else {
+        info.funds.clone().into()
+    };
 
-    // Check lp token balance before, to pass into callback
+    // Check lp token balance before, to compare with in the reply
     let lp_token_balance = pool
         .lp_token()
         .query_balance(&deps.querier, env.contract.address.to_string())?;
 
-    // For XYK pools we need to swap some amount of one asset into the other before
-    // we provide liquidity. For other types we can just provide liquidity
-    // directly.
-    let is_xyk = match &pool.pair_type {
-        PairType::Xyk {} => true,
-        PairType::Custom(t) if t == "astroport-pair-xyk-sale-tax" => true,
-        PairType::Stable {} => false,
-        PairType::Custom(t) if t == "concentrated" => false,
-        _ => return Err(ContractError::UnsupportedPairType {}),
-    };
-    let swap_res = if is_xyk {
-        let pool_res = pool.query_pool_info(&deps.querier)?;
-
-        let pool_reserves: [Asset; 2] = [
-            Asset::from(pool_res.assets[0].clone()),
-            Asset::from(pool_res.assets[1].clone()),
-        ];
-        if assets.len() > 2 {
-            return Err(ContractError::MoreThanTwoAssets {});
-        }
+    // Check the balances of the pool assets before the assets were received,
+    // so that anything not used can be refunded in the reply
+    let mut balances_before = query_balances(deps.as_ref(), &env, &pool.pool_assets)?;
+    for asset in received
+        .iter()
+        .filter(|x| pool.pool_assets.contains(&x.info))
+    {
+        balances_before.deduct(asset)?;
+    }
 
-        // If only one asset is provided in the AssetList, we need to
-        // create the other asset with an empty amount
-        let assets_slice: [Asset; 2] = [
-            assets
-                .find(&pool_reserves[0].info)
-                .cloned()
-                .unwrap_or_else(|| Asset {
-                    info: pool_reserves[0].info.clone(),
-                    amount: Uint128::zero(),
-                }),
-            assets
-                .find(&pool_reserves[1].info)
-                .cloned()
-                .unwrap_or_else(|| Asset {
-                    info: pool_reserves[1].info.clone(),
-                    amount: Uint128::zero(),
-                }),
-        ];
+    // The first provide to an empty pool sets its price, so there is nothing
+    // to balance against. The assets in excess of the initial price, if one is
+    // given, are returned to the recipient.
+    let is_empty = pool.query_lp_token_supply(&deps.querier)?.is_zero();
 
-        // Get fee amount
-        let fee_info = query_fee_info(
-            &deps.querier,
-            ASTROPORT_FACTORY.load(deps.storage)?,
-            pool.pair_type.clone(),
-        )?;
-        let fee = fee_info.total_fee_rate;
-
-        // Get sale tax if applicable
-        let tax_configs: Option<TaxConfigs<Addr>> = match &pool.pair_type {
-            PairType::Custom(t) => match t.as_str() {
-                "astroport-pair-xyk-sale-tax" => {
-                    let config: ConfigResponse = deps
-                        .querier
-                        .query_wasm_smart(&pool.pair_addr, &PairQueryMsg::Config {})?;
-                    let astro_asset_infos: Vec<AstroV3AssetInfo> = pool
-                        .pool_assets
-                        .iter()
-                        .map(|x| match x {
-                            AssetInfo::Cw20(addr) => AstroV3AssetInfo::cw20(addr.clone()),
-                            AssetInfo::Native(denom) => AstroV3AssetInfo::native(denom),
-                        })
-                        .collect();
-                    let sale_tax_params: SaleTaxInitParams = from_json(config.params.unwrap())?;
-                    let tax_configs = sale_tax_params
-                        .tax_configs
-                        .check(deps.api, &astro_asset_infos)?;
-                    Some(tax_configs)
-                }
-                _ => None,
-            },
-            _ => None,
-        };
+    // Check the spot price against the TWAP before the balancing swap is
+    // calculated from the reserves, and record a price observation for later
+    // checks. Empty pools don't have a price yet. The first observation must be
+    // recorded with `RecordPriceObservation`, since one recorded here would be
+    // reverted together with a failed check.
+    if let (false, Some(max_price_deviation)) = (is_empty, max_price_deviation) {
+        assert_price_deviation(deps.as_ref(), &env, &pool, max_price_deviation)?;
+        record_price_observation(deps.branch(), &env, &pool)?;
+    }
 
-        // Calculate amount of tokens to swap
-        let (offer_asset, return_asset) = calc_xyk_balancing_swap(
-            assets_slice,
-            [pool_reserves[0].amount, pool_reserves[1].amount],
-            fee,
-            tax_configs,
-        )?;
+    let leftover = if is_empty {
+        calc_initial_provide_leftover(&assets, &pool, initial_price)?
+    } else {
+        AssetList::new()
+    };
+    assets.deduct_many(&leftover)?;
+    let leftover_res = Response::new().add_messages(leftover.transfer_msgs(&recipient)?);
 
+    // For XYK pools we need to swap some amount of one asset into the other before
+    // we provide liquidity. For stableswap and PCL pools we swap if it reduces
+    // the fee on imbalanced provides, otherwise we provide liquidity directly.
+    let balancing_swap = if is_empty {
+        None
+    } else {
+        calc_balancing_swap(deps.as_ref(), &assets, &pool)?
+    };
+    let (swap_res, swapped) = if let Some((offer_asset, return_asset)) = balancing_swap {
         // Update balances for liquidity provision
         assets.add(&return_asset)?;
         assets.deduct(&offer_asset)?;
@@ -194,26 +336,41 @@ pub fn execute_balancing_provide_liquidity(
         // If either of the assets are still zero after the swap, we can't
         // provide liquidity. This can happen if the amount of tokens to swap
         // is so small that the returned amount of the other asset would be zero.
-        if pool.pool_assets.iter().any(|x| {
-            assets
-                .find(x)
-                .map_or_else(Uint128::zero, |y| y.amount)
-                .is_zero()
-        }) {
+        // Stableswap pools accept any ratio, so they may be provided a zero
+        // amount of some of their assets.
+        if pool.pair_type != (PairType::Stable {})
+            && pool.pool_assets.iter().any(|x| {
+                assets
+                    .find(x)
+                    .map_or_else(Uint128::zero, |y| y.amount)
+                    .is_zero()
+            })
+        {
             if min_out.is_zero() {
                 // If min_out is zero, we can just return the received native
                 // assets. We don't need to return any Cw20 assets, because
-                // we did not execute the transferFrom on them.
-                let event = Event::new(
-                    "apollo/astroport-liquidity-helper/execute_balancing_provide_liquidity",
-                )
-                .add_attribute("action", "No liquidity provided. Zero amount of asset")
-                .add_attribute("assets", assets.to_string())
-                .add_attribute("min_out", min_out);
+                // we did not execute the transferFrom on them. Assets already
+                // held by the contract are all returned to the recipient.
+                let event = AstroportLiquidityHelper::event("execute_balancing_provide_liquidity")
+                    .add_attribute("action", "No liquidity provided. Zero amount of asset")
+                    .add_attribute("assets", assets.to_string())
+                    .add_attribute("min_out", min_out);
 
                 // Can only return funds if there are some
-                let mut res = Response::new().add_event(event);
-                if !info.funds.is_empty() {
+                let data = ProvideLiquidityResponse {
+                    lp_token: pool.lp_token(),
+                    lp_amount: Uint128::zero(),
+                    swapped: None,
+                    leftover: received.clone(),
+                    lock_id: None,
+                    vault_tokens: None,
+                };
+                let mut res = Response::new()
+                    .add_event(event)
+                    .set_data(to_json_binary(&data)?);
+                if assets_held {
+                    res = res.add_messages(received.transfer_msgs(&recipient)?);
+                } else if !info.funds.is_empty() {
                     res = res.add_message(CosmosMsg::Bank(BankMsg::Send {
                         to_address: info.sender.to_string(),
                         amount: info.funds,
@@ -231,18 +388,39 @@ pub fn execute_balancing_provide_liquidity(
 
         // Create message to swap some of the asset to the other
         if offer_asset.amount > Uint128::zero() && return_asset.amount > Uint128::zero() {
-            pool.swap(
+            // If a max slippage is given, fail if the pair's simulation of the
+            // swap returns less than the predicted amount minus the slippage,
+            // and require the swap to return at least that much.
+            if let Some(max_swap_slippage) = max_swap_slippage {
+                let simulated_return = pool.simulate_swap(
+                    deps.as_ref(),
+                    offer_asset.clone(),
+                    return_asset.info.clone(),
+                )?;
+                assert_max_swap_slippage(return_asset.amount, simulated_return, max_swap_slippage)?;
+            }
+            let swap_min_out = max_swap_slippage.map_or(Uint128::one(), |x| {
+                (return_asset.amount * Decimal::one().saturating_sub(x)).max(Uint128::one())
+            });
+            let swap_res = pool.swap(
                 deps.as_ref(),
                 &env,
-                offer_asset,
+                offer_asset.clone(),
                 return_asset.info.clone(),
-                Uint128::one(),
-            )?
+                swap_min_out,
+            )?;
+            (
+                swap_res,
+                Some(BalancingSwap {
+                    offer_asset,
+                    return_asset,
+                }),
+            )
         } else {
-            Response::new()
+            (Response::new(), None)
         }
     } else {
-        Response::new()
+        (Response::new(), None)
     };
 
     // For stableswap and concentrated liquidity pools we are allowed to provide
@@ -251,80 +429,856 @@ pub fn execute_balancing_provide_liquidity(
     let provide_liquidity_res =
         pool.provide_liquidity(deps.as_ref(), &env, assets.clone(), min_out)?;
 
-    // Callback to return LP tokens
-    let callback_msg = CallbackMsg::ReturnLpTokens {
-        pool,
-        balance_before: lp_token_balance,
-        recipient,
+    // Save state to return the LP tokens in the reply to the provide liquidity
+    // message
+    PROVIDE_LIQUIDITY_STATE.save(
+        deps.storage,
+        &ProvideLiquidityState {
+            pool,
+            lp_balance_before: lp_token_balance,
+            balances_before,
+            swapped,
+            leftover,
+            recipient,
+            post_provide,
+        },
+    )?;
+    let provide_liquidity_res =
+        reply_on_last_message(provide_liquidity_res, PROVIDE_LIQUIDITY_REPLY_ID)?;
+
+    let event = AstroportLiquidityHelper::event("execute_balancing_provide_liquidity")
+        .add_attribute("assets", assets.to_string())
+        .add_attribute("min_out", min_out);
+
+    Ok(merge_responses(vec![
+        receive_res,
+        leftover_res,
+        swap_res,
+        provide_liquidity_res,
+    ])
+    .add_event(event))
+}
+
+/// Calculates the assets that are not used by the first provide to an empty
+/// pool, which are those in excess of `initial_price`. All assets are provided
+/// if no initial price is given.
+fn calc_initial_provide_leftover(
+    assets: &AssetList,
+    pool: &AstroportPool,
+    initial_price: Option<Decimal>,
+) -> Result<AssetList, ContractError> {
+    let Some(initial_price) = initial_price else {
+        return Ok(AssetList::new());
+    };
+    if pool.pool_assets.len() != 2 {
+        return Err(ContractError::MoreThanTwoAssets {});
+    }
+
+    let amounts = [0, 1].map(|i| {
+        assets
+            .find(&pool.pool_assets[i])
+            .map_or_else(Uint128::zero, |x| x.amount)
+    });
+    let provide_amounts = calc_initial_provide_amounts(amounts, initial_price)?;
+
+    let mut leftover = AssetList::new();
+    for (info, (amount, provide_amount)) in pool
+        .pool_assets
+        .iter()
+        .zip(amounts.into_iter().zip(provide_amounts))
+    {
+        if amount > provide_amount {
+            leftover.add(&Asset::new(info.clone(), amount - provide_amount))?;
+        }
+    }
+
+    Ok(leftover)
+}
+
+/// Calculates the swap needed to balance `assets` to the ratio of the pool's
+/// reserves before providing liquidity.
+///
+/// Returns `Some((offer_asset, return_asset))` for XYK pools, and for
+/// stableswap and PCL pools if swapping is estimated to yield more LP tokens
+/// than providing the assets as they are.
+pub fn calc_balancing_swap(
+    deps: Deps,
+    assets: &AssetList,
+    pool: &AstroportPool,
+) -> Result<Option<(Asset, Asset)>, ContractError> {
+    match &pool.pair_type {
+        PairType::Xyk {} | PairType::Stable {} => {}
+        PairType::Custom(t) if t == "astroport-pair-xyk-sale-tax" || t == "concentrated" => {}
+        _ => return Err(ContractError::UnsupportedPairType {}),
+    };
+
+    let pool_res = pool.query_pool_info(&deps.querier)?;
+    let is_stable = pool.pair_type == (PairType::Stable {});
+    let is_pcl = pool.pair_type == PairType::Custom("concentrated".to_string());
+
+    // Pool assets missing from `assets` are added with an empty amount, so that
+    // the assets are in the same order as the reserves
+    let pool_reserves: Vec<Asset> = pool_res.assets.into_iter().map(Asset::from).collect();
+    let reserves: Vec<Uint128> = pool_reserves.iter().map(|x| x.amount).collect();
+    let assets_vec: Vec<Asset> = pool_reserves
+        .iter()
+        .map(|reserve| {
+            assets
+                .find(&reserve.info)
+                .cloned()
+                .unwrap_or_else(|| Asset::new(reserve.info.clone(), Uint128::zero()))
+        })
+        .collect();
+
+    // Get fee amount
+    let fee_info = query_fee_info(
+        &deps.querier,
+        ASTROPORT_FACTORY.load(deps.storage)?,
+        pool.pair_type.clone(),
+    )?;
+    let fee = fee_info.total_fee_rate;
+
+    if is_stable {
+        if assets.len() > pool_reserves.len() {
+            return Ok(None);
+        }
+        return calc_stable_pool_balancing_swap(deps, pool, &assets_vec, &reserves, &fee_info);
+    }
+
+    // The PCL strategy only supports pools with two assets, otherwise the
+    // assets are provided as they are
+    if is_pcl && (pool_reserves.len() != 2 || assets.len() > 2) {
+        return Ok(None);
+    }
+    if assets.len() > 2 {
+        return Err(ContractError::MoreThanTwoAssets {});
+    }
+    let assets_slice: [Asset; 2] = [assets_vec[0].clone(), assets_vec[1].clone()];
+
+    if is_pcl {
+        return calc_pcl_pool_balancing_swap(deps, pool, assets_slice, [reserves[0], reserves[1]]);
+    }
+
+    // Get sale tax if applicable
+    let tax_configs: Option<TaxConfigs<Addr>> = match &pool.pair_type {
+        PairType::Custom(t) => match t.as_str() {
+            "astroport-pair-xyk-sale-tax" => {
+                let config: ConfigResponse = deps
+                    .querier
+                    .query_wasm_smart(&pool.pair_addr, &PairQueryMsg::Config {})?;
+                let astro_asset_infos: Vec<AstroV3AssetInfo> = pool
+                    .pool_assets
+                    .iter()
+                    .map(|x| match x {
+                        AssetInfo::Cw20(addr) => AstroV3AssetInfo::cw20(addr.clone()),
+                        AssetInfo::Native(denom) => AstroV3AssetInfo::native(denom),
+                    })
+                    .collect();
+                let sale_tax_params: SaleTaxInitParams = from_json(config.params.unwrap())?;
+                let tax_configs = sale_tax_params
+                    .tax_configs
+                    .check(deps.api, &astro_asset_infos)?;
+                Some(tax_configs)
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    // Calculate amount of tokens to swap
+    Ok(Some(calc_xyk_balancing_swap(
+        assets_slice,
+        [reserves[0], reserves[1]],
+        fee,
+        tax_configs,
+    )?))
+}
+
+/// Calculates the swap to do before providing liquidity to a stableswap pool.
+///
+/// Returns `Some((offer_asset, return_asset))` only if swapping is estimated to
+/// yield more LP tokens than providing `assets` as they are, which is
+/// simulated by the pair itself. Returns `None` if the solver fails, since the
+/// assets can always be provided without swapping.
+fn calc_stable_pool_balancing_swap(
+    deps: Deps,
+    pool: &AstroportPool,
+    assets: &[Asset],
+    reserves: &[Uint128],
+    fee_info: &FeeInfo,
+) -> Result<Option<(Asset, Asset)>, ContractError> {
+    let amp = query_stable_amp(deps, pool)?;
+    let total_share = pool.query_lp_token_supply(&deps.querier)?;
+    let raw_provide_lp = simulate_provide_liquidity(deps, pool, &assets.to_vec().into())?;
+
+    let Some((offer_asset, return_asset)) =
+        calc_stable_balancing_swap(assets, reserves, total_share, raw_provide_lp, amp, fee_info)
+            .ok()
+            .flatten()
+    else {
+        return Ok(None);
+    };
+
+    // Use the exact return amount of the swap as simulated by the pair
+    let simulation: SimulationResponse = deps.querier.query_wasm_smart(
+        &pool.pair_addr,
+        &PairQueryMsg::Simulation {
+            offer_asset: offer_asset.clone().into(),
+            ask_asset_info: Some(return_asset.info.clone().into()),
+        },
+    )?;
+    if simulation.return_amount.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        offer_asset,
+        Asset::new(return_asset.info, simulation.return_amount),
+    )))
+}
+
+/// Calculates the swap to do before providing liquidity to a PCL pool.
+///
+/// PCL pairs charge a fee on the imbalanced part of a provide, which can be
+/// avoided by first swapping the assets to the same value at the pool's price
+/// scale. Returns `Some((offer_asset, return_asset))` only if the value of the
+/// assets after the swap, as simulated by the pair, is larger than the value
+/// of the assets minus the fee on an imbalanced provide. If the pair fails to
+/// simulate the swap, e.g. because its invariant solver does not converge, the
+/// assets are provided as they are.
+fn calc_pcl_pool_balancing_swap(
+    deps: Deps,
+    pool: &AstroportPool,
+    assets: [Asset; 2],
+    reserves: [Uint128; 2],
+) -> Result<Option<(Asset, Asset)>, ContractError> {
+    let params = query_pcl_params(deps, pool)?;
+
+    let Some(offer_asset) = calc_pcl_balancing_swap(assets.clone(), &params)? else {
+        return Ok(None);
+    };
+    let (offer_idx, ask_idx) = if offer_asset.info == assets[0].info {
+        (0, 1)
+    } else {
+        (1, 0)
+    };
+
+    let Ok(simulation) = deps.querier.query_wasm_smart::<SimulationResponse>(
+        &pool.pair_addr,
+        &PairQueryMsg::Simulation {
+            offer_asset: offer_asset.clone().into(),
+            ask_asset_info: Some(assets[ask_idx].info.clone().into()),
+        },
+    ) else {
+        return Ok(None);
+    };
+    if simulation.return_amount.is_zero() {
+        return Ok(None);
+    }
+
+    // Compare the value of the assets provided with and without the swap
+    let raw_value =
+        calc_pcl_provide_value([assets[0].amount, assets[1].amount], reserves, &params)?;
+    let mut deposits = [assets[0].amount, assets[1].amount];
+    let mut swapped_reserves = reserves;
+    deposits[offer_idx] -= offer_asset.amount;
+    deposits[ask_idx] += simulation.return_amount;
+    swapped_reserves[offer_idx] += offer_asset.amount;
+    swapped_reserves[ask_idx] = swapped_reserves[ask_idx].saturating_sub(simulation.return_amount);
+    let swapped_value = calc_pcl_provide_value(deposits, swapped_reserves, &params)?;
+    if swapped_value <= raw_value {
+        return Ok(None);
     }
-    .into_cosmos_msg(&env)?;
 
-    let event: Event =
-        Event::new("apollo/astroport-liquidity-helper/execute_balancing_provide_liquidity")
-            .add_attribute("assets", assets.to_string())
-            .add_attribute("min_out", min_out);
+    Ok(Some((
+        offer_asset,
+        Asset::new(assets[ask_idx].info.clone(), simulation.return_amount),
+    )))
+}
 
-    Ok(
-        merge_responses(vec![receive_res, swap_res, provide_liquidity_res])
-            .add_message(callback_msg)
-            .add_event(event),
+/// Queries the parameters of a PCL pair
+fn query_pcl_params(deps: Deps, pool: &AstroportPool) -> StdResult<ConcentratedPoolConfig> {
+    let config: ConfigResponse = deps
+        .querier
+        .query_wasm_smart(&pool.pair_addr, &PairQueryMsg::Config {})?;
+    from_json(
+        config
+            .params
+            .ok_or_else(|| StdError::generic_err("PCL pair config has no params"))?,
     )
 }
 
-pub fn execute_callback_return_lp_tokens(
+/// Queries the current amplification of a stableswap pair
+fn query_stable_amp(deps: Deps, pool: &AstroportPool) -> StdResult<Decimal> {
+    let config: ConfigResponse = deps
+        .querier
+        .query_wasm_smart(&pool.pair_addr, &PairQueryMsg::Config {})?;
+    let params: StablePoolConfig = from_json(
+        config
+            .params
+            .ok_or_else(|| StdError::generic_err("Stable pair config has no params"))?,
+    )?;
+    Ok(params.amp)
+}
+
+/// Queries the cumulative price of the first pool asset in units of the second
+/// from the pair
+fn query_price_observation(
+    deps: Deps,
+    env: &Env,
+    pool: &AstroportPool,
+) -> StdResult<PriceObservation> {
+    let res: CumulativePricesResponse = deps
+        .querier
+        .query_wasm_smart(&pool.pair_addr, &PairQueryMsg::CumulativePrices {})?;
+    let price_cumulative = res
+        .cumulative_prices
+        .into_iter()
+        .find(|(from, to, _)| from == &res.assets[0].info && to == &res.assets[1].info)
+        .map(|(_, _, price)| price)
+        .ok_or_else(|| StdError::generic_err("Pair has no cumulative price for its assets"))?;
+
+    Ok(PriceObservation {
+        timestamp: env.block.time.seconds(),
+        price_cumulative,
+    })
+}
+
+/// Records the current cumulative price of the pair. The latest observation is
+/// only replaced once it is `TWAP_WINDOW` old, so that there is always one at
+/// least that old to calculate the TWAP from after the first two.
+fn record_price_observation(
     deps: DepsMut,
-    env: Env,
-    _info: MessageInfo,
-    pool: AstroportPool,
-    balance_before: Uint128,
-    recipient: Addr,
-) -> Result<Response, ContractError> {
+    env: &Env,
+    pool: &AstroportPool,
+) -> Result<(), ContractError> {
+    let observation = query_price_observation(deps.as_ref(), env, pool)?;
+    let observations = match PRICE_OBSERVATIONS.may_load(deps.storage, &pool.pair_addr)? {
+        Some(observations)
+            if observation.timestamp - observations.latest.timestamp < TWAP_WINDOW =>
+        {
+            return Ok(());
+        }
+        Some(observations) => PriceObservations {
+            previous: Some(observations.latest),
+            latest: observation,
+        },
+        None => PriceObservations {
+            previous: None,
+            latest: observation,
+        },
+    };
+    PRICE_OBSERVATIONS.save(deps.storage, &pool.pair_addr, &observations)?;
+
+    Ok(())
+}
+
+/// Returns an error if the spot price of the first pool asset in units of the
+/// second deviates from its time weighted average price by more than
+/// `max_price_deviation`. The TWAP is calculated from the cumulative price
+/// reported by the pair since an observation at least `TWAP_WINDOW` and at
+/// most `MAX_OBSERVATION_AGE` old, preferring the latest one.
+fn assert_price_deviation(
+    deps: Deps,
+    env: &Env,
+    pool: &AstroportPool,
+    max_price_deviation: Decimal,
+) -> Result<(), ContractError> {
+    let current = query_price_observation(deps, env, pool)?;
+    let start = PRICE_OBSERVATIONS
+        .may_load(deps.storage, &pool.pair_addr)?
+        .into_iter()
+        .flat_map(|observations| [Some(observations.latest), observations.previous])
+        .flatten()
+        .find(|x| (TWAP_WINDOW..=MAX_OBSERVATION_AGE).contains(&(current.timestamp - x.timestamp)))
+        .ok_or_else(|| ContractError::NoPriceObservation {
+            pair_addr: pool.pair_addr.clone(),
+        })?;
+
+    // The cumulative price is scaled by the TWAP precision and wraps around on
+    // overflow
+    let elapsed = Uint128::from(current.timestamp - start.timestamp)
+        .checked_mul(Uint128::from(10u128.pow(TWAP_PRECISION.into())))?;
+    let mut twap = Decimal::checked_from_ratio(
+        current
+            .price_cumulative
+            .wrapping_sub(start.price_cumulative),
+        elapsed,
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let reserves = pool.query_pool_info(&deps.querier)?.assets;
+    let is_xyk = matches!(&pool.pair_type, PairType::Xyk {})
+        || pool.pair_type == PairType::Custom("astroport-pair-xyk-sale-tax".to_string());
+    let spot = if is_xyk {
+        Decimal::checked_from_ratio(reserves[1].amount, reserves[0].amount)
+            .map_err(|e| StdError::generic_err(e.to_string()))?
+    } else {
+        // Stableswap and PCL pairs accumulate the price of whole tokens rather
+        // than of the smallest unit
+        let factory = ASTROPORT_FACTORY.load(deps.storage)?;
+        let base_precision = query_token_precision(&deps.querier, &reserves[0].info, &factory)?;
+        let quote_precision = query_token_precision(&deps.querier, &reserves[1].info, &factory)?;
+        twap = twap.checked_mul(Decimal::from_ratio(
+            10u128.pow(quote_precision.into()),
+            10u128.pow(base_precision.into()),
+        ))?;
+
+        // The spot price is simulated by swapping a small amount, before fees
+        let offer_amount = (reserves[0].amount / Uint128::new(10_000)).max(Uint128::one());
+        let simulation: SimulationResponse = deps.querier.query_wasm_smart(
+            &pool.pair_addr,
+            &PairQueryMsg::Simulation {
+                offer_asset: AstroAsset {
+                    info: reserves[0].info.clone(),
+                    amount: offer_amount,
+                },
+                ask_asset_info: Some(reserves[1].info.clone()),
+            },
+        )?;
+        Decimal::from_ratio(
+            simulation.return_amount + simulation.commission_amount,
+            offer_amount,
+        )
+    };
+
+    let deviation = spot
+        .abs_diff(twap)
+        .checked_div(twap)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    if deviation > max_price_deviation {
+        return Err(ContractError::PriceDeviationExceeded {
+            max_price_deviation,
+            deviation,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    AstroportLiquidityHelper::reply(deps, env, msg)
+}
+
+/// Reply handler for the provide liquidity message of a
+/// `BalancingProvideLiquidity`. Sends the minted LP tokens to the recipient, or
+/// stakes them or deposits them in a vault for it, together with any of the
+/// pool assets that were not used.
+pub fn reply_provide_liquidity(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let ProvideLiquidityState {
+        pool,
+        lp_balance_before,
+        balances_before,
+        swapped,
+        mut leftover,
+        recipient,
+        post_provide,
+    } = PROVIDE_LIQUIDITY_STATE.load(deps.storage)?;
+    PROVIDE_LIQUIDITY_STATE.remove(deps.storage);
+
     let lp_token = pool.lp_token();
-    let lp_token_balance = lp_token.query_balance(&deps.querier, env.contract.address)?;
+    let lp_token_balance = lp_token.query_balance(&deps.querier, &env.contract.address)?;
 
-    let return_amount = lp_token_balance.checked_sub(balance_before)?;
-    let return_asset = Asset::new(lp_token, return_amount);
-    let msg = return_asset.transfer_msg(&recipient)?;
+    let return_amount = lp_token_balance.checked_sub(lp_balance_before)?;
+    let return_asset = Asset::new(lp_token.clone(), return_amount);
+    let msg = match &post_provide {
+        Some(PostProvideAction::Stake {}) => Some(stake_msg(
+            &ASTROPORT_INCENTIVES.load(deps.storage)?,
+            &return_asset,
+            &recipient,
+        )?),
+        // Deposited below, once the data is known
+        Some(PostProvideAction::VaultDeposit { .. }) => None,
+        _ => Some(return_asset.transfer_msg(&recipient)?),
+    };
 
-    let event = Event::new("apollo/astroport-liquidity-helper/execute_callback_return_lp_tokens")
+    // Refund anything left of the pool assets, e.g. dust from rounding in the
+    // balancing swap or the provide, so that nothing is stuck in the contract
+    let mut refunded = AssetList::new();
+    for asset_info in &pool.pool_assets {
+        let balance = asset_info.query_balance(&deps.querier, &env.contract.address)?;
+        let balance_before = balances_before
+            .find(asset_info)
+            .map_or_else(Uint128::zero, |x| x.amount);
+        refunded.add(&Asset::new(
+            asset_info.clone(),
+            balance.saturating_sub(balance_before),
+        ))?;
+    }
+    let refund_msgs = refunded.transfer_msgs(&recipient)?;
+    leftover.add_many(&refunded)?;
+
+    let mut event = AstroportLiquidityHelper::event("reply_provide_liquidity")
         .add_attribute("return_asset", return_asset.to_string())
-        .add_attribute("recipient", recipient);
+        .add_attribute("recipient", &recipient)
+        .add_attribute(
+            "staked",
+            matches!(post_provide, Some(PostProvideAction::Stake {})).to_string(),
+        );
+    // Attribute values must not be empty
+    if refunded.len() > 0 {
+        event = event.add_attribute("refunded", refunded.to_string());
+    }
+
+    let data = ProvideLiquidityResponse {
+        lp_token,
+        lp_amount: return_amount,
+        swapped,
+        leftover,
+        lock_id: None,
+        vault_tokens: None,
+    };
+
+    let res = Response::new()
+        .add_messages(msg)
+        .add_messages(refund_msgs)
+        .add_event(event);
+
+    match post_provide {
+        // The data is set in the reply to the deposit, with the vault tokens
+        Some(PostProvideAction::VaultDeposit {
+            vault,
+            min_vault_tokens,
+        }) => {
+            let deposit_res = AstroportLiquidityHelper::vault_deposit(
+                deps,
+                &env,
+                &vault,
+                &return_asset,
+                min_vault_tokens,
+                recipient,
+                data,
+            )?;
+            Ok(merge_responses(vec![res, deposit_res]))
+        }
+        _ => Ok(res.set_data(to_json_binary(&data)?)),
+    }
+}
 
-    Ok(Response::new().add_message(msg).add_event(event))
+/// Returns the message depositing `lp_tokens` in the Astroport incentives
+/// contract for `recipient`. `AstroportStaking::stake` of cw-dex deposits for
+/// the sender, so the message is built here.
+fn stake_msg(incentives: &Addr, lp_tokens: &Asset, recipient: &Addr) -> StdResult<CosmosMsg> {
+    let recipient = Some(recipient.to_string());
+    Ok(match &lp_tokens.info {
+        AssetInfo::Cw20(addr) => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: addr.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                contract: incentives.to_string(),
+                amount: lp_tokens.amount,
+                msg: to_json_binary(&IncentivesCw20Msg::Deposit { recipient })?,
+            })?,
+            funds: vec![],
+        }),
+        AssetInfo::Native(denom) => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: incentives.to_string(),
+            msg: to_json_binary(&IncentivesExecuteMsg::Deposit { recipient })?,
+            funds: coins(lp_tokens.amount.u128(), denom),
+        }),
+    })
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::AstroportFactory {} => to_json_binary(&ASTROPORT_FACTORY.load(deps.storage)?),
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    let msg = match msg {
+        QueryMsg::AstroportFactory {} => {
+            return to_json_binary(&ASTROPORT_FACTORY.load(deps.storage)?)
+        }
+        QueryMsg::AstroportLiquidityManager {} => {
+            return to_json_binary(&ASTROPORT_LIQUIDITY_MANAGER.load(deps.storage)?)
+        }
+        QueryMsg::AstroportIncentives {} => {
+            return to_json_binary(&ASTROPORT_INCENTIVES.may_load(deps.storage)?)
+        }
+        QueryMsg::Ownership {} => BaseQueryMsg::Ownership {},
+        QueryMsg::PauseInfo {} => BaseQueryMsg::PauseInfo {},
+        QueryMsg::Router {} => BaseQueryMsg::Router {},
+        QueryMsg::SimulateBalancingProvideLiquidity {
+            assets,
+            pool,
+            initial_price,
+        } => BaseQueryMsg::SimulateBalancingProvideLiquidity {
+            assets,
+            pool,
+            initial_price,
+        },
+    };
+    AstroportLiquidityHelper::query(deps, env, msg)
+}
+
+pub fn query_simulate_balancing_provide_liquidity(
+    deps: Deps,
+    _env: Env,
+    assets: AssetList,
+    pool: AstroportPool,
+    initial_price: Option<Decimal>,
+) -> Result<SimulateBalancingProvideLiquidityResponse, ContractError> {
+    let mut provide_assets = assets.clone();
+
+    // The first provide to an empty pool is done without a balancing swap
+    if pool.query_lp_token_supply(&deps.querier)?.is_zero() {
+        let leftover = calc_initial_provide_leftover(&assets, &pool, initial_price)?;
+        provide_assets.deduct_many(&leftover)?;
+        let lp_amount = if pool.pair_type == PairType::Custom("concentrated".to_string()) {
+            let amounts = [0, 1].map(|i| {
+                provide_assets
+                    .find(&pool.pool_assets[i])
+                    .map_or_else(Uint128::zero, |x| x.amount)
+            });
+            calc_pcl_initial_provide_liquidity(amounts, &query_pcl_params(deps, &pool)?)?
+        } else {
+            simulate_provide_liquidity(deps, &pool, &provide_assets)?
+        };
+
+        return Ok(SimulateBalancingProvideLiquidityResponse {
+            swap: None,
+            provide_assets,
+            lp_tokens: Asset::new(pool.lp_token(), lp_amount),
+            leftover,
+        });
     }
+
+    let swap = match calc_balancing_swap(deps, &assets, &pool)? {
+        Some((offer_asset, return_asset)) => {
+            provide_assets.add(&return_asset)?;
+            provide_assets.deduct(&offer_asset)?;
+
+            // If either of the assets are zero after the swap, no liquidity
+            // would be provided and all assets would be returned. Stableswap
+            // pools accept any ratio.
+            if pool.pair_type != (PairType::Stable {})
+                && pool
+                    .pool_assets
+                    .iter()
+                    .any(|x| provide_assets.find(x).is_none())
+            {
+                return Ok(SimulateBalancingProvideLiquidityResponse {
+                    swap: None,
+                    provide_assets: AssetList::new(),
+                    lp_tokens: Asset::new(pool.lp_token(), Uint128::zero()),
+                    leftover: assets,
+                });
+            }
+
+            if offer_asset.amount.is_zero() || return_asset.amount.is_zero() {
+                None
+            } else {
+                Some(BalancingSwap {
+                    offer_asset,
+                    return_asset,
+                })
+            }
+        }
+        None => None,
+    };
+
+    let (lp_amount, leftover) = match &swap {
+        Some(BalancingSwap {
+            offer_asset,
+            return_asset,
+        }) => {
+            // The swap changes the reserves, so we simulate the provide against
+            // the reserves after the swap instead of querying the pool.
+            let mut reserves: AssetList = pool.get_pool_liquidity(deps)?;
+            reserves.add(offer_asset)?;
+            reserves.deduct(return_asset)?;
+            let reserves_slice = pool
+                .pool_assets
+                .iter()
+                .map(|x| reserves.find(x).map_or_else(Uint128::zero, |y| y.amount))
+                .collect::<Vec<_>>();
+            let assets_slice = pool
+                .pool_assets
+                .iter()
+                .map(|x| {
+                    provide_assets
+                        .find(x)
+                        .map_or_else(Uint128::zero, |y| y.amount)
+                })
+                .collect::<Vec<_>>();
+            let total_share = pool.query_lp_token_supply(&deps.querier)?;
+
+            if pool.pair_type == (PairType::Stable {}) {
+                // Stableswap pools accept any ratio, so all assets are used
+                let fee_info = query_fee_info(
+                    &deps.querier,
+                    ASTROPORT_FACTORY.load(deps.storage)?,
+                    pool.pair_type.clone(),
+                )?;
+                let lp_amount = calc_stable_provide_liquidity(
+                    &assets_slice,
+                    &reserves_slice,
+                    total_share,
+                    query_stable_amp(deps, &pool)?,
+                    &fee_info,
+                )?;
+                (lp_amount, AssetList::new())
+            } else if pool.pair_type == PairType::Custom("concentrated".to_string()) {
+                let lp_amount =
+                    estimate_pcl_provide_liquidity(&pool, &reserves, total_share, &provide_assets)?;
+                (lp_amount, AssetList::new())
+            } else {
+                let (lp_amount, used_amounts) = calc_xyk_provide_liquidity(
+                    [assets_slice[0], assets_slice[1]],
+                    [reserves_slice[0], reserves_slice[1]],
+                    total_share,
+                )?;
+
+                let mut leftover = provide_assets.clone();
+                for (info, used_amount) in pool.pool_assets.iter().zip(used_amounts) {
+                    leftover.deduct(&Asset::new(info.clone(), used_amount))?;
+                }
+                provide_assets.deduct_many(&leftover)?;
+
+                (lp_amount, leftover)
+            }
+        }
+        None => {
+            let lp_amount = match &pool.pair_type {
+                PairType::Custom(t) if t == "concentrated" => estimate_pcl_provide_liquidity(
+                    &pool,
+                    &pool.get_pool_liquidity(deps)?,
+                    pool.query_lp_token_supply(&deps.querier)?,
+                    &provide_assets,
+                )?,
+                _ => simulate_provide_liquidity(deps, &pool, &provide_assets)?,
+            };
+            (lp_amount, AssetList::new())
+        }
+    };
+
+    Ok(SimulateBalancingProvideLiquidityResponse {
+        swap,
+        provide_assets,
+        lp_tokens: Asset::new(pool.lp_token(), lp_amount),
+        leftover,
+    })
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    // Read current cw2 version info
-    let cw2_data = cw2::get_contract_version(deps.storage)?;
-
-    // Only allow migrating to the same contract name
-    if cw2_data.contract != CONTRACT_NAME {
-        return Err(ContractError::InvalidContractName {
-            expected: CONTRACT_NAME.to_string(),
-            received: cw2_data.contract,
-        });
+pub fn migrate(mut deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let res = AstroportLiquidityHelper::migrate(deps.branch(), msg.owner)?;
+
+    if let Some(astroport_liquidity_manager) = msg.astroport_liquidity_manager {
+        let astroport_liquidity_manager = deps.api.addr_validate(&astroport_liquidity_manager)?;
+        ASTROPORT_LIQUIDITY_MANAGER.save(deps.storage, &astroport_liquidity_manager)?;
+    }
+    if let Some(astroport_incentives) = msg.astroport_incentives {
+        let astroport_incentives = deps.api.addr_validate(&astroport_incentives)?;
+        ASTROPORT_INCENTIVES.save(deps.storage, &astroport_incentives)?;
     }
 
-    // Only allow migrating if the new version is greater than the current one
-    let old_version = semver::Version::parse(&cw2_data.version)?;
-    let new_version = semver::Version::parse(CONTRACT_VERSION)?;
-    if new_version <= old_version {
-        return Err(ContractError::InvalidContractVersion {
-            old_version,
-            new_version,
+    Ok(res)
+}
+
+/// Simulates providing `assets` to the pool through the liquidity manager and
+/// returns the amount of LP tokens that would be minted. Unlike
+/// `Pool::simulate_provide_liquidity` this includes zero amounts for pool assets
+/// missing from `assets`, which the liquidity manager requires.
+fn simulate_provide_liquidity(
+    deps: Deps,
+    pool: &AstroportPool,
+    assets: &AssetList,
+) -> StdResult<Uint128> {
+    let assets_vec: Vec<AstroAsset> = pool
+        .pool_assets
+        .iter()
+        .map(|info| {
+            Asset::new(
+                info.clone(),
+                assets.find(info).map_or_else(Uint128::zero, |x| x.amount),
+            )
+            .into()
+        })
+        .collect();
+
+    deps.querier.query_wasm_smart(
+        &pool.liquidity_manager,
+        &LiquidityManagerQueryMsg::SimulateProvide {
+            pair_addr: pool.pair_addr.to_string(),
+            pair_msg: PairExecuteMsg::ProvideLiquidity {
+                assets: assets_vec,
+                slippage_tolerance: Some(Decimal::from_str(MAX_ALLOWED_SLIPPAGE)?),
+                auto_stake: Some(false),
+                receiver: None,
+            },
+        },
+    )
+}
+
+/// Estimates the amount of LP tokens minted when providing `assets` to a PCL
+/// pool with the given `reserves`, which the liquidity manager can not
+/// simulate. Each asset is valued at its share of the pool's reserves, so the
+/// estimate is exact for balanced provides but does not account for the fee
+/// charged on imbalanced ones.
+fn estimate_pcl_provide_liquidity(
+    pool: &AstroportPool,
+    reserves: &AssetList,
+    total_share: Uint128,
+    assets: &AssetList,
+) -> Result<Uint128, ContractError> {
+    let mut lp_amount = Uint128::zero();
+    for info in &pool.pool_assets {
+        let reserve = reserves.find(info).map_or_else(Uint128::zero, |x| x.amount);
+        if reserve.is_zero() {
+            return Err(StdError::generic_err("No liquidity in pool").into());
+        }
+        let amount = assets.find(info).map_or_else(Uint128::zero, |x| x.amount);
+        lp_amount = lp_amount.checked_add(total_share.multiply_ratio(amount, reserve))?;
+    }
+
+    Ok(lp_amount / Uint128::from(pool.pool_assets.len() as u128))
+}
+
+/// Returns an error if `simulated_return` is more than `max_swap_slippage`
+/// below the `predicted_return` of the balancing swap
+fn assert_max_swap_slippage(
+    predicted_return: Uint128,
+    simulated_return: Uint128,
+    max_swap_slippage: Decimal,
+) -> Result<(), ContractError> {
+    let slippage =
+        Decimal::one().saturating_sub(Decimal::from_ratio(simulated_return, predicted_return));
+    if slippage > max_swap_slippage {
+        return Err(ContractError::MaxSwapSlippageExceeded {
+            max_swap_slippage,
+            slippage,
         });
     }
 
-    // Store new cw2 version info
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(())
+}
 
-    Ok(Response::default())
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Decimal, Uint128};
+    use test_case::test_case;
+
+    use super::assert_max_swap_slippage;
+    use crate::error::ContractError;
+
+    #[test_case(1_000, None ; "simulation as predicted")]
+    #[test_case(1_010, None ; "simulation above prediction")]
+    #[test_case(990, None ; "slippage at the max")]
+    #[test_case(989, Some("0.011") ; "slippage above the max")]
+    fn test_assert_max_swap_slippage(simulated_return: u128, expected_slippage: Option<&str>) {
+        let res = assert_max_swap_slippage(
+            Uint128::new(1_000),
+            Uint128::new(simulated_return),
+            Decimal::percent(1),
+        );
+
+        match expected_slippage {
+            None => res.unwrap(),
+            Some(expected_slippage) => assert!(matches!(
+                res.unwrap_err(),
+                ContractError::MaxSwapSlippageExceeded {
+                    max_swap_slippage,
+                    slippage,
+                } if max_swap_slippage == Decimal::percent(1)
+                    && slippage == expected_slippage.parse::<Decimal>().unwrap()
+            )),
+        }
+    }
 }
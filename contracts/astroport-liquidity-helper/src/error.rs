@@ -1,6 +1,10 @@
-use cosmwasm_std::{OverflowError, StdError, Uint128};
+use apollo_cw_asset::AssetInfo;
+use cosmwasm_std::{Addr, Decimal, OverflowError, StdError, Uint128};
 use cw_bigint::TryFromBigIntError;
 use cw_dex::CwDexError;
+use cw_dex_astroport::astroport::factory::PairType;
+use cw_ownable::OwnershipError;
+use liquidity_helper::LiquidityHelperError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,10 +22,13 @@ pub enum ContractError {
     Overflow(#[from] OverflowError),
 
     #[error("{0}")]
-    Semver(#[from] semver::Error),
+    Ownership(#[from] OwnershipError),
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("{0}")]
+    LiquidityHelper(#[from] LiquidityHelperError),
+
+    #[error("No Astroport incentives contract is set to stake LP tokens in")]
+    NoAstroportIncentives {},
 
     #[error("Can't provide liquidity with more than 2 assets")]
     MoreThanTwoAssets {},
@@ -29,9 +36,57 @@ pub enum ContractError {
     #[error("Pair type not supported")]
     UnsupportedPairType {},
 
+    #[error(
+        "Balancing swap slippage of {slippage} exceeds max_swap_slippage of {max_swap_slippage}"
+    )]
+    MaxSwapSlippageExceeded {
+        max_swap_slippage: Decimal,
+        slippage: Decimal,
+    },
+
+    #[error(
+        "Price deviation of {deviation} from the TWAP exceeds max_price_deviation of {max_price_deviation}"
+    )]
+    PriceDeviationExceeded {
+        max_price_deviation: Decimal,
+        deviation: Decimal,
+    },
+
+    #[error(
+        "No price observation of pair {pair_addr} is old enough to calculate a TWAP and recent enough to be used"
+    )]
+    NoPriceObservation { pair_addr: Addr },
+
+    #[error("Pair {pair_addr} is not registered in the Astroport factory")]
+    PairNotRegistered { pair_addr: Addr },
+
+    #[error("LP token mismatch. Expected: {expected}, received: {received}")]
+    LpTokenMismatch {
+        /// The LP token of the pair
+        expected: AssetInfo,
+        /// The LP token of the pool passed in the message
+        received: AssetInfo,
+    },
+
+    #[error("Pair type mismatch. Expected: {expected}, received: {received}")]
+    PairTypeMismatch {
+        /// The pair type of the pair
+        expected: PairType,
+        /// The pair type of the pool passed in the message
+        received: PairType,
+    },
+
+    #[error("Liquidity manager mismatch. Expected: {expected}, received: {received}")]
+    LiquidityManagerMismatch {
+        /// The configured Astroport liquidity manager
+        expected: Addr,
+        /// The liquidity manager of the pool passed in the message
+        received: Addr,
+    },
+
     /// The minimum amount of tokens requested was not returned from the action
     #[error(
-        "Did not receive expected amount of LP tokens. Expected: {min_out}, received: {received}"
+        "Did not receive expected amount of tokens. Expected: {min_out}, received: {received}"
     )]
     MinOutNotReceived {
         /// The minimum amount of tokens the user requested
@@ -39,22 +94,6 @@ pub enum ContractError {
         /// The actual amount of tokens received
         received: Uint128,
     },
-
-    #[error("Can only migrate to a codeID with the correct name. Expected: {expected}, received: {received}")]
-    InvalidContractName {
-        /// The expected contract name
-        expected: String,
-        /// The actual contract name
-        received: String,
-    },
-
-    #[error("Can only migrate to a codeID with a newer version. Old version: {old_version}, new version: {new_version}")]
-    InvalidContractVersion {
-        /// The current contract version
-        old_version: semver::Version,
-        /// The version that the user is trying to migrate to
-        new_version: semver::Version,
-    },
 }
 
 impl From<ContractError> for StdError {
@@ -1,9 +1,12 @@
 //! Module containing implementations of calculations needed for swapping
 
 use apollo_cw_asset::Asset;
+use astroport_v3::pair_concentrated::ConcentratedPoolConfig;
 use astroport_v3::pair_xyk_sale_tax::TaxConfigsChecked;
 use cosmwasm_std::{Decimal, Decimal256, StdError, StdResult, Uint128, Uint256};
 use cw_bigint::BigInt;
+use cw_dex_astroport::astroport::asset::MINIMUM_LIQUIDITY_AMOUNT;
+use cw_dex_astroport::astroport::querier::FeeInfo;
 
 use crate::big_decimal::{bigint_to_u128, BigDecimal};
 
@@ -122,13 +125,516 @@ pub fn calc_xyk_balancing_swap(
     Ok((offer_asset, return_asset))
 }
 
+/// Calculates the amount of LP tokens minted when providing `assets` to a
+/// constant product pool with the given `reserves` and total LP token supply.
+///
+/// Returns `(lp_amount, used_assets): (Uint128, [Uint128; 2])` containing the
+/// amount of LP tokens and the amounts of each asset that will be taken by
+/// the pool.
+pub fn calc_xyk_provide_liquidity(
+    assets: [Uint128; 2],
+    reserves: [Uint128; 2],
+    total_share: Uint128,
+) -> StdResult<(Uint128, [Uint128; 2])> {
+    // Make sure there is liquidity in the pool
+    if reserves[0].is_zero() || reserves[1].is_zero() || total_share.is_zero() {
+        return Err(StdError::generic_err("No liquidity in pool"));
+    }
+
+    let lp_amount = std::cmp::min(
+        assets[0].multiply_ratio(total_share, reserves[0]),
+        assets[1].multiply_ratio(total_share, reserves[1]),
+    );
+    let used_assets = [
+        std::cmp::min(
+            assets[0],
+            lp_amount.multiply_ratio(reserves[0], total_share),
+        ),
+        std::cmp::min(
+            assets[1],
+            lp_amount.multiply_ratio(reserves[1], total_share),
+        ),
+    ];
+
+    Ok((lp_amount, used_assets))
+}
+
+/// Calculates the amounts of `assets` to provide to an empty pool so that the
+/// first pool asset is priced at `price` units of the second. The asset in
+/// excess of this ratio is reduced, the other is used in full.
+pub fn calc_initial_provide_amounts(
+    assets: [Uint128; 2],
+    price: Decimal,
+) -> StdResult<[Uint128; 2]> {
+    if price.is_zero() {
+        return Err(StdError::generic_err(
+            "Initial price must be greater than zero",
+        ));
+    }
+
+    match assets[0].checked_mul_floor(price) {
+        Ok(amount) if amount <= assets[1] => Ok([assets[0], amount]),
+        _ => Ok([
+            assets[1]
+                .checked_div_floor(price)
+                .map_err(|e| StdError::generic_err(e.to_string()))?,
+            assets[1],
+        ]),
+    }
+}
+
+/// The maximum number of iterations of Newton's method in the StableSwap
+/// calculations
+const STABLE_ITERATIONS: u8 = 64;
+
+/// The tolerance used to check if Newton's method has converged. The
+/// StableSwap calculations are done on amounts normalized to the total amount
+/// of assets, so this is relative to the size of the pool.
+const STABLE_TOL: Decimal256 = Decimal256::raw(1_000);
+
+/// The number of rounds of the ternary search for the balancing swap of a
+/// StableSwap pool. Each round narrows the searched range of twice the first
+/// guess to two thirds, so the swap is found to within about 5% of the guess.
+/// The LP tokens received are flat around the best swap, so this loses little.
+const STABLE_SEARCH_ITERATIONS: u8 = 9;
+
+/// The number of assets in a PCL pool
+const N_COINS: Decimal256 = Decimal256::raw(2_000_000_000_000_000_000);
+
+/// Divides two `Decimal256`s, returning a `StdError` on division by zero or
+/// overflow
+fn checked_div(numerator: Decimal256, denominator: Decimal256) -> StdResult<Decimal256> {
+    numerator
+        .checked_div(denominator)
+        .map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+/// Computes the StableSwap invariant D for the reserves `pools` with Newton's
+/// method, in the same way as the Astroport stableswap pair. `leverage` is the
+/// amplification times the number of coins.
+fn compute_d(leverage: Decimal256, pools: &[Decimal256]) -> StdResult<Decimal256> {
+    let n_coins = Decimal256::from_ratio(pools.len() as u128, 1u8);
+    let sum_x = pools
+        .iter()
+        .try_fold(Decimal256::zero(), |sum, x| sum.checked_add(*x))?;
+    if sum_x.is_zero() {
+        return Ok(Decimal256::zero());
+    }
+
+    // d = (leverage * sum_x + d_product * n) * d / ((leverage - 1) * d + (n + 1) * d_product)
+    let mut d = sum_x;
+    for _ in 0..STABLE_ITERATIONS {
+        // d_product = d^(n+1) / (n^n * prod(pools))
+        let d_product = pools.iter().try_fold(d, |d_product, x| {
+            checked_div(d_product.checked_mul(d)?, x.checked_mul(n_coins)?)
+        })?;
+        let numerator = leverage
+            .checked_mul(sum_x)?
+            .checked_add(d_product.checked_mul(n_coins)?)?
+            .checked_mul(d)?;
+        let denominator = leverage
+            .checked_sub(Decimal256::one())?
+            .checked_mul(d)?
+            .checked_add(d_product.checked_mul(n_coins + Decimal256::one())?)?;
+        let d_previous = d;
+        d = checked_div(numerator, denominator)?;
+        if d.abs_diff(d_previous) <= STABLE_TOL {
+            return Ok(d);
+        }
+    }
+
+    Err(StdError::generic_err(
+        "Newton method for D failed to converge",
+    ))
+}
+
+/// Computes the reserve of asset `ask_idx` of a StableSwap pool with invariant
+/// `d` when the reserves of the other assets are those in `pools`, by solving
+/// `y^2 + b*y = c` with Newton's method.
+fn calc_y(
+    leverage: Decimal256,
+    pools: &[Decimal256],
+    ask_idx: usize,
+    d: Decimal256,
+) -> StdResult<Decimal256> {
+    let n_coins = Decimal256::from_ratio(pools.len() as u128, 1u8);
+    let mut sum = Decimal256::zero();
+    let mut c = d;
+    for (i, x) in pools.iter().enumerate() {
+        if i != ask_idx {
+            sum = sum.checked_add(*x)?;
+            c = checked_div(c.checked_mul(d)?, x.checked_mul(n_coins)?)?;
+        }
+    }
+    let c = checked_div(c.checked_mul(d)?, leverage.checked_mul(n_coins)?)?;
+    let b = sum.checked_add(checked_div(d, leverage)?)?;
+
+    let mut y = d;
+    for _ in 0..STABLE_ITERATIONS {
+        let y_previous = y;
+        y = checked_div(
+            y.checked_pow(2)?.checked_add(c)?,
+            (y + y).checked_add(b)?.checked_sub(d)?,
+        )?;
+        if y.abs_diff(y_previous) <= STABLE_TOL {
+            return Ok(y);
+        }
+    }
+
+    Err(StdError::generic_err(
+        "Newton method for y failed to converge",
+    ))
+}
+
+/// Calculates the share of the pool's LP tokens minted when providing
+/// `deposits` to a StableSwap pool with the given `reserves`. The pool is
+/// charged a fee on the imbalanced part of the deposit the same way Curve
+/// does, so for pairs that don't charge this fee the result is a lower bound.
+fn calc_stable_provide_share(
+    leverage: Decimal256,
+    deposits: &[Decimal256],
+    reserves: &[Decimal256],
+    fee: Decimal256,
+) -> StdResult<Decimal256> {
+    let d_before = compute_d(leverage, reserves)?;
+    let new_reserves = deposits
+        .iter()
+        .zip(reserves)
+        .map(|(deposit, reserve)| reserve.checked_add(*deposit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let d_after = compute_d(leverage, &new_reserves)?;
+
+    // fee * n / (4 * (n - 1)) on the difference to a balanced deposit
+    let n_coins = reserves.len() as u128;
+    let imbalance_fee = fee * Decimal256::from_ratio(n_coins, 4 * (n_coins - 1));
+    let mut adjusted_reserves = new_reserves;
+    for (adjusted, reserve) in adjusted_reserves.iter_mut().zip(reserves) {
+        let ideal = checked_div(reserve.checked_mul(d_after)?, d_before)?;
+        *adjusted = adjusted.saturating_sub(ideal.abs_diff(*adjusted) * imbalance_fee);
+    }
+    let d_adjusted = compute_d(leverage, &adjusted_reserves)?;
+
+    checked_div(d_adjusted.saturating_sub(d_before), d_before)
+}
+
+/// Calculates the amount of LP tokens minted when first swapping `offer_amount`
+/// of `assets[offer_idx]` to `assets[ask_idx]` and then providing all assets to
+/// a StableSwap pool. All amounts are normalized to the total amount of assets.
+/// `d` is the invariant of `reserves`.
+fn calc_stable_swap_and_provide(
+    leverage: Decimal256,
+    (offer_idx, ask_idx): (usize, usize),
+    offer_amount: Decimal256,
+    assets: &[Decimal256],
+    (reserves, d): (&[Decimal256], Decimal256),
+    fee_info: &FeeInfo,
+) -> StdResult<(Decimal256, Decimal256)> {
+    let fee = Decimal256::from(fee_info.total_fee_rate);
+    let mut deposits = assets.to_vec();
+    let mut reserves = reserves.to_vec();
+    let return_amount = if offer_amount.is_zero() {
+        Decimal256::zero()
+    } else {
+        reserves[offer_idx] = reserves[offer_idx].checked_add(offer_amount)?;
+        let new_ask_reserve = calc_y(leverage, &reserves, ask_idx, d)?;
+        let return_amount = reserves[ask_idx].saturating_sub(new_ask_reserve);
+        let commission_amount = return_amount * fee;
+        let maker_fee_amount = commission_amount * Decimal256::from(fee_info.maker_fee_rate);
+        let return_amount = return_amount - commission_amount;
+        reserves[ask_idx] = reserves[ask_idx].saturating_sub(return_amount + maker_fee_amount);
+        return_amount
+    };
+
+    deposits[offer_idx] = deposits[offer_idx].saturating_sub(offer_amount);
+    deposits[ask_idx] = deposits[ask_idx].checked_add(return_amount)?;
+    let share = calc_stable_provide_share(leverage, &deposits, &reserves, fee)?;
+
+    Ok((return_amount, share))
+}
+
+/// For a StableSwap pool, calculates how much of one asset to swap to another
+/// before providing liquidity in order to receive the most LP tokens. Providing
+/// imbalanced liquidity to a StableSwap pool is charged a fee, which can be
+/// reduced by first swapping some of the asset in excess.
+///
+/// `assets` and `reserves` are in the order of the pool's assets, with zero
+/// amounts for assets that are not provided. The swap is from the asset that
+/// makes up the largest share of the assets compared to the reserves to the
+/// asset that makes up the smallest. The first guess for the swap amount is
+/// the amount that balances the assets if swapped at a price of one, which is
+/// close to the price of a StableSwap pool. It is refined by a ternary search
+/// with a fixed number of rounds over the amount of LP tokens received, where
+/// each step solves the StableSwap invariant with Newton's method, to keep the
+/// gas used bounded. `amp` is the amplification of
+/// the pool as returned in the pair's config, and `raw_provide_lp` is the
+/// amount of LP tokens the pool mints for providing `assets` as they are.
+///
+/// Returns `Some((offer_asset, return_asset))` if swapping is estimated to
+/// yield more LP tokens than `raw_provide_lp`, and `None` otherwise.
+pub fn calc_stable_balancing_swap(
+    assets: &[Asset],
+    reserves: &[Uint128],
+    total_share: Uint128,
+    raw_provide_lp: Uint128,
+    amp: Decimal,
+    fee_info: &FeeInfo,
+) -> StdResult<Option<(Asset, Asset)>> {
+    if assets.len() != reserves.len()
+        || reserves.iter().any(Uint128::is_zero)
+        || total_share.is_zero()
+    {
+        return Ok(None);
+    }
+
+    let share_of_reserve = |i: &usize| Decimal256::from_ratio(assets[*i].amount, reserves[*i]);
+    let (Some(offer_idx), Some(ask_idx)) = (
+        (0..assets.len()).max_by_key(share_of_reserve),
+        (0..assets.len()).min_by_key(share_of_reserve),
+    ) else {
+        return Ok(None);
+    };
+    if offer_idx == ask_idx || assets[offer_idx].amount.is_zero() {
+        return Ok(None);
+    }
+
+    // Normalize all amounts to the total amount of assets, to keep the powers
+    // in Newton's method from overflowing
+    let scale = assets
+        .iter()
+        .map(|x| x.amount)
+        .chain(reserves.iter().copied())
+        .fold(Uint256::zero(), |sum, x| sum + Uint256::from(x));
+    let normalize = |x: Uint128| Decimal256::from_ratio(x, scale);
+    let normalized_assets = assets
+        .iter()
+        .map(|x| normalize(x.amount))
+        .collect::<Vec<_>>();
+    let normalized_reserves = reserves.iter().copied().map(normalize).collect::<Vec<_>>();
+    let leverage =
+        Decimal256::from(amp).checked_mul(Decimal256::from_ratio(assets.len() as u128, 1u8))?;
+    let d = compute_d(leverage, &normalized_reserves)?;
+
+    let lp_for = |offer_amount: Uint128| -> StdResult<(Uint128, Uint128)> {
+        let (return_amount, share) = calc_stable_swap_and_provide(
+            leverage,
+            (offer_idx, ask_idx),
+            normalize(offer_amount),
+            &normalized_assets,
+            (&normalized_reserves, d),
+            fee_info,
+        )?;
+        Ok((
+            scale.mul_floor(return_amount).try_into()?,
+            Uint256::from(total_share).mul_floor(share).try_into()?,
+        ))
+    };
+
+    // The amount x that balances the assets at a price of one, ignoring the
+    // change of the reserves: (offer - x) / offer_reserve = (ask + x) / ask_reserve
+    let (offer, ask) = (assets[offer_idx].amount, assets[ask_idx].amount);
+    let (offer_reserve, ask_reserve) = (reserves[offer_idx], reserves[ask_idx]);
+    let guess = Uint256::from(offer)
+        .checked_mul(ask_reserve.into())?
+        .saturating_sub(Uint256::from(ask).checked_mul(offer_reserve.into())?)
+        .checked_div(Uint256::from(offer_reserve) + Uint256::from(ask_reserve))?;
+    let guess: Uint128 = guess.try_into()?;
+
+    // Ternary search for the swap amount giving the most LP tokens between
+    // zero and twice the guess, to allow for the price differing from one
+    let mut low = Uint128::zero();
+    let mut high = guess.saturating_mul(Uint128::new(2)).min(offer);
+    for _ in 0..STABLE_SEARCH_ITERATIONS {
+        let third = (high - low) / Uint128::new(3);
+        if third.is_zero() {
+            break;
+        }
+        let (_, lp_low) = lp_for(low + third)?;
+        let (_, lp_high) = lp_for(high - third)?;
+        if lp_low < lp_high {
+            low += third;
+        } else {
+            high -= third;
+        }
+    }
+    let offer_amount = low + (high - low) / Uint128::new(2);
+    let (return_amount, lp_amount) = lp_for(offer_amount)?;
+
+    if offer_amount.is_zero() || return_amount.is_zero() || lp_amount <= raw_provide_lp {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        Asset::new(assets[offer_idx].info.clone(), offer_amount),
+        Asset::new(assets[ask_idx].info.clone(), return_amount),
+    )))
+}
+
+/// Calculates the amount of LP tokens minted when providing `assets` to a
+/// StableSwap pool with the given `reserves`, amplification and total LP
+/// token supply. `assets` and `reserves` are in the order of the pool's
+/// assets. Like [`calc_stable_balancing_swap`] this includes a fee on
+/// imbalanced deposits.
+pub fn calc_stable_provide_liquidity(
+    assets: &[Uint128],
+    reserves: &[Uint128],
+    total_share: Uint128,
+    amp: Decimal,
+    fee_info: &FeeInfo,
+) -> StdResult<Uint128> {
+    if reserves.iter().any(Uint128::is_zero) || total_share.is_zero() {
+        return Err(StdError::generic_err("No liquidity in pool"));
+    }
+    if assets.len() != reserves.len() {
+        return Err(StdError::generic_err(
+            "Number of assets doesn't match the pool",
+        ));
+    }
+
+    let scale = assets
+        .iter()
+        .chain(reserves)
+        .fold(Uint256::zero(), |sum, x| sum + Uint256::from(*x));
+    let normalize = |x: &Uint128| Decimal256::from_ratio(*x, scale);
+    let share = calc_stable_provide_share(
+        Decimal256::from(amp).checked_mul(Decimal256::from_ratio(assets.len() as u128, 1u8))?,
+        &assets.iter().map(normalize).collect::<Vec<_>>(),
+        &reserves.iter().map(normalize).collect::<Vec<_>>(),
+        Decimal256::from(fee_info.total_fee_rate),
+    )?;
+
+    Ok(Uint256::from(total_share).mul_floor(share).try_into()?)
+}
+
+/// Below this weight the PCL fee is charged fully at `out_fee`
+const PCL_FEE_TOL: Decimal256 = Decimal256::raw(1_000_000_000_000_000);
+
+/// Calculates the fee rate of an Astroport PCL pool with the price scaled
+/// reserves `xp`, in the same way as the pair. The fee moves from `mid_fee`
+/// towards `out_fee` the further the pool is from being balanced.
+fn pcl_fee(xp: [Decimal256; 2], params: &ConcentratedPoolConfig) -> Decimal256 {
+    let fee_gamma = Decimal256::from(params.fee_gamma);
+    let sum = xp[0] + xp[1];
+    if sum.is_zero() {
+        return params.out_fee.into();
+    }
+    let k = xp[0] * xp[1] * N_COINS * N_COINS / (sum * sum);
+    let mut k = fee_gamma / (fee_gamma + Decimal256::one() - k);
+    if k <= PCL_FEE_TOL {
+        k = Decimal256::zero();
+    }
+
+    k * Decimal256::from(params.mid_fee)
+        + (Decimal256::one() - k) * Decimal256::from(params.out_fee)
+}
+
+/// Calculates the value of `deposits` to an Astroport PCL pool with the given
+/// `reserves`, in units of the first asset at the pool's price scale, minus the
+/// fee that the pair charges on the imbalanced part of a provide. Comparing this
+/// for different deposits tells which one gives more LP tokens, without
+/// solving the pool's invariant.
+pub fn calc_pcl_provide_value(
+    deposits: [Uint128; 2],
+    reserves: [Uint128; 2],
+    params: &ConcentratedPoolConfig,
+) -> StdResult<Decimal256> {
+    let price_scale = Decimal256::from(params.price_scale);
+    let ideposits = [
+        Decimal256::from_ratio(deposits[0], 1u8),
+        Decimal256::from_ratio(deposits[1], 1u8).checked_mul(price_scale)?,
+    ];
+    let new_xp = [
+        Decimal256::from_ratio(reserves[0], 1u8).checked_add(ideposits[0])?,
+        Decimal256::from_ratio(reserves[1], 1u8)
+            .checked_mul(price_scale)?
+            .checked_add(ideposits[1])?,
+    ];
+
+    let value = ideposits[0].checked_add(ideposits[1])?;
+    if value.is_zero() {
+        return Ok(Decimal256::zero());
+    }
+    let avg = value / N_COINS;
+    let provide_fee = ideposits[0].abs_diff(avg) * pcl_fee(new_xp, params) / value;
+
+    Ok(value * (Decimal256::one() - provide_fee))
+}
+
+/// Estimates the amount of LP tokens minted by the first provide of `deposits`
+/// to an empty Astroport PCL pool. The pair mints the geometric mean of the
+/// deposits valued at its price scale, minus the minimum liquidity it keeps.
+/// The estimate is exact for deposits in the ratio of the price scale and an
+/// upper bound otherwise.
+pub fn calc_pcl_initial_provide_liquidity(
+    deposits: [Uint128; 2],
+    params: &ConcentratedPoolConfig,
+) -> StdResult<Uint128> {
+    let price_scale = Decimal256::from(params.price_scale);
+    let value = Decimal256::from_ratio(deposits[0], 1u8)
+        .checked_add(Decimal256::from_ratio(deposits[1], 1u8).checked_mul(price_scale)?)?;
+    let xcp = checked_div(value, N_COINS.checked_mul(price_scale.sqrt())?)?;
+    let lp_amount: Uint128 = xcp.to_uint_floor().try_into()?;
+
+    Ok(lp_amount.saturating_sub(MINIMUM_LIQUIDITY_AMOUNT))
+}
+
+/// For an Astroport PCL pool, calculates how much of one asset to swap to the
+/// other so that the assets have the same value at the pool's price scale.
+/// Providing balanced assets avoids the fee the pair charges on imbalanced
+/// provides, but the swap itself is charged a fee, so whether to swap is
+/// decided by comparing [`calc_pcl_provide_value`] before and after the swap.
+///
+/// Returns the asset to offer, or `None` if the assets are already balanced.
+pub fn calc_pcl_balancing_swap(
+    assets: [Asset; 2],
+    params: &ConcentratedPoolConfig,
+) -> StdResult<Option<Asset>> {
+    let price_scale = Decimal256::from(params.price_scale);
+    if price_scale.is_zero() {
+        return Ok(None);
+    }
+    let values = [
+        Decimal256::from_ratio(assets[0].amount, 1u8),
+        Decimal256::from_ratio(assets[1].amount, 1u8).checked_mul(price_scale)?,
+    ];
+    let (offer_idx, ask_idx) = if values[0] > values[1] {
+        (0, 1)
+    } else {
+        (1, 0)
+    };
+
+    // Swap half of the difference in value, converted to the offer asset
+    let offer_value = (values[offer_idx] - values[ask_idx]) / N_COINS;
+    let offer_amount = if offer_idx == 0 {
+        offer_value
+    } else {
+        checked_div(offer_value, price_scale)?
+    };
+    let offer_amount: Uint128 = offer_amount.to_uint_floor().try_into()?;
+    if offer_amount.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some(Asset::new(
+        assets[offer_idx].info.clone(),
+        offer_amount,
+    )))
+}
+
 #[cfg(test)]
 mod test {
     use apollo_cw_asset::{Asset, AssetInfo};
     use cosmwasm_std::{Decimal, Uint128};
+    use std::str::FromStr;
     use test_case::test_case;
 
-    use crate::math::calc_xyk_balancing_swap;
+    use crate::math::{
+        calc_initial_provide_amounts, calc_pcl_balancing_swap, calc_pcl_initial_provide_liquidity,
+        calc_pcl_provide_value, calc_stable_balancing_swap, calc_stable_provide_liquidity,
+        calc_xyk_balancing_swap, calc_xyk_provide_liquidity,
+    };
+    use astroport_v3::pair_concentrated::ConcentratedPoolConfig;
+    use cosmwasm_std::Decimal256;
+    use cw_dex_astroport::astroport::querier::FeeInfo;
 
     /// Assert that two Decimals are almost the same (diff smaller than one
     /// permille)
@@ -274,4 +780,349 @@ mod test {
             return_asset.amount,
         );
     }
+
+    #[test_case(
+        [Uint128::new(1_000), Uint128::new(2_000)],
+        [Uint128::new(1_000_000), Uint128::new(2_000_000)],
+        Uint128::new(1_000_000),
+        Uint128::new(1_000),
+        [Uint128::new(1_000), Uint128::new(2_000)];
+        "Assets in same ratio as pool"
+    )]
+    #[test_case(
+        [Uint128::new(1_000), Uint128::new(5_000)],
+        [Uint128::new(1_000_000), Uint128::new(2_000_000)],
+        Uint128::new(1_000_000),
+        Uint128::new(1_000),
+        [Uint128::new(1_000), Uint128::new(2_000)];
+        "Excess of second asset is not used"
+    )]
+    #[test_case(
+        [Uint128::new(1_000), Uint128::zero()],
+        [Uint128::new(1_000_000), Uint128::new(2_000_000)],
+        Uint128::new(1_000_000),
+        Uint128::zero(),
+        [Uint128::zero(), Uint128::zero()];
+        "One asset zero"
+    )]
+    fn test_calc_xyk_provide_liquidity(
+        assets: [Uint128; 2],
+        reserves: [Uint128; 2],
+        total_share: Uint128,
+        expected_lp_amount: Uint128,
+        expected_used: [Uint128; 2],
+    ) {
+        let (lp_amount, used) = calc_xyk_provide_liquidity(assets, reserves, total_share).unwrap();
+
+        assert_eq!(lp_amount, expected_lp_amount);
+        assert_eq!(used, expected_used);
+    }
+
+    #[test_case([1_000, 5_000], "2", [1_000, 2_000] ; "Excess of second asset")]
+    #[test_case([1_000, 1_000], "2", [500, 1_000] ; "Excess of first asset")]
+    #[test_case([1_000, 2_000], "2", [1_000, 2_000] ; "Assets at the price")]
+    #[test_case([1_000, 0], "2", [0, 0] ; "Second asset zero")]
+    fn test_calc_initial_provide_amounts(assets: [u128; 2], price: &str, expected: [u128; 2]) {
+        let amounts = calc_initial_provide_amounts(
+            assets.map(Uint128::new),
+            Decimal::from_str(price).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(amounts, expected.map(Uint128::new));
+    }
+
+    #[test]
+    fn test_calc_initial_provide_amounts_zero_price() {
+        let res = calc_initial_provide_amounts([Uint128::new(1_000); 2], Decimal::zero());
+
+        assert!(res.is_err());
+    }
+
+    #[test_case([1_000_000, 1_000_000], "1", 999_000 ; "Balanced at price scale one")]
+    #[test_case([4_000_000, 1_000_000], "4", 1_999_000 ; "Balanced at price scale four")]
+    #[test_case([1_000, 1_000], "1", 0 ; "Less than the minimum liquidity")]
+    fn test_calc_pcl_initial_provide_liquidity(
+        deposits: [u128; 2],
+        price_scale: &str,
+        expected_lp_amount: u128,
+    ) {
+        let lp_amount = calc_pcl_initial_provide_liquidity(
+            deposits.map(Uint128::new),
+            &pcl_params(Decimal::from_str(price_scale).unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(lp_amount, Uint128::new(expected_lp_amount));
+    }
+
+    fn stable_fee_info() -> FeeInfo {
+        FeeInfo {
+            fee_address: None,
+            total_fee_rate: Decimal::bps(5),
+            maker_fee_rate: Decimal::zero(),
+        }
+    }
+
+    fn uint128s(amounts: &[u128]) -> Vec<Uint128> {
+        amounts.iter().copied().map(Uint128::new).collect()
+    }
+
+    #[test_case(
+        &[1_000, 1_000],
+        &[1_000_000, 1_000_000],
+        Uint128::new(2_000);
+        "Balanced deposit into balanced pool"
+    )]
+    #[test_case(
+        &[2_000, 6_000],
+        &[1_000_000, 3_000_000],
+        Uint128::new(4_000);
+        "Deposit in same ratio as imbalanced pool"
+    )]
+    #[test_case(
+        &[1_000, 1_000, 1_000],
+        &[1_000_000, 1_000_000, 1_000_000],
+        Uint128::new(2_000);
+        "Balanced deposit into balanced three asset pool"
+    )]
+    #[test_case(
+        &[1_000, 2_000, 3_000],
+        &[1_000_000, 2_000_000, 3_000_000],
+        Uint128::new(2_000);
+        "Deposit in same ratio as imbalanced three asset pool"
+    )]
+    fn test_calc_stable_provide_liquidity(
+        assets: &[u128],
+        reserves: &[u128],
+        expected_lp_amount: Uint128,
+    ) {
+        let total_share = Uint128::new(2_000_000);
+        let lp_amount = calc_stable_provide_liquidity(
+            &uint128s(assets),
+            &uint128s(reserves),
+            total_share,
+            Decimal::from_ratio(10u8, 1u8),
+            &stable_fee_info(),
+        )
+        .unwrap();
+
+        // Allow for rounding down
+        assert!(lp_amount <= expected_lp_amount);
+        assert!(lp_amount + Uint128::new(2) >= expected_lp_amount);
+    }
+
+    #[test_case(
+        &[500_000, 500_000],
+        &[1_000_000, 0];
+        "Two asset pool"
+    )]
+    #[test_case(
+        &[500_000, 500_000, 500_000],
+        &[1_500_000, 0, 0];
+        "Three asset pool"
+    )]
+    fn test_calc_stable_provide_liquidity_charges_imbalance_fee(
+        balanced: &[u128],
+        imbalanced: &[u128],
+    ) {
+        let reserves = vec![Uint128::new(1_000_000_000); balanced.len()];
+        let total_share = Uint128::new(2_000_000_000);
+        let amp = Decimal::from_ratio(10u8, 1u8);
+
+        let balanced = calc_stable_provide_liquidity(
+            &uint128s(balanced),
+            &reserves,
+            total_share,
+            amp,
+            &stable_fee_info(),
+        )
+        .unwrap();
+        let imbalanced = calc_stable_provide_liquidity(
+            &uint128s(imbalanced),
+            &reserves,
+            total_share,
+            amp,
+            &stable_fee_info(),
+        )
+        .unwrap();
+
+        assert!(imbalanced < balanced);
+    }
+
+    #[test_case(
+        &[1_000_000, 1_000_000],
+        &[1_000_000_000, 1_000_000_000],
+        false;
+        "Balanced deposit should not swap"
+    )]
+    #[test_case(
+        &[1_000_000_000, 0],
+        &[1_000_000_000, 1_000_000_000],
+        true;
+        "Single sided deposit into balanced pool"
+    )]
+    #[test_case(
+        &[0, 5_000_000_000],
+        &[1_000_000_000, 3_000_000_000],
+        true;
+        "Single sided deposit of second asset into imbalanced pool"
+    )]
+    #[test_case(
+        &[1_000_000, 1_000_000],
+        &[0, 0],
+        false;
+        "Empty pool should not swap"
+    )]
+    #[test_case(
+        &[1_000_000, 1_000_000, 1_000_000],
+        &[1_000_000_000, 1_000_000_000, 1_000_000_000],
+        false;
+        "Balanced deposit into three asset pool should not swap"
+    )]
+    // With three or more assets a single swap reduces the fee on the
+    // imbalanced deposit by less than the fee charged on the swap
+    #[test_case(
+        &[1_000_000_000, 0, 0],
+        &[1_000_000_000, 1_000_000_000, 1_000_000_000],
+        false;
+        "Single sided deposit into three asset pool should not swap"
+    )]
+    #[test_case(
+        &[0, 1_000_000_000, 2_000_000_000],
+        &[1_000_000_000, 1_000_000_000, 2_000_000_000],
+        false;
+        "Deposit missing one asset of three asset pool should not swap"
+    )]
+    fn test_calc_stable_balancing_swap(assets: &[u128], reserves: &[u128], should_swap: bool) {
+        let assets = ["uatom", "uosmo", "uluna"]
+            .iter()
+            .zip(uint128s(assets))
+            .map(|(denom, amount)| Asset::new(AssetInfo::native(*denom), amount))
+            .collect::<Vec<_>>();
+        let amounts = assets.iter().map(|x| x.amount).collect::<Vec<_>>();
+        let reserves = uint128s(reserves);
+        let total_share = reserves.iter().sum();
+        let amp = Decimal::from_ratio(10u8, 1u8);
+        let fee_info = stable_fee_info();
+
+        // Fails for the empty pool
+        let raw_provide_lp =
+            calc_stable_provide_liquidity(&amounts, &reserves, total_share, amp, &fee_info)
+                .unwrap_or_default();
+
+        let swap = calc_stable_balancing_swap(
+            &assets,
+            &reserves,
+            total_share,
+            raw_provide_lp,
+            amp,
+            &fee_info,
+        )
+        .unwrap();
+        let Some((offer_asset, return_asset)) = swap else {
+            assert!(!should_swap);
+            return;
+        };
+        assert!(should_swap);
+
+        // Should yield more LP tokens than providing the assets as they are
+        let offer_idx = assets
+            .iter()
+            .position(|x| x.info == offer_asset.info)
+            .unwrap();
+        let ask_idx = assets
+            .iter()
+            .position(|x| x.info == return_asset.info)
+            .unwrap();
+        let mut swapped_assets = amounts.clone();
+        let mut swapped_reserves = reserves.clone();
+        swapped_assets[offer_idx] -= offer_asset.amount;
+        swapped_assets[ask_idx] += return_asset.amount;
+        swapped_reserves[offer_idx] += offer_asset.amount;
+        swapped_reserves[ask_idx] -= return_asset.amount;
+        let lp_amount = calc_stable_provide_liquidity(
+            &swapped_assets,
+            &swapped_reserves,
+            total_share,
+            amp,
+            &fee_info,
+        )
+        .unwrap();
+        assert!(lp_amount > raw_provide_lp);
+    }
+
+    fn pcl_params(price_scale: Decimal) -> ConcentratedPoolConfig {
+        ConcentratedPoolConfig {
+            amp: Decimal::from_ratio(40u8, 1u8),
+            gamma: Decimal::from_str("0.000145").unwrap(),
+            mid_fee: Decimal::from_str("0.0026").unwrap(),
+            out_fee: Decimal::from_str("0.0045").unwrap(),
+            fee_gamma: Decimal::from_str("0.00023").unwrap(),
+            repeg_profit_threshold: Decimal::from_str("0.000002").unwrap(),
+            min_price_scale_delta: Decimal::from_str("0.000146").unwrap(),
+            price_scale,
+            ma_half_time: 600,
+            track_asset_balances: false,
+            fee_share: None,
+        }
+    }
+
+    #[test_case(
+        [Uint128::new(2_000), Uint128::new(1_000)],
+        Decimal::zero();
+        "Balanced deposit is not charged"
+    )]
+    #[test_case(
+        [Uint128::new(4_000), Uint128::zero()],
+        Decimal::from_str("0.0013").unwrap();
+        "Single sided deposit is charged half the fee"
+    )]
+    #[test_case(
+        [Uint128::zero(), Uint128::new(2_000)],
+        Decimal::from_str("0.0013").unwrap();
+        "Single sided deposit of second asset is charged half the fee"
+    )]
+    fn test_calc_pcl_provide_value(deposits: [Uint128; 2], expected_fee: Decimal) {
+        let reserves = [Uint128::new(2_000_000_000), Uint128::new(1_000_000_000)];
+        let params = pcl_params(Decimal::from_ratio(2u8, 1u8));
+
+        let value = calc_pcl_provide_value(deposits, reserves, &params).unwrap();
+
+        // The pool is close to balanced so the fee is close to `mid_fee`
+        let total_value = Decimal256::from_ratio(4_000u128, 1u8);
+        let expected_value = total_value * (Decimal256::one() - Decimal256::from(expected_fee));
+        assert!(value <= total_value);
+        assert!(value.abs_diff(expected_value) < Decimal256::percent(1));
+    }
+
+    #[test_case(
+        [Uint128::new(4_000), Uint128::zero()],
+        Some((0, Uint128::new(2_000)));
+        "Swap half of the first asset"
+    )]
+    #[test_case(
+        [Uint128::zero(), Uint128::new(2_000)],
+        Some((1, Uint128::new(1_000)));
+        "Swap half of the second asset"
+    )]
+    #[test_case(
+        [Uint128::new(2_000), Uint128::new(1_000)],
+        None;
+        "Balanced assets"
+    )]
+    fn test_calc_pcl_balancing_swap(assets: [Uint128; 2], expected: Option<(usize, Uint128)>) {
+        let assets = [
+            Asset::new(AssetInfo::native("uatom"), assets[0]),
+            Asset::new(AssetInfo::native("uosmo"), assets[1]),
+        ];
+        let params = pcl_params(Decimal::from_ratio(2u8, 1u8));
+
+        let offer_asset = calc_pcl_balancing_swap(assets.clone(), &params).unwrap();
+
+        assert_eq!(
+            offer_asset,
+            expected.map(|(idx, amount)| Asset::new(assets[idx].info.clone(), amount))
+        );
+    }
 }
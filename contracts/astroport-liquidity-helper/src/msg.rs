@@ -1,40 +1,60 @@
+use apollo_cw_asset::AssetListUnchecked;
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{to_json_binary, Addr, CosmosMsg, Env, StdResult, Uint128, WasmMsg};
-use cw_dex_astroport::AstroportPool;
-use liquidity_helper::msg::ExecuteMsg as GenericExecuteMsg;
+use cosmwasm_std::{Addr, Decimal};
+use cw_ownable::cw_ownable_query;
+use liquidity_helper::msg::{
+    PauseInfoResponse, PoolInput, SimulateBalancingProvideLiquidityResponse,
+};
+
+pub use liquidity_helper::msg::ExecuteMsg;
 
 #[cw_serde]
 pub struct InstantiateMsg {
+    /// The owner of the contract, who can sweep assets held by it
+    pub owner: String,
     pub astroport_factory: String,
+    /// The liquidity manager used for pools given by their pair address
+    pub astroport_liquidity_manager: String,
+    /// The incentives contract that LP tokens are staked in with
+    /// `PostProvideAction::Stake`
+    pub astroport_incentives: Option<String>,
 }
 
-pub type ExecuteMsg = GenericExecuteMsg<CallbackMsg>;
-
-#[cw_serde]
-pub enum CallbackMsg {
-    ReturnLpTokens {
-        pool: AstroportPool,
-        balance_before: Uint128,
-        recipient: Addr,
-    },
-}
-
-impl CallbackMsg {
-    pub fn into_cosmos_msg(&self, env: &Env) -> StdResult<CosmosMsg> {
-        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: env.contract.address.to_string(),
-            msg: to_json_binary(&ExecuteMsg::Callback(self.clone()))?,
-            funds: vec![],
-        }))
-    }
-}
-
+#[cw_ownable_query]
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     #[returns(Addr)]
     AstroportFactory {},
+    #[returns(Addr)]
+    AstroportLiquidityManager {},
+    #[returns(Option<Addr>)]
+    AstroportIncentives {},
+    /// Same as `liquidity_helper::msg::QueryMsg::SimulateBalancingProvideLiquidity`
+    #[returns(SimulateBalancingProvideLiquidityResponse)]
+    SimulateBalancingProvideLiquidity {
+        assets: AssetListUnchecked,
+        pool: PoolInput,
+        initial_price: Option<Decimal>,
+    },
+    /// Same as `liquidity_helper::msg::QueryMsg::PauseInfo`
+    #[returns(PauseInfoResponse)]
+    PauseInfo {},
+    /// Same as `liquidity_helper::msg::QueryMsg::Router`
+    #[returns(Option<Addr>)]
+    Router {},
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// Sets the liquidity manager used for pools given by their pair address,
+    /// which contracts instantiated before it was added don't have.
+    pub astroport_liquidity_manager: Option<String>,
+    /// Sets the incentives contract that LP tokens are staked in with
+    /// `PostProvideAction::Stake`
+    pub astroport_incentives: Option<String>,
+    /// Sets the owner of the contract, which contracts instantiated before
+    /// ownership was added don't have. Fails with `OwnerAlreadySet` if the
+    /// contract has an owner.
+    pub owner: Option<String>,
+}
@@ -1,4 +1,50 @@
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use apollo_cw_asset::AssetList;
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Uint128};
+use cw_dex_astroport::AstroportPool;
+use cw_storage_plus::{Item, Map};
+use liquidity_helper::msg::{BalancingSwap, PostProvideAction};
 
 pub const ASTROPORT_FACTORY: Item<Addr> = Item::new("astroport_factory");
+pub const ASTROPORT_LIQUIDITY_MANAGER: Item<Addr> = Item::new("astroport_liquidity_manager");
+pub const ASTROPORT_INCENTIVES: Item<Addr> = Item::new("astroport_incentives");
+
+/// The cumulative price of the first pool asset in units of the second, as
+/// reported by the pair, at the given time
+#[cw_serde]
+pub struct PriceObservation {
+    pub timestamp: u64,
+    pub price_cumulative: Uint128,
+}
+
+/// The most recent price observations of a pair, which its time weighted
+/// average price is calculated from
+#[cw_serde]
+pub struct PriceObservations {
+    /// The observation before `latest`, at least `TWAP_WINDOW` older than it
+    pub previous: Option<PriceObservation>,
+    pub latest: PriceObservation,
+}
+
+pub const PRICE_OBSERVATIONS: Map<&Addr, PriceObservations> = Map::new("price_observations");
+
+/// State of a `BalancingProvideLiquidity` that is waiting for the reply to the
+/// provide liquidity submessage. Removed again in the reply.
+#[cw_serde]
+pub struct ProvideLiquidityState {
+    pub pool: AstroportPool,
+    /// The LP token balance of the contract before providing liquidity
+    pub lp_balance_before: Uint128,
+    /// The balances of the pool assets held by the contract before the assets
+    /// were received
+    pub balances_before: AssetList,
+    /// The balancing swap done before providing liquidity, if any
+    pub swapped: Option<BalancingSwap>,
+    /// Assets that were returned to the recipient instead of being provided
+    pub leftover: AssetList,
+    pub recipient: Addr,
+    pub post_provide: Option<PostProvideAction>,
+}
+
+pub const PROVIDE_LIQUIDITY_STATE: Item<ProvideLiquidityState> =
+    Item::new("provide_liquidity_state");
@@ -1,20 +1,32 @@
 use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 
+use astroport_liquidity_helper::contract::{migrate, MAX_OBSERVATION_AGE, TWAP_WINDOW};
 use astroport_liquidity_helper::math::calc_xyk_balancing_swap;
-use astroport_liquidity_helper::msg::InstantiateMsg;
-use cosmwasm_std::{assert_approx_eq, coin, to_json_binary, Addr, Coin, Decimal, Uint128};
+use astroport_liquidity_helper::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::testing::{mock_dependencies, mock_env};
+use cosmwasm_std::{
+    assert_approx_eq, coin, to_json_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut,
+    Env, Event, MessageInfo, Reply, Response, StdError, StdResult, SubMsgResponse, SubMsgResult,
+    Uint128, WasmMsg,
+};
 use cw20::{AllowanceResponse, BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
 use cw_dex_astroport::astroport::asset::{Asset as AstroAsset, AssetInfo as AstroAssetInfo};
 use cw_dex_astroport::astroport::factory::{
     ExecuteMsg as FactoryExecuteMsg, FeeInfoResponse, PairType,
 };
+use cw_dex_astroport::astroport::liquidity_manager::QueryMsg as LiquidityManagerQueryMsg;
 use cw_dex_astroport::astroport::pair::{
     ExecuteMsg as PairExecuteMsg, PoolResponse, QueryMsg as PairQueryMsg, SimulationResponse,
-    StablePoolParams,
+    StablePoolParams, MAX_ALLOWED_SLIPPAGE,
 };
 use cw_dex_astroport::astroport::pair_concentrated::ConcentratedPoolParams;
+use cw_dex_astroport::astroport_v5::incentives::QueryMsg as IncentivesQueryMsg;
 use cw_dex_astroport::{astroport, AstroportPool};
-use cw_it::astroport::astroport::factory::{PairConfig, QueryMsg as FactoryQueryMsg};
+use cw_dex_router::msg::{ExecuteMsg as RouterExecuteMsg, QueryMsg as RouterQueryMsg};
+use cw_it::astroport::astroport::factory::{
+    ConfigResponse as FactoryConfigResponse, PairConfig, QueryMsg as FactoryQueryMsg,
+};
 use cw_it::astroport::astroport_v3::pair_xyk_sale_tax::{SaleTaxInitParams, TaxConfig};
 use cw_it::astroport::utils::{
     create_astroport_pair, get_local_contracts, setup_astroport, AstroportContracts,
@@ -27,8 +39,14 @@ use cw_it::osmosis_std::types::cosmos::bank::v1beta1::QueryBalanceRequest;
 use cw_it::test_tube::{Account, Bank, Module, Runner, SigningAccount, Wasm};
 use cw_it::traits::CwItRunner;
 use cw_it::{ContractType, OwnedTestRunner, TestRunner};
+use cw_ownable::{Action, Ownership};
+use cw_storage_plus::{Item, Map};
+use cw_vault_standard::{VaultInfoResponse, VaultStandardExecuteMsg, VaultStandardQueryMsg};
+use liquidity_helper::msg::{
+    PauseInfoResponse, PoolIdentifier, PostProvideAction, SimulateBalancingProvideLiquidityResponse,
+};
 use liquidity_helper::LiquidityHelper;
-use test_case::test_matrix;
+use test_case::{test_case, test_matrix};
 
 #[cfg(feature = "osmosis-test-tube")]
 use cw_it::osmosis_test_tube::OsmosisTestApp;
@@ -115,13 +133,14 @@ where
         TestRunner::OsmosisTestApp(_) => ContractType::Artifact(Artifact::Local(
             ASTROPORT_LIQUIDITY_HELPER_WASM_FILE.to_string(),
         )),
-        TestRunner::MultiTest(_) => {
-            ContractType::MultiTestContract(Box::new(ContractWrapper::new(
+        TestRunner::MultiTest(_) => ContractType::MultiTestContract(Box::new(
+            ContractWrapper::new(
                 astroport_liquidity_helper::contract::execute,
                 astroport_liquidity_helper::contract::instantiate,
                 astroport_liquidity_helper::contract::query,
-            )))
-        }
+            )
+            .with_reply(astroport_liquidity_helper::contract::reply),
+        )),
         _ => panic!("Unsupported runner"),
     };
     let astroport_liquidity_helper_code_id = app
@@ -135,7 +154,10 @@ where
         .instantiate(
             astroport_liquidity_helper_code_id,
             &InstantiateMsg {
+                owner: admin.address(),
                 astroport_factory: astroport_contracts.factory.address.clone(),
+                astroport_liquidity_manager: astroport_contracts.liquidity_manager.address.clone(),
+                astroport_incentives: Some(astroport_contracts.incentives.address.clone()),
             },
             Some(&admin.address()), // contract admin used for migration
             Some("Astroport Liquidity Helper"), // contract label
@@ -386,7 +408,15 @@ pub fn test_balancing_provide_liquidity(
             .unwrap(),
         ),
         PairType::Custom(t) => match t.as_str() {
-            "concentrated" => Some(to_json_binary(&common_pcl_params()).unwrap()),
+            // Create the pool at the price of its initial liquidity
+            "concentrated" => Some(
+                to_json_binary(&ConcentratedPoolParams {
+                    price_scale: Decimal::checked_from_ratio(reserves[0], reserves[1])
+                        .unwrap_or(Decimal::one()),
+                    ..common_pcl_params()
+                })
+                .unwrap(),
+            ),
             "astroport-pair-xyk-sale-tax" => Some(
                 to_json_binary(&SaleTaxInitParams {
                     tax_config_admin: admin.address(),
@@ -416,9 +446,9 @@ pub fn test_balancing_provide_liquidity(
         None,
     );
     let pool = AstroportPool {
-        lp_token: AssetInfo::cw20(Addr::unchecked(uluna_astro_lp_token)),
+        lp_token: AssetInfo::cw20(Addr::unchecked(&uluna_astro_lp_token)),
         pair_addr: Addr::unchecked(uluna_astro_pair_addr.clone()),
-        pair_type,
+        pair_type: pair_type.clone(),
         pool_assets: vec![
             AssetInfo::native("uluna".to_string()),
             AssetInfo::cw20(Addr::unchecked(&astro_token)),
@@ -497,6 +527,11 @@ pub fn test_balancing_provide_liquidity(
     // Check asset balances before balancing provide liquidity
     let uluna_balance_before = query_token_balance(&runner, &admin.address(), "uluna");
     let astro_balance_before = query_cw20_balance(&runner, admin.address(), &astro_token);
+    let contract_addr = liquidity_helper.addr().to_string();
+    let contract_balances_before = [
+        query_token_balance(&runner, &contract_addr, "uluna"),
+        query_cw20_balance(&runner, &contract_addr, &astro_token),
+    ];
 
     // Balancing Provide liquidity
     println!("Balancing provide liquidity");
@@ -507,17 +542,132 @@ pub fn test_balancing_provide_liquidity(
             asset_amounts[1],
         ))
         .unwrap();
+    // For stableswap pools, simulate providing the assets as they are to check
+    // that balancing never gives fewer LP tokens
+    let raw_provide_lp: Option<Uint128> = (pair_type == PairType::Stable {}).then(|| {
+        wasm.query(
+            &astroport_contracts.liquidity_manager.address,
+            &LiquidityManagerQueryMsg::SimulateProvide {
+                pair_addr: uluna_astro_pair_addr.clone(),
+                pair_msg: PairExecuteMsg::ProvideLiquidity {
+                    assets: vec![
+                        AstroAsset {
+                            amount: asset_amounts[0],
+                            info: AstroAssetInfo::NativeToken {
+                                denom: "uluna".into(),
+                            },
+                        },
+                        AstroAsset {
+                            amount: asset_amounts[1],
+                            info: AstroAssetInfo::Token {
+                                contract_addr: Addr::unchecked(&astro_token),
+                            },
+                        },
+                    ],
+                    slippage_tolerance: Some(Decimal::from_str(MAX_ALLOWED_SLIPPAGE).unwrap()),
+                    auto_stake: Some(false),
+                    receiver: None,
+                },
+            },
+        )
+        .unwrap()
+    });
+
+    // PCL pairs fail to solve their invariant (`newton_d is not converging`)
+    // for very large pools, as the tolerance is absolute, and for provides
+    // that move the pool far away from its price scale. The liquidity helper
+    // then provides the assets as they are, so it may only fail if providing
+    // them directly to the pair fails too.
+    let is_pcl = pair_type == PairType::Custom("concentrated".to_string());
+    let assert_pair_fails_to_provide = || {
+        assert!(is_pcl);
+        wasm.execute(
+            &astro_token,
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: uluna_astro_pair_addr.clone(),
+                amount: asset_amounts[1],
+                expires: None,
+            },
+            &[],
+            &admin,
+        )
+        .unwrap();
+        let provide_liq_msg = PairExecuteMsg::ProvideLiquidity {
+            assets: vec![
+                AstroAsset {
+                    amount: asset_amounts[0],
+                    info: AstroAssetInfo::NativeToken {
+                        denom: "uluna".into(),
+                    },
+                },
+                AstroAsset {
+                    amount: asset_amounts[1],
+                    info: AstroAssetInfo::Token {
+                        contract_addr: Addr::unchecked(&astro_token),
+                    },
+                },
+            ],
+            slippage_tolerance: Some(Decimal::from_str(MAX_ALLOWED_SLIPPAGE).unwrap()),
+            auto_stake: Some(false),
+            receiver: None,
+        };
+        let coins = if !asset_amounts[0].is_zero() {
+            vec![Coin::new(asset_amounts[0].u128(), "uluna")]
+        } else {
+            vec![]
+        };
+        assert!(wasm
+            .execute(&uluna_astro_pair_addr, &provide_liq_msg, &coins, &admin)
+            .is_err());
+    };
+
+    let simulation = wasm.query::<_, SimulateBalancingProvideLiquidityResponse>(
+        liquidity_helper.addr().as_ref(),
+        &QueryMsg::SimulateBalancingProvideLiquidity {
+            assets: assets.clone().into(),
+            pool: to_json_binary(&pool).unwrap().into(),
+            initial_price: None,
+        },
+    );
+    let simulation = match simulation {
+        Err(_) if is_pcl => return assert_pair_fails_to_provide(),
+        simulation => simulation.unwrap(),
+    };
+    let lp_balance_before = query_cw20_balance(&runner, admin.address(), &uluna_astro_lp_token);
     let msgs = liquidity_helper
         .balancing_provide_liquidity(
             assets,
             Uint128::zero(),
             to_json_binary(&pool).unwrap(),
             None,
+            None,
+            None,
+            None,
+            None,
         )
         .unwrap();
-    let _res = runner
-        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
-        .unwrap();
+    let res = match runner.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin) {
+        Err(_) if is_pcl => return assert_pair_fails_to_provide(),
+        res => res.unwrap(),
+    };
+
+    // The LP tokens are returned in a reply rather than a callback, so the
+    // liquidity helper should only have been executed once
+    assert_eq!(
+        count_contract_executions(&res.events, liquidity_helper.addr().as_str()),
+        1
+    );
+
+    // Parse the response data as a caller would in its reply
+    let provide_res = LiquidityHelper::parse_provide_liquidity_response(Reply {
+        id: 0,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(res.raw_data.into()),
+        }),
+    })
+    .unwrap();
+    assert_eq!(provide_res.lp_token, pool.lp_token);
 
     // Check pool liquidity after adding
     let pool_liquidity = wasm
@@ -544,10 +694,42 @@ pub fn test_balancing_provide_liquidity(
             TOLERANCE
         );
 
-        // Should have used all assets
-        assert_eq!(uluna_balance_before - uluna_balance_after, asset_amounts[0]);
-        assert_eq!(astro_balance_before - astro_balance_after, asset_amounts[1]);
+        // Should have used all assets, except for any leftover refunded to
+        // the recipient. The refund may include some of the asset received in
+        // the balancing swap, so it can exceed the amount provided.
+        let refunded = |info: &AssetInfo| {
+            provide_res
+                .leftover
+                .find(info)
+                .map(|a| a.amount)
+                .unwrap_or_default()
+        };
+        assert_eq!(
+            uluna_balance_after + asset_amounts[0],
+            uluna_balance_before + refunded(&AssetInfo::native("uluna"))
+        );
+        assert_eq!(
+            astro_balance_after + asset_amounts[1],
+            astro_balance_before + refunded(&AssetInfo::cw20(Addr::unchecked(&astro_token)))
+        );
+
+        // Should have received about as many LP tokens as simulated. For PCL
+        // the simulation is an estimate that excludes the imbalanced provide
+        // fee, so we only check that it is an upper bound.
+        let lp_balance_after = query_cw20_balance(&runner, admin.address(), &uluna_astro_lp_token);
+        let lp_received = lp_balance_after - lp_balance_before;
+        assert_eq!(provide_res.lp_amount, lp_received);
+        if pair_type == PairType::Custom("concentrated".to_string()) {
+            assert!(lp_received <= simulation.lp_tokens.amount);
+        } else {
+            assert_approx_eq!(lp_received, simulation.lp_tokens.amount, TOLERANCE);
+        }
+        if let Some(raw_provide_lp) = raw_provide_lp {
+            assert!(lp_received >= raw_provide_lp);
+        }
     } else {
+        assert_eq!(simulation.lp_tokens.amount, Uint128::zero());
+        assert_eq!(provide_res.lp_amount, Uint128::zero());
         assert_eq!(pool_liquidity[0].amount, reserves[0]);
         assert_eq!(pool_liquidity[1].amount, reserves[1]);
 
@@ -555,60 +737,1892 @@ pub fn test_balancing_provide_liquidity(
         assert_eq!(uluna_balance_before - uluna_balance_after, Uint128::zero());
         assert_eq!(astro_balance_before - astro_balance_after, Uint128::zero());
     }
+
+    // Anything not provided should have been refunded, so the liquidity helper
+    // holds the same balances as before
+    assert_eq!(
+        [
+            query_token_balance(&runner, &contract_addr, "uluna"),
+            query_cw20_balance(&runner, &contract_addr, &astro_token),
+        ],
+        contract_balances_before
+    );
 }
 
-fn query_token_balance<'a, R>(runner: &'a R, address: &str, denom: &str) -> Uint128
-where
-    R: Runner<'a>,
-{
-    let bank = Bank::new(runner);
-    let balance = bank
-        .query_balance(&QueryBalanceRequest {
-            address: address.to_string(),
-            denom: denom.to_string(),
-        })
-        .unwrap()
-        .balance
-        .unwrap_or_default()
-        .amount;
-    Uint128::from_str(&balance).unwrap()
+#[test_matrix(
+    [PairType::Xyk {}, PairType::Stable {}, PairType::Custom("concentrated".to_string())],
+    [true, false]
+)]
+/// Tests the BalancingWithdrawLiquidity message
+pub fn test_balancing_withdraw_liquidity(pair_type: PairType, target_is_native: bool) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) = setup_pool_with_liquidity(&runner, &admin, pair_type, reserves);
+    let astro_token = pool.pool_assets[1].to_string();
+    let uluna_astro_lp_token = pool.lp_token.to_string();
+
+    // Check balances before balancing withdraw liquidity
+    let uluna_balance_before = query_token_balance(&runner, &admin.address(), "uluna");
+    let astro_balance_before = query_cw20_balance(&runner, admin.address(), &astro_token);
+    let lp_balance_before = query_cw20_balance(&runner, admin.address(), &uluna_astro_lp_token);
+
+    // Withdraw a tenth of the LP tokens into a single asset
+    let lp_amount = lp_balance_before / Uint128::new(10);
+    let target_asset = if target_is_native {
+        pool.pool_assets[0].clone()
+    } else {
+        pool.pool_assets[1].clone()
+    };
+    println!("Balancing withdraw liquidity");
+    let msgs = liquidity_helper
+        .balancing_withdraw_liquidity(
+            Asset::new(pool.lp_token.clone(), lp_amount),
+            to_json_binary(&pool).unwrap(),
+            target_asset,
+            Uint128::one(),
+            None,
+        )
+        .unwrap();
+    let res = runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+    assert_eq!(
+        count_contract_executions(&res.events, liquidity_helper.addr().as_str()),
+        1
+    );
+
+    // Check balances after balancing withdraw liquidity
+    let uluna_balance_after = query_token_balance(&runner, &admin.address(), "uluna");
+    let astro_balance_after = query_cw20_balance(&runner, admin.address(), &astro_token);
+    let lp_balance_after = query_cw20_balance(&runner, admin.address(), &uluna_astro_lp_token);
+    assert_eq!(lp_balance_before - lp_balance_after, lp_amount);
+
+    // The whole position should have been exited into the target asset. A
+    // tenth of the LP tokens is worth a tenth of each reserve, so in a 1:1 pool
+    // we should get a bit less than a fifth of one reserve after swap fees and
+    // slippage.
+    let (target_received, other_received) = if target_is_native {
+        (
+            uluna_balance_after - uluna_balance_before,
+            astro_balance_after - astro_balance_before,
+        )
+    } else {
+        (
+            astro_balance_after - astro_balance_before,
+            uluna_balance_after - uluna_balance_before,
+        )
+    };
+    assert_eq!(other_received, Uint128::zero());
+    assert!(target_received < reserves[0] / Uint128::new(5));
+    assert!(target_received > reserves[0] * Decimal::percent(18));
+
+    // The contract should not hold any of the pool assets
+    let contract_addr = liquidity_helper.addr().to_string();
+    assert_eq!(
+        query_token_balance(&runner, &contract_addr, "uluna"),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_cw20_balance(&runner, &contract_addr, &astro_token),
+        Uint128::zero()
+    );
 }
 
-fn query_cw20_balance<'a, R, S>(runner: &'a R, address: S, contract_addr: &str) -> Uint128
-where
-    R: Runner<'a>,
-    S: Into<String>,
-{
-    let wasm = Wasm::new(runner);
-    wasm.query::<_, BalanceResponse>(
-        contract_addr,
-        &Cw20QueryMsg::Balance {
-            address: address.into(),
-        },
-    )
-    .unwrap()
-    .balance
+/// The maximum gas a BalancingProvideLiquidity to a stable pair may use,
+/// including finding the balancing swap
+const MAX_STABLE_PROVIDE_GAS: u64 = 2_500_000;
+
+#[test_case(Uint128::new(1_000_000); "Small amount of one asset")]
+#[test_case(Uint128::new(1_000_000_000_000); "Amount of one asset equal to the reserves")]
+/// Tests that the gas used by a BalancingProvideLiquidity to a stable pair is
+/// bounded, as finding the balancing swap solves the StableSwap invariant for
+/// every swap amount tried
+pub fn test_balancing_provide_liquidity_stable_gas(amount: Uint128) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Stable {}, reserves);
+
+    let assets: AssetList = vec![Coin::new(amount.u128(), "uluna")].into();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::one(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    assert_gas_used(res.gas_info.gas_used, MAX_STABLE_PROVIDE_GAS);
 }
 
-pub fn f64_to_dec<T>(val: f64) -> T
-where
-    T: FromStr,
-    T::Err: std::error::Error,
-{
-    T::from_str(&val.to_string()).unwrap()
+// Test 1: Small amount of one asset, low slippage
+#[test_case(
+    [Uint128::from(0u128), Uint128::from(1_000_000u128)],
+    Some(Decimal::permille(1))
+)]
+// Test 2: Large amount of one asset compared to the pool, low slippage. The
+// slippage only guards the return of the swap against its simulation, not the
+// price impact of the swap itself.
+#[test_case(
+    [Uint128::from(0u128), Uint128::from(100_000_000_000u128)],
+    Some(Decimal::permille(1))
+)]
+// Test 3: Large amount of one asset compared to the pool, within max slippage
+#[test_case(
+    [Uint128::from(0u128), Uint128::from(100_000_000_000u128)],
+    Some(Decimal::percent(5))
+)]
+// Test 4: Large amount of one asset compared to the pool, no max slippage
+#[test_case(
+    [Uint128::from(0u128), Uint128::from(100_000_000_000u128)],
+    None
+)]
+/// Tests the max_swap_slippage of BalancingProvideLiquidity
+pub fn test_balancing_provide_liquidity_max_swap_slippage(
+    asset_amounts: [Uint128; 2],
+    max_swap_slippage: Option<Decimal>,
+) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+
+    let mut assets: AssetList = vec![Coin::new(asset_amounts[0].u128(), "uluna")].into();
+    assets
+        .add(&Asset::new(pool.pool_assets[1].clone(), asset_amounts[1]))
+        .unwrap();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            max_swap_slippage,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    let lp_balance_after = query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+    assert!(lp_balance_after > lp_balance_before);
 }
 
-pub fn common_pcl_params() -> ConcentratedPoolParams {
-    ConcentratedPoolParams {
-        amp: f64_to_dec(40f64),
-        gamma: f64_to_dec(0.000145),
-        mid_fee: f64_to_dec(0.0026),
-        out_fee: f64_to_dec(0.0045),
-        fee_gamma: f64_to_dec(0.00023),
-        repeg_profit_threshold: f64_to_dec(0.000002),
-        min_price_scale_delta: f64_to_dec(0.000146),
-        price_scale: Decimal::one(),
-        ma_half_time: 600,
-        track_asset_balances: None,
+#[test_matrix(
+    [PairType::Xyk {}, PairType::Stable {}, PairType::Custom("concentrated".to_string())]
+)]
+/// Tests that `max_swap_slippage` is applied the same way for all pair types,
+/// as the minimum out of the balancing swap below its simulated return, rather
+/// than by skipping or failing swaps with a large price impact
+pub fn test_balancing_provide_liquidity_max_swap_slippage_pair_types(pair_type: PairType) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, pair_type.clone(), reserves);
+    let max_swap_slippage = Decimal::permille(1);
+
+    let assets: AssetList = vec![Coin::new(1_000_000_000_000, "uluna")].into();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            Some(max_swap_slippage),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    let provide_res = LiquidityHelper::parse_provide_liquidity_response(Reply {
+        id: 0,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(res.raw_data.into()),
+        }),
+    })
+    .unwrap();
+    let min_out = res
+        .events
+        .iter()
+        .find(|e| e.ty == "wasm-apollo/cw-dex/swap")
+        .and_then(|e| e.attributes.iter().find(|a| a.key == "minimum_out_amount"))
+        .map(|a| Uint128::from_str(&a.value).unwrap());
+    if matches!(pair_type, PairType::Xyk {}) {
+        assert!(provide_res.swapped.is_some());
     }
+    // Stable and PCL pairs only swap if that avoids more fees than it costs
+    assert_eq!(
+        min_out,
+        provide_res
+            .swapped
+            .map(|swapped| swapped.return_asset.amount * (Decimal::one() - max_swap_slippage))
+    );
+}
+
+#[test_case(
+    |_, _| {};
+    "Valid pool"
+)]
+#[test_case(
+    |pool, _| pool.pair_type = PairType::Stable {}
+    => panics "Pair type mismatch";
+    "Pool lies about its pair type"
+)]
+#[test_case(
+    |pool, _| pool.lp_token = AssetInfo::native("uluna")
+    => panics "LP token mismatch";
+    "Pool lies about its LP token"
+)]
+#[test_case(
+    |pool, liquidity_helper| pool.pair_addr = liquidity_helper.addr()
+    => panics "is not registered in the Astroport factory";
+    "Pool lies about its pair address"
+)]
+#[test_case(
+    |pool, liquidity_helper| pool.liquidity_manager = liquidity_helper.addr()
+    => panics "Liquidity manager mismatch";
+    "Pool has a forged liquidity manager"
+)]
+/// Tests that BalancingProvideLiquidity rejects pools that don't match the
+/// pair registered in the Astroport factory
+pub fn test_balancing_provide_liquidity_validates_pool(
+    modify_pool: fn(&mut AstroportPool, &LiquidityHelper),
+) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, mut pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    modify_pool(&mut pool, &liquidity_helper);
+
+    let assets: AssetList = vec![Coin::new(1_000_000, "uluna")].into();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+}
+
+#[test_matrix(
+    [PairType::Xyk {}, PairType::Stable {}, PairType::Custom("concentrated".to_string())]
+)]
+/// Tests BalancingProvideLiquidity with the pool given by its pair address
+/// instead of the serialised pool
+pub fn test_balancing_provide_liquidity_by_pair_address(pair_type: PairType) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) = setup_pool_with_liquidity(&runner, &admin, pair_type, reserves);
+    let pool_identifier = PoolIdentifier::AstroportPair {
+        address: pool.pair_addr.to_string(),
+    };
+    let lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+
+    let assets: AssetList = vec![Coin::new(1_000_000, "uluna")].into();
+    let simulation: SimulateBalancingProvideLiquidityResponse = Wasm::new(&runner)
+        .query(
+            liquidity_helper.addr().as_ref(),
+            &QueryMsg::SimulateBalancingProvideLiquidity {
+                assets: assets.clone().into(),
+                pool: pool_identifier.clone().into(),
+                initial_price: None,
+            },
+        )
+        .unwrap();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            pool_identifier,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    let lp_received = query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string())
+        - lp_balance_before;
+    assert_eq!(simulation.lp_tokens.info, pool.lp_token);
+    assert!(!lp_received.is_zero());
+    // The PCL estimate ignores the provide fee, so it is slightly higher
+    assert_approx_eq!(lp_received, simulation.lp_tokens.amount, "0.005");
+}
+
+#[test_matrix(
+    [PairType::Xyk {}, PairType::Stable {}, PairType::Custom("concentrated".to_string())],
+    [None, Some("2")]
+)]
+/// Tests that BalancingProvideLiquidity seeds an empty pool, at the initial
+/// price if one is given and otherwise in the ratio of the assets
+pub fn test_balancing_provide_liquidity_empty_pool(
+    pair_type: PairType,
+    initial_price: Option<&str>,
+) {
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) = setup_pool(&runner, &admin, pair_type.clone());
+    let wasm = Wasm::new(&runner);
+    let astro_token = pool.pool_assets[1].to_string();
+    let initial_price = initial_price.map(|x| Decimal::from_str(x).unwrap());
+
+    let asset_amounts = [
+        Uint128::from(1_000_000_000u128),
+        Uint128::from(3_000_000_000u128),
+    ];
+    let assets: AssetList = vec![
+        Asset::new(pool.pool_assets[0].clone(), asset_amounts[0]),
+        Asset::new(pool.pool_assets[1].clone(), asset_amounts[1]),
+    ]
+    .into();
+    let simulation: SimulateBalancingProvideLiquidityResponse = wasm
+        .query(
+            liquidity_helper.addr().as_ref(),
+            &QueryMsg::SimulateBalancingProvideLiquidity {
+                assets: assets.clone().into(),
+                pool: to_json_binary(&pool).unwrap().into(),
+                initial_price,
+            },
+        )
+        .unwrap();
+
+    let uluna_balance_before = query_token_balance(&runner, &admin.address(), "uluna");
+    let astro_balance_before = query_cw20_balance(&runner, admin.address(), &astro_token);
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            initial_price,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+    let provide_res = LiquidityHelper::parse_provide_liquidity_response(Reply {
+        id: 0,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(res.raw_data.into()),
+        }),
+    })
+    .unwrap();
+
+    // There is nothing to balance against in an empty pool
+    assert_eq!(provide_res.swapped, None);
+    assert_eq!(simulation.swap, None);
+
+    // With an initial price of two the excess of the second asset is returned
+    let provided = match initial_price {
+        Some(price) => [asset_amounts[0], asset_amounts[0] * price],
+        None => asset_amounts,
+    };
+    let uluna_balance_after = query_token_balance(&runner, &admin.address(), "uluna");
+    let astro_balance_after = query_cw20_balance(&runner, admin.address(), &astro_token);
+    assert_eq!(uluna_balance_before - uluna_balance_after, provided[0]);
+    assert_eq!(astro_balance_before - astro_balance_after, provided[1]);
+    let mut leftover = AssetList::new();
+    if provided[1] < asset_amounts[1] {
+        leftover
+            .add(&Asset::new(
+                pool.pool_assets[1].clone(),
+                asset_amounts[1] - provided[1],
+            ))
+            .unwrap();
+    }
+    assert_eq!(provide_res.leftover, leftover);
+    assert_eq!(simulation.leftover, leftover);
+
+    // The pool should hold the provided assets
+    let pool_liquidity = wasm
+        .query::<_, PoolResponse>(pool.pair_addr.as_ref(), &PairQueryMsg::Pool {})
+        .unwrap()
+        .assets;
+    assert_approx_eq!(pool_liquidity[0].amount, provided[0], TOLERANCE);
+    assert_approx_eq!(pool_liquidity[1].amount, provided[1], TOLERANCE);
+
+    // The first provide is not charged a fee, but the PCL estimate assumes
+    // the assets are in the ratio of the price scale, so it is an upper bound
+    let lp_received = query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+    assert!(!lp_received.is_zero());
+    assert_eq!(provide_res.lp_amount, lp_received);
+    if pair_type == PairType::Custom("concentrated".to_string()) {
+        assert!(lp_received <= simulation.lp_tokens.amount);
+    } else {
+        assert_approx_eq!(lp_received, simulation.lp_tokens.amount, TOLERANCE);
+    }
+
+    // The contract should not hold any of the pool assets
+    let contract_addr = liquidity_helper.addr().to_string();
+    assert_eq!(
+        query_token_balance(&runner, &contract_addr, "uluna"),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_cw20_balance(&runner, &contract_addr, &astro_token),
+        Uint128::zero()
+    );
+}
+
+#[test_matrix(
+    [PairType::Xyk {}, PairType::Stable {}, PairType::Custom("concentrated".to_string())],
+    [false, true],
+    [None, Some("0.01")]
+)]
+/// Tests that BalancingProvideLiquidity fails if `max_price_deviation` is given
+/// and the price of the pool was moved away from its TWAP in the same block
+pub fn test_balancing_provide_liquidity_max_price_deviation(
+    pair_type: PairType,
+    manipulated: bool,
+    max_price_deviation: Option<&str>,
+) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) = setup_pool_with_liquidity(&runner, &admin, pair_type, reserves);
+    let wasm = Wasm::new(&runner);
+    let max_price_deviation = max_price_deviation.map(|x| Decimal::from_str(x).unwrap());
+
+    // Record an observation to calculate the TWAP from and let it age
+    wasm.execute(
+        liquidity_helper.addr().as_ref(),
+        &ExecuteMsg::RecordPriceObservation {
+            pool: to_json_binary(&pool).unwrap().into(),
+        },
+        &[],
+        &admin,
+    )
+    .unwrap();
+    runner.increase_time(TWAP_WINDOW).unwrap();
+
+    // Move the price with a large swap in the same block as the provide
+    if manipulated {
+        wasm.execute(
+            pool.pair_addr.as_ref(),
+            &PairExecuteMsg::Swap {
+                offer_asset: AstroAsset {
+                    amount: Uint128::from(500_000_000_000u128),
+                    info: AstroAssetInfo::NativeToken {
+                        denom: "uluna".into(),
+                    },
+                },
+                ask_asset_info: None,
+                belief_price: None,
+                max_spread: Some(Decimal::percent(50)),
+                to: None,
+            },
+            &[coin(500_000_000_000u128, "uluna")],
+            &admin,
+        )
+        .unwrap();
+    }
+
+    let assets: AssetList = vec![Coin::new(1_000_000, "uluna")].into();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            max_price_deviation,
+            None,
+        )
+        .unwrap();
+    let res = runner.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin);
+
+    if manipulated && max_price_deviation.is_some() {
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("exceeds max_price_deviation"));
+    } else {
+        res.unwrap();
+    }
+}
+
+#[test]
+#[should_panic(expected = "No price observation")]
+/// Tests that BalancingProvideLiquidity with `max_price_deviation` fails if no
+/// price observation of the pool is old enough to calculate a TWAP from
+pub fn test_balancing_provide_liquidity_max_price_deviation_no_observation() {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+
+    let assets: AssetList = vec![Coin::new(1_000_000, "uluna")].into();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            Some(Decimal::percent(1)),
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+}
+
+#[test_case(true ; "Observation older than MAX_OBSERVATION_AGE")]
+#[test_case(false ; "Provide without max_price_deviation doesn't record an observation")]
+#[should_panic(expected = "No price observation")]
+/// Tests that BalancingProvideLiquidity with `max_price_deviation` fails if the
+/// only price observation is too old, or was never recorded because the
+/// earlier provides didn't ask for the guard
+pub fn test_balancing_provide_liquidity_max_price_deviation_stale_observation(stale: bool) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let provide = |max_price_deviation: Option<Decimal>| {
+        let msgs = liquidity_helper
+            .balancing_provide_liquidity(
+                vec![Coin::new(1_000_000, "uluna")].into(),
+                Uint128::zero(),
+                to_json_binary(&pool).unwrap(),
+                None,
+                None,
+                None,
+                max_price_deviation,
+                None,
+            )
+            .unwrap();
+        runner
+            .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+            .unwrap();
+    };
+
+    if stale {
+        Wasm::new(&runner)
+            .execute(
+                liquidity_helper.addr().as_ref(),
+                &ExecuteMsg::RecordPriceObservation {
+                    pool: to_json_binary(&pool).unwrap().into(),
+                },
+                &[],
+                &admin,
+            )
+            .unwrap();
+        runner.increase_time(MAX_OBSERVATION_AGE + 1).unwrap();
+    } else {
+        provide(None);
+        runner.increase_time(TWAP_WINDOW).unwrap();
+    }
+
+    provide(Some(Decimal::percent(1)));
+}
+
+#[test_case(true; "Owner can sweep")]
+#[test_case(false => panics "Caller is not the contract's current owner"; "Non-owner can't sweep")]
+/// Tests that the owner can recover assets sent to the contract by mistake
+pub fn test_sweep(sender_is_owner: bool) {
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let user = runner.init_account(&[coin(1_000_000, "uosmo")]).unwrap();
+    let (liquidity_helper, pool) = setup_pool(&runner, &admin, PairType::Xyk {});
+    let astro_token = pool.pool_assets[1].to_string();
+    let contract_addr = liquidity_helper.addr().to_string();
+
+    // Send some assets to the liquidity helper by mistake
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(
+            &[BankMsg::Send {
+                to_address: contract_addr.clone(),
+                amount: vec![coin(1_000_000, "uluna")],
+            }
+            .into()],
+            &admin,
+        )
+        .unwrap();
+    Wasm::new(&runner)
+        .execute(
+            &astro_token,
+            &Cw20ExecuteMsg::Transfer {
+                recipient: contract_addr.clone(),
+                amount: Uint128::from(2_000_000u128),
+            },
+            &[],
+            &admin,
+        )
+        .unwrap();
+
+    let assets: AssetList = vec![
+        Asset::new(AssetInfo::native("uluna"), 1_000_000u128),
+        Asset::new(pool.pool_assets[1].clone(), 2_000_000u128),
+    ]
+    .into();
+    let msg = liquidity_helper
+        .call(
+            ExecuteMsg::Sweep {
+                assets: assets.into(),
+                recipient: Some(user.address()),
+            },
+            vec![],
+        )
+        .unwrap();
+    let sender = if sender_is_owner { &admin } else { &user };
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], sender)
+        .unwrap();
+
+    assert_eq!(
+        query_token_balance(&runner, &user.address(), "uluna"),
+        Uint128::from(1_000_000u128)
+    );
+    assert_eq!(
+        query_cw20_balance(&runner, user.address(), &astro_token),
+        Uint128::from(2_000_000u128)
+    );
+    assert_eq!(
+        query_token_balance(&runner, &contract_addr, "uluna"),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_cw20_balance(&runner, &contract_addr, &astro_token),
+        Uint128::zero()
+    );
+}
+
+#[test]
+/// Tests the two step transfer of the ownership of the contract
+pub fn test_update_ownership() {
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let new_owner = runner.init_account(&[coin(1_000_000, "uosmo")]).unwrap();
+    let (liquidity_helper, _) = setup_pool(&runner, &admin, PairType::Xyk {});
+    let wasm = Wasm::new(&runner);
+    let query_ownership = || -> Ownership<String> {
+        wasm.query(liquidity_helper.addr().as_ref(), &QueryMsg::Ownership {})
+            .unwrap()
+    };
+    let update_ownership = |action: Action, signer: &SigningAccount| {
+        let msg = liquidity_helper
+            .call(ExecuteMsg::UpdateOwnership(action), vec![])
+            .unwrap();
+        runner.execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], signer)
+    };
+
+    // The owner is set at instantiation
+    assert_eq!(query_ownership().owner, Some(admin.address()));
+
+    // Propose the new owner, which only takes effect once accepted
+    update_ownership(
+        Action::TransferOwnership {
+            new_owner: new_owner.address(),
+            expiry: None,
+        },
+        &admin,
+    )
+    .unwrap();
+    let ownership = query_ownership();
+    assert_eq!(ownership.owner, Some(admin.address()));
+    assert_eq!(ownership.pending_owner, Some(new_owner.address()));
+
+    // Only the pending owner can accept
+    update_ownership(Action::AcceptOwnership, &admin).unwrap_err();
+    update_ownership(Action::AcceptOwnership, &new_owner).unwrap();
+    let ownership = query_ownership();
+    assert_eq!(ownership.owner, Some(new_owner.address()));
+    assert_eq!(ownership.pending_owner, None);
+}
+
+/// The account sending an `UpdatePause` in `test_update_pause`
+#[derive(Clone, Copy)]
+pub enum PauseSender {
+    Owner,
+    Guardian,
+    User,
+}
+
+/// What an `UpdatePause` in `test_update_pause` pauses
+#[derive(Clone, Copy)]
+pub enum PauseTarget {
+    All,
+    Pool,
+    OtherPool,
+}
+
+#[test_case(false ; "Sets the owner of a contract without one")]
+#[test_case(true => panics "The contract already has an owner" ; "Can't replace the owner")]
+/// Tests that `owner` in the `MigrateMsg` only sets the owner of contracts
+/// migrated from a version without ownership
+pub fn test_migrate_owner(has_owner: bool) {
+    let mut deps = mock_dependencies();
+    cw2::set_contract_version(
+        deps.as_mut().storage,
+        "crates.io:astroport-liquidity-helper",
+        "0.3.3",
+    )
+    .unwrap();
+    if has_owner {
+        cw_ownable::initialize_owner(&mut deps.storage, &deps.api, Some("owner")).unwrap();
+    }
+
+    let res = migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            astroport_liquidity_manager: None,
+            astroport_incentives: None,
+            owner: Some("new_owner".to_string()),
+        },
+    );
+    if let Err(err) = res {
+        panic!("{err}");
+    }
+
+    let ownership = cw_ownable::get_ownership(deps.as_ref().storage).unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked("new_owner")));
+}
+
+#[test_case(&[] ; "Not paused")]
+#[test_case(
+    &[(PauseSender::Owner, PauseTarget::All, true)]
+    => panics "Providing liquidity is paused";
+    "Owner pauses all pools"
+)]
+#[test_case(
+    &[(PauseSender::Guardian, PauseTarget::Pool, true)]
+    => panics "Providing liquidity is paused";
+    "Guardian pauses the pool"
+)]
+#[test_case(&[(PauseSender::Guardian, PauseTarget::OtherPool, true)] ; "Other pool paused")]
+#[test_case(
+    &[(PauseSender::Guardian, PauseTarget::Pool, true), (PauseSender::Owner, PauseTarget::Pool, false)];
+    "Owner unpauses the pool"
+)]
+#[test_case(
+    &[(PauseSender::Owner, PauseTarget::All, true), (PauseSender::Guardian, PauseTarget::All, false)]
+    => panics "Unauthorized";
+    "Guardian can't unpause"
+)]
+#[test_case(
+    &[(PauseSender::User, PauseTarget::All, true)]
+    => panics "Unauthorized";
+    "User can't pause"
+)]
+/// Tests that BalancingProvideLiquidity fails while paused globally or for the
+/// pool, after applying the given `UpdatePause` messages
+pub fn test_update_pause(updates: &[(PauseSender, PauseTarget, bool)]) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let guardian = runner.init_account(&[coin(1_000_000, "uosmo")]).unwrap();
+    let user = runner.init_account(&[coin(1_000_000, "uosmo")]).unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let pool_identifier = PoolIdentifier::AstroportPair {
+        address: pool.pair_addr.to_string(),
+    };
+
+    let execute = |msg: ExecuteMsg, signer: &SigningAccount| {
+        let msg = liquidity_helper.call(msg, vec![]).unwrap();
+        runner.execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], signer)
+    };
+    execute(
+        ExecuteMsg::UpdateGuardian {
+            guardian: Some(guardian.address()),
+        },
+        &admin,
+    )
+    .unwrap();
+    for (sender, target, paused) in updates {
+        let pool = match target {
+            PauseTarget::All => None,
+            PauseTarget::Pool => Some(pool_identifier.clone()),
+            PauseTarget::OtherPool => Some(PoolIdentifier::AstroportPair {
+                address: liquidity_helper.addr().to_string(),
+            }),
+        };
+        let sender = match sender {
+            PauseSender::Owner => &admin,
+            PauseSender::Guardian => &guardian,
+            PauseSender::User => &user,
+        };
+        execute(
+            ExecuteMsg::UpdatePause {
+                pool,
+                paused: *paused,
+            },
+            sender,
+        )
+        .unwrap();
+    }
+
+    let pause_info: PauseInfoResponse = Wasm::new(&runner)
+        .query(liquidity_helper.addr().as_ref(), &QueryMsg::PauseInfo {})
+        .unwrap();
+    assert_eq!(
+        pause_info.guardian,
+        Some(Addr::unchecked(guardian.address()))
+    );
+    let is_paused = pause_info.global || pause_info.pools.contains(&pool_identifier);
+
+    let assets: AssetList = vec![Coin::new(1_000_000, "uluna")].into();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            pool_identifier,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = runner.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin);
+    assert_eq!(res.is_err(), is_paused);
+    res.unwrap();
+}
+
+#[test]
+/// Tests that BalancingProvideLiquidity with `PostProvideAction::Stake` stakes
+/// the LP tokens in the Astroport incentives contract for the recipient
+pub fn test_balancing_provide_liquidity_stake() {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[coin(
+            1_000_000_000_000_000_000_000_000_000_000_000u128,
+            "uluna",
+        )])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let wasm = Wasm::new(&runner);
+    let incentives: Option<Addr> = wasm
+        .query(
+            liquidity_helper.addr().as_ref(),
+            &QueryMsg::AstroportIncentives {},
+        )
+        .unwrap();
+    let incentives = incentives.unwrap();
+
+    let lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            vec![Coin::new(1_000_000, "uluna")].into(),
+            Uint128::one(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            Some(PostProvideAction::Stake {}),
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    // The LP tokens should be staked for the recipient instead of sent to it
+    assert_eq!(
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string()),
+        lp_balance_before
+    );
+    let staked: Uint128 = wasm
+        .query(
+            incentives.as_ref(),
+            &IncentivesQueryMsg::Deposit {
+                lp_token: pool.lp_token.to_string(),
+                user: admin.address(),
+            },
+        )
+        .unwrap();
+    assert!(!staked.is_zero());
+    assert_eq!(
+        query_cw20_balance(
+            &runner,
+            liquidity_helper.addr().to_string(),
+            &pool.lp_token.to_string()
+        ),
+        Uint128::zero()
+    );
+}
+
+#[test_case(true, Uint128::one() ; "Deposits into the vault")]
+#[test_case(
+    true,
+    Uint128::MAX => panics "Insufficient vault tokens received" ;
+    "Fails if less than min_vault_tokens are received"
+)]
+#[test_case(
+    false,
+    Uint128::one() => panics "not the LP token" ;
+    "Fails if the base token of the vault is not the LP token"
+)]
+/// Tests that BalancingProvideLiquidity with `PostProvideAction::VaultDeposit`
+/// deposits the LP tokens in the vault and sends the vault tokens to the
+/// recipient
+pub fn test_balancing_provide_liquidity_vault_deposit(
+    base_token_is_lp: bool,
+    min_vault_tokens: Uint128,
+) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[coin(
+            1_000_000_000_000_000_000_000_000_000_000_000u128,
+            "uluna",
+        )])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let base_token = if base_token_is_lp {
+        pool.lp_token.to_string()
+    } else {
+        pool.pair_addr.to_string()
+    };
+    let vault = setup_mock_vault(&runner, &admin, &base_token);
+
+    let lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            vec![Coin::new(1_000_000, "uluna")].into(),
+            Uint128::one(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            Some(PostProvideAction::VaultDeposit {
+                vault: vault.clone(),
+                min_vault_tokens,
+            }),
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    // The LP tokens should be deposited in the vault and the vault tokens sent
+    // to the recipient
+    assert_eq!(
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string()),
+        lp_balance_before
+    );
+    let deposited = query_cw20_balance(&runner, &vault, &pool.lp_token.to_string());
+    assert!(!deposited.is_zero());
+    assert_eq!(
+        query_cw20_balance(&runner, admin.address(), &vault),
+        deposited * Uint128::from(MOCK_VAULT_RATE)
+    );
+    for token in [pool.lp_token.to_string(), vault] {
+        assert_eq!(
+            query_cw20_balance(&runner, liquidity_helper.addr().to_string(), &token),
+            Uint128::zero()
+        );
+    }
+}
+
+#[test_case(false ; "Only an asset not in the pool")]
+#[test_case(true ; "Assets in and not in the pool")]
+/// Tests that BalancingProvideLiquidity routes assets that are not in the pool
+/// into pool assets through the router before providing them
+pub fn test_balancing_provide_liquidity_zap(with_pool_asset: bool) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let (router, _, _) = setup_mock_router(&runner, &admin, &liquidity_helper);
+    let wasm = Wasm::new(&runner);
+
+    wasm.execute(
+        liquidity_helper.addr().as_ref(),
+        &ExecuteMsg::UpdateRouter {
+            router: Some(router.clone()),
+        },
+        &[],
+        &admin,
+    )
+    .unwrap();
+    let router_res: Option<Addr> = wasm
+        .query(liquidity_helper.addr().as_ref(), &QueryMsg::Router {})
+        .unwrap();
+    assert_eq!(router_res, Some(Addr::unchecked(&router)));
+
+    let lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+    let mut funds = vec![Coin::new(1_000_000, "uosmo")];
+    if with_pool_asset {
+        funds.insert(0, Coin::new(1_000_000, "uluna"));
+    }
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            funds.into(),
+            Uint128::one(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    let lp_received = query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string())
+        - lp_balance_before;
+    assert!(!lp_received.is_zero());
+
+    // The contract should not hold any of the assets
+    let contract_addr = liquidity_helper.addr().to_string();
+    for denom in ["uluna", "uosmo"] {
+        assert_eq!(
+            query_token_balance(&runner, &contract_addr, denom),
+            Uint128::zero()
+        );
+    }
+    assert_eq!(
+        query_cw20_balance(&runner, &contract_addr, &pool.pool_assets[1].to_string()),
+        Uint128::zero()
+    );
+}
+
+#[test]
+#[should_panic(expected = "The router has no route from uatom into any of the pool assets")]
+/// Tests that BalancingProvideLiquidity fails if the router has no route from
+/// an asset that is not in the pool into the pool assets
+pub fn test_balancing_provide_liquidity_zap_no_route() {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uatom"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let (router, _, _) = setup_mock_router(&runner, &admin, &liquidity_helper);
+    let msg = liquidity_helper
+        .call(
+            ExecuteMsg::UpdateRouter {
+                router: Some(router),
+            },
+            vec![],
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], &admin)
+        .unwrap();
+
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            vec![Coin::new(1_000_000, "uatom")].into(),
+            Uint128::zero(),
+            to_json_binary(&pool).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+}
+
+#[test]
+/// Tests that MigrateLiquidity withdraws liquidity from one pool and provides
+/// the withdrawn assets to another, routing the assets not in it
+pub fn test_migrate_liquidity() {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let (router, from_pair, from_lp_token) = setup_mock_router(&runner, &admin, &liquidity_helper);
+    let msg = liquidity_helper
+        .call(
+            ExecuteMsg::UpdateRouter {
+                router: Some(router),
+            },
+            vec![],
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], &admin)
+        .unwrap();
+
+    let from_lp_balance_before = query_cw20_balance(&runner, admin.address(), &from_lp_token);
+    let to_lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+
+    // Migrate from the uluna/uosmo pool to the uluna/ASTRO pool
+    let lp_amount = Uint128::from(1_000_000u128);
+    let msgs = liquidity_helper
+        .migrate_liquidity(
+            Asset::cw20(Addr::unchecked(&from_lp_token), lp_amount),
+            PoolIdentifier::AstroportPair { address: from_pair },
+            to_json_binary(&pool).unwrap(),
+            Uint128::one(),
+            None,
+            Some(vec![Coin::new(900_000, "uluna"), Coin::new(900_000, "uosmo")].into()),
+            Some(Decimal::percent(1)),
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    assert_eq!(
+        from_lp_balance_before - query_cw20_balance(&runner, admin.address(), &from_lp_token),
+        lp_amount
+    );
+    let lp_received = query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string())
+        - to_lp_balance_before;
+    assert!(!lp_received.is_zero());
+
+    // The contract should not hold any of the assets
+    let contract_addr = liquidity_helper.addr().to_string();
+    for denom in ["uluna", "uosmo"] {
+        assert_eq!(
+            query_token_balance(&runner, &contract_addr, denom),
+            Uint128::zero()
+        );
+    }
+    for cw20 in [pool.pool_assets[1].to_string(), from_lp_token] {
+        assert_eq!(
+            query_cw20_balance(&runner, &contract_addr, &cw20),
+            Uint128::zero()
+        );
+    }
+}
+
+#[test_case(
+    Coin::new(1_100_000, "uosmo") => panics "Insufficient uosmo withdrawn";
+    "Less than min_withdrawn withdrawn"
+)]
+#[test_case(
+    Coin::new(1, "uatom") => panics "Asset uatom of min_withdrawn is not in the pool";
+    "min_withdrawn asset not in from_pool"
+)]
+/// Tests that MigrateLiquidity fails if less than `min_withdrawn` is withdrawn,
+/// or if `min_withdrawn` contains assets that are not in `from_pool`
+pub fn test_migrate_liquidity_min_withdrawn(min_withdrawn: Coin) {
+    let reserves = [
+        Uint128::from(1_000_000_000_000u128),
+        Uint128::from(1_000_000_000_000u128),
+    ];
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap();
+    let (liquidity_helper, pool) =
+        setup_pool_with_liquidity(&runner, &admin, PairType::Xyk {}, reserves);
+    let (_, from_pair, from_lp_token) = setup_mock_router(&runner, &admin, &liquidity_helper);
+
+    let lp_amount = Uint128::from(1_000_000u128);
+    let msgs = liquidity_helper
+        .migrate_liquidity(
+            Asset::cw20(Addr::unchecked(&from_lp_token), lp_amount),
+            PoolIdentifier::AstroportPair { address: from_pair },
+            to_json_binary(&pool).unwrap(),
+            Uint128::one(),
+            None,
+            Some(vec![min_withdrawn].into()),
+            None,
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+}
+
+/// Sets up Astroport and the liquidity helper, and creates a uluna/ASTRO pool
+/// of the given type without any liquidity.
+fn setup_pool<'a>(
+    runner: &'a TestRunner<'a>,
+    admin: &SigningAccount,
+    pair_type: PairType,
+) -> (LiquidityHelper, AstroportPool) {
+    let astroport_contracts =
+        get_local_contracts(runner, &Some("tests/astroport-artifacts"), false, &None);
+    let astroport_contracts = &setup_astroport(runner, astroport_contracts, admin);
+
+    let liquidity_helper =
+        setup_astroport_liquidity_provider_tests(runner, astroport_contracts, admin);
+    let astro_token = astroport_contracts.astro_token.address.clone();
+
+    // Create pool
+    let asset_infos: [AstroAssetInfo; 2] = [
+        AstroAssetInfo::NativeToken {
+            denom: "uluna".into(),
+        },
+        AstroAssetInfo::Token {
+            contract_addr: Addr::unchecked(&astro_token),
+        },
+    ];
+    let init_params = match &pair_type {
+        PairType::Stable {} => Some(
+            to_json_binary(&StablePoolParams {
+                amp: 10u64,
+                owner: None,
+            })
+            .unwrap(),
+        ),
+        PairType::Custom(_) => Some(to_json_binary(&common_pcl_params()).unwrap()),
+        _ => None,
+    };
+    let (uluna_astro_pair_addr, uluna_astro_lp_token) = create_astroport_pair(
+        runner,
+        &astroport_contracts.factory.address,
+        pair_type.clone(),
+        asset_infos,
+        init_params,
+        admin,
+        None,
+    );
+    let pool = AstroportPool {
+        lp_token: AssetInfo::cw20(Addr::unchecked(&uluna_astro_lp_token)),
+        pair_addr: Addr::unchecked(uluna_astro_pair_addr.clone()),
+        pair_type,
+        pool_assets: vec![
+            AssetInfo::native("uluna".to_string()),
+            AssetInfo::cw20(Addr::unchecked(&astro_token)),
+        ],
+        liquidity_manager: Addr::unchecked(astroport_contracts.liquidity_manager.address.clone()),
+    };
+
+    (liquidity_helper, pool)
+}
+
+/// Sets up Astroport and the liquidity helper, and creates a uluna/ASTRO pool
+/// of the given type with `reserves` as initial liquidity provided by `admin`.
+fn setup_pool_with_liquidity<'a>(
+    runner: &'a TestRunner<'a>,
+    admin: &SigningAccount,
+    pair_type: PairType,
+    reserves: [Uint128; 2],
+) -> (LiquidityHelper, AstroportPool) {
+    let (liquidity_helper, pool) = setup_pool(runner, admin, pair_type);
+    let wasm = Wasm::new(runner);
+    let astro_token = pool.pool_assets[1].to_string();
+    let uluna_astro_pair_addr = pool.pair_addr.to_string();
+
+    // Add initial pool liquidity
+    wasm.execute(
+        &astro_token,
+        &Cw20ExecuteMsg::IncreaseAllowance {
+            spender: uluna_astro_pair_addr.clone(),
+            amount: reserves[1],
+            expires: None,
+        },
+        &[],
+        admin,
+    )
+    .unwrap();
+    let provide_liq_msg = PairExecuteMsg::ProvideLiquidity {
+        assets: vec![
+            AstroAsset {
+                amount: reserves[0],
+                info: AstroAssetInfo::NativeToken {
+                    denom: "uluna".into(),
+                },
+            },
+            AstroAsset {
+                amount: reserves[1],
+                info: AstroAssetInfo::Token {
+                    contract_addr: Addr::unchecked(&astro_token),
+                },
+            },
+        ],
+        slippage_tolerance: Some(Decimal::from_str("0.02").unwrap()),
+        auto_stake: Some(false),
+        receiver: None,
+    };
+    wasm.execute(
+        &uluna_astro_pair_addr,
+        &provide_liq_msg,
+        &[coin(reserves[0].u128(), "uluna")],
+        admin,
+    )
+    .unwrap();
+
+    (liquidity_helper, pool)
+}
+
+/// Asserts that a transaction used at most `max_gas`. Multi-test does not meter
+/// gas and reports zero, so this is only checked when running the tests with
+/// the osmosis-test-app runner.
+fn assert_gas_used(gas_used: u64, max_gas: u64) {
+    assert!(
+        gas_used <= max_gas,
+        "Used {gas_used} gas, more than the maximum of {max_gas}"
+    );
+}
+
+/// Counts the number of times the contract was executed in the transaction.
+/// Multi-test names the attribute `_contract_addr` while wasmd uses
+/// `_contract_address`, so we match on the prefix.
+fn count_contract_executions(events: &[Event], contract_addr: &str) -> usize {
+    events
+        .iter()
+        .filter(|e| {
+            e.ty == "execute"
+                && e.attributes
+                    .iter()
+                    .any(|a| a.key.starts_with("_contract_addr") && a.value == contract_addr)
+        })
+        .count()
+}
+
+fn query_token_balance<'a, R>(runner: &'a R, address: &str, denom: &str) -> Uint128
+where
+    R: Runner<'a>,
+{
+    let bank = Bank::new(runner);
+    let balance = bank
+        .query_balance(&QueryBalanceRequest {
+            address: address.to_string(),
+            denom: denom.to_string(),
+        })
+        .unwrap()
+        .balance
+        .unwrap_or_default()
+        .amount;
+    Uint128::from_str(&balance).unwrap()
+}
+
+fn query_cw20_balance<'a, R, S>(runner: &'a R, address: S, contract_addr: &str) -> Uint128
+where
+    R: Runner<'a>,
+    S: Into<String>,
+{
+    let wasm = Wasm::new(runner);
+    wasm.query::<_, BalanceResponse>(
+        contract_addr,
+        &Cw20QueryMsg::Balance {
+            address: address.into(),
+        },
+    )
+    .unwrap()
+    .balance
+}
+
+pub fn f64_to_dec<T>(val: f64) -> T
+where
+    T: FromStr,
+    T::Err: std::error::Error,
+{
+    T::from_str(&val.to_string()).unwrap()
+}
+
+pub fn common_pcl_params() -> ConcentratedPoolParams {
+    ConcentratedPoolParams {
+        amp: f64_to_dec(40f64),
+        gamma: f64_to_dec(0.000145),
+        mid_fee: f64_to_dec(0.0026),
+        out_fee: f64_to_dec(0.0045),
+        fee_gamma: f64_to_dec(0.00023),
+        repeg_profit_threshold: f64_to_dec(0.000002),
+        min_price_scale_delta: f64_to_dec(0.000146),
+        price_scale: Decimal::one(),
+        ma_half_time: 600,
+        track_asset_balances: None,
+    }
+}
+
+/// A route of the mock router, which swaps `offer_denom` in the pair at
+/// `pair_addr` into `ask_asset`
+#[cw_serde]
+pub struct MockRoute {
+    pub offer_denom: String,
+    pub ask_asset: AssetInfo,
+    pub pair_addr: String,
+}
+
+const MOCK_ROUTES: Item<Vec<MockRoute>> = Item::new("routes");
+
+fn mock_router_instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    routes: Vec<MockRoute>,
+) -> StdResult<Response> {
+    MOCK_ROUTES.save(deps.storage, &routes)?;
+    Ok(Response::default())
+}
+
+/// Handles the `BasketLiquidate` of the cw-dex-router for native assets, by
+/// swapping each of them in the pair of its route and sending the return to
+/// the sender
+fn mock_router_execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: RouterExecuteMsg,
+) -> StdResult<Response> {
+    let offer_assets = match msg {
+        RouterExecuteMsg::BasketLiquidate { offer_assets, .. } => offer_assets.check(deps.api)?,
+        _ => return Err(StdError::generic_err("Unsupported message")),
+    };
+    let routes = MOCK_ROUTES.load(deps.storage)?;
+
+    let mut res = Response::new();
+    for asset in offer_assets.iter() {
+        let route = routes
+            .iter()
+            .find(|x| AssetInfo::native(&x.offer_denom) == asset.info)
+            .ok_or_else(|| StdError::generic_err("No route"))?;
+        res = res.add_message(WasmMsg::Execute {
+            contract_addr: route.pair_addr.clone(),
+            msg: to_json_binary(&PairExecuteMsg::Swap {
+                offer_asset: AstroAsset {
+                    amount: asset.amount,
+                    info: AstroAssetInfo::NativeToken {
+                        denom: route.offer_denom.clone(),
+                    },
+                },
+                ask_asset_info: None,
+                belief_price: None,
+                max_spread: Some(Decimal::percent(50)),
+                to: Some(info.sender.to_string()),
+            })?,
+            funds: vec![coin(asset.amount.u128(), &route.offer_denom)],
+        });
+    }
+    Ok(res)
+}
+
+/// Handles the `SupportedAskAssets` query of the cw-dex-router
+fn mock_router_query(deps: Deps, _env: Env, msg: RouterQueryMsg) -> StdResult<Binary> {
+    match msg {
+        RouterQueryMsg::SupportedAskAssets { offer_asset } => {
+            let offer_asset = offer_asset.check(deps.api)?;
+            let ask_assets: Vec<AssetInfo> = MOCK_ROUTES
+                .load(deps.storage)?
+                .into_iter()
+                .filter(|x| AssetInfo::native(&x.offer_denom) == offer_asset)
+                .map(|x| x.ask_asset)
+                .collect();
+            to_json_binary(&ask_assets)
+        }
+        _ => Err(StdError::generic_err("Unsupported query")),
+    }
+}
+
+/// Creates a uosmo/uluna XYK pool with liquidity and instantiates a mock
+/// router that routes uosmo into uluna through it. Returns the addresses of the
+/// router, the pair and the LP token of the pair.
+fn setup_mock_router<'a>(
+    runner: &'a TestRunner<'a>,
+    admin: &SigningAccount,
+    liquidity_helper: &LiquidityHelper,
+) -> (String, String, String) {
+    let wasm = Wasm::new(runner);
+    let factory: Addr = wasm
+        .query(
+            liquidity_helper.addr().as_ref(),
+            &QueryMsg::AstroportFactory {},
+        )
+        .unwrap();
+    let factory_config: FactoryConfigResponse = wasm
+        .query(factory.as_ref(), &FactoryQueryMsg::Config {})
+        .unwrap();
+
+    // Set uosmo precision in native coin registry
+    wasm.execute(
+        factory_config.coin_registry_address.as_ref(),
+        &astroport::native_coin_registry::ExecuteMsg::Add {
+            native_coins: vec![("uosmo".to_string(), 6)],
+        },
+        &[],
+        admin,
+    )
+    .unwrap();
+
+    let asset_infos = [
+        AstroAssetInfo::NativeToken {
+            denom: "uluna".into(),
+        },
+        AstroAssetInfo::NativeToken {
+            denom: "uosmo".into(),
+        },
+    ];
+    let (pair_addr, lp_token) = create_astroport_pair(
+        runner,
+        factory.as_ref(),
+        PairType::Xyk {},
+        asset_infos.clone(),
+        None,
+        admin,
+        None,
+    );
+    let reserve = Uint128::from(1_000_000_000_000u128);
+    wasm.execute(
+        &pair_addr,
+        &PairExecuteMsg::ProvideLiquidity {
+            assets: asset_infos
+                .into_iter()
+                .map(|info| AstroAsset {
+                    info,
+                    amount: reserve,
+                })
+                .collect(),
+            slippage_tolerance: None,
+            auto_stake: Some(false),
+            receiver: None,
+        },
+        &[coin(reserve.u128(), "uluna"), coin(reserve.u128(), "uosmo")],
+        admin,
+    )
+    .unwrap();
+
+    let router_code_id = runner
+        .store_code(
+            ContractType::MultiTestContract(Box::new(ContractWrapper::new_with_empty(
+                mock_router_execute,
+                mock_router_instantiate,
+                mock_router_query,
+            ))),
+            admin,
+        )
+        .unwrap();
+    let router = wasm
+        .instantiate(
+            router_code_id,
+            &vec![MockRoute {
+                offer_denom: "uosmo".to_string(),
+                ask_asset: AssetInfo::native("uluna"),
+                pair_addr: pair_addr.clone(),
+            }],
+            None,
+            Some("Mock router"),
+            &[],
+            admin,
+        )
+        .unwrap()
+        .data
+        .address;
+
+    (router, pair_addr, lp_token)
+}
+
+/// The amount of vault tokens the mock vault mints per base token
+const MOCK_VAULT_RATE: u128 = 2;
+
+const MOCK_VAULT_BASE_TOKEN: Item<String> = Item::new("base_token");
+const MOCK_VAULT_BALANCES: Map<&Addr, Uint128> = Map::new("balances");
+
+/// The messages of the mock vault, which is its own Cw20 vault token like a
+/// CW4626 vault
+#[cw_serde]
+#[serde(untagged)]
+pub enum MockVaultExecuteMsg {
+    Vault(VaultStandardExecuteMsg),
+    Cw20(Cw20ExecuteMsg),
+}
+
+#[cw_serde]
+#[serde(untagged)]
+pub enum MockVaultQueryMsg {
+    Vault(VaultStandardQueryMsg),
+    Cw20(Cw20QueryMsg),
+}
+
+fn mock_vault_instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    base_token: String,
+) -> StdResult<Response> {
+    MOCK_VAULT_BASE_TOKEN.save(deps.storage, &base_token)?;
+    Ok(Response::default())
+}
+
+/// Handles `Deposit` of a Cw20 base token, by transferring the approved amount
+/// and minting `MOCK_VAULT_RATE` vault tokens per base token, and `Transfer`
+/// of the vault token
+fn mock_vault_execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: MockVaultExecuteMsg,
+) -> StdResult<Response> {
+    match msg {
+        #[allow(deprecated)]
+        MockVaultExecuteMsg::Vault(VaultStandardExecuteMsg::Deposit { amount, recipient }) => {
+            let recipient = deps
+                .api
+                .addr_validate(&recipient.unwrap_or_else(|| info.sender.to_string()))?;
+            MOCK_VAULT_BALANCES.update(deps.storage, &recipient, |x| {
+                x.unwrap_or_default()
+                    .checked_add(amount * Uint128::from(MOCK_VAULT_RATE))
+                    .map_err(StdError::from)
+            })?;
+            Ok(Response::new().add_message(WasmMsg::Execute {
+                contract_addr: MOCK_VAULT_BASE_TOKEN.load(deps.storage)?,
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: env.contract.address.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }))
+        }
+        MockVaultExecuteMsg::Cw20(Cw20ExecuteMsg::Transfer { recipient, amount }) => {
+            let recipient = deps.api.addr_validate(&recipient)?;
+            MOCK_VAULT_BALANCES.update(deps.storage, &info.sender, |x| {
+                x.unwrap_or_default()
+                    .checked_sub(amount)
+                    .map_err(StdError::from)
+            })?;
+            MOCK_VAULT_BALANCES.update(deps.storage, &recipient, |x| {
+                x.unwrap_or_default()
+                    .checked_add(amount)
+                    .map_err(StdError::from)
+            })?;
+            Ok(Response::default())
+        }
+        _ => Err(StdError::generic_err("Unsupported message")),
+    }
+}
+
+/// Handles the `Info` query of the vault standard and the Cw20 `Balance` query
+/// of the vault token
+fn mock_vault_query(deps: Deps, env: Env, msg: MockVaultQueryMsg) -> StdResult<Binary> {
+    match msg {
+        MockVaultQueryMsg::Vault(VaultStandardQueryMsg::Info {}) => {
+            to_json_binary(&VaultInfoResponse {
+                base_token: MOCK_VAULT_BASE_TOKEN.load(deps.storage)?,
+                vault_token: env.contract.address.to_string(),
+            })
+        }
+        MockVaultQueryMsg::Cw20(Cw20QueryMsg::Balance { address }) => {
+            to_json_binary(&BalanceResponse {
+                balance: MOCK_VAULT_BALANCES
+                    .may_load(deps.storage, &deps.api.addr_validate(&address)?)?
+                    .unwrap_or_default(),
+            })
+        }
+        _ => Err(StdError::generic_err("Unsupported query")),
+    }
+}
+
+/// Instantiates a mock vault with the Cw20 `base_token` and returns its
+/// address, which is also the vault token
+fn setup_mock_vault<'a>(
+    runner: &'a TestRunner<'a>,
+    admin: &SigningAccount,
+    base_token: &str,
+) -> String {
+    let code_id = runner
+        .store_code(
+            ContractType::MultiTestContract(Box::new(ContractWrapper::new_with_empty(
+                mock_vault_execute,
+                mock_vault_instantiate,
+                mock_vault_query,
+            ))),
+            admin,
+        )
+        .unwrap();
+    Wasm::new(runner)
+        .instantiate(code_id, &base_token, None, Some("Mock vault"), &[], admin)
+        .unwrap()
+        .data
+        .address
 }
@@ -11,8 +11,7 @@ fn astroport_pair_type() -> impl Strategy<Value = PairType> {
     prop_oneof![
         Just(PairType::Xyk {}),
         Just(PairType::Stable {}),
-        // Just(PairType::Custom("concentrated".to_string())), // Errors with `newton_d is not
-        // converging`
+        Just(PairType::Custom("concentrated".to_string())),
         Just(PairType::Custom("astroport-pair-xyk-sale-tax".to_string())),
     ]
 }
@@ -82,4 +81,28 @@ proptest! {
         );
     }
 
+    /// Balancing a provide into a stableswap pool should never yield fewer LP
+    /// tokens than providing the assets as they are, which is checked in
+    /// `test_balancing_provide_liquidity`.
+    #[test]
+    fn stable_balancing_provide_liquidity_never_worse(
+        asset_amount_1 in 1..100_000_000_000_000u128,
+        asset_amount_2 in 0..100_000_000_000_000u128,
+        reserve_1 in 1_000_000..100_000_000_000_000u128,
+        reserve_2 in 1_000_000..100_000_000_000_000u128,
+        reverse_assets in any::<bool>())
+    {
+        let (asset_amount_1, asset_amount_2) = if reverse_assets {
+            (asset_amount_2, asset_amount_1)
+        } else {
+            (asset_amount_1, asset_amount_2)
+        };
+
+        test_balancing_provide_liquidity(
+            [Uint128::new(asset_amount_1), Uint128::new(asset_amount_2)],
+            [Uint128::new(reserve_1), Uint128::new(reserve_2)],
+            PairType::Stable {},
+            true
+        );
+    }
 }
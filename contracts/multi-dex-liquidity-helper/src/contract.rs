@@ -0,0 +1,267 @@
+use apollo_cw_asset::{AssetInfo, AssetList};
+#[cfg(feature = "astroport")]
+use astroport_liquidity_helper::contract::AstroportLiquidityHelper;
+#[cfg(feature = "astroport")]
+use astroport_liquidity_helper::state::{
+    ASTROPORT_FACTORY, ASTROPORT_INCENTIVES, ASTROPORT_LIQUIDITY_MANAGER,
+};
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    from_json, to_json_binary, Api, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Reply,
+    Response, StdResult,
+};
+use liquidity_helper::contract::BalancingProvideLiquidityArgs;
+use liquidity_helper::msg::{PoolIdentifier, PoolInput, SimulateBalancingProvideLiquidityResponse};
+use liquidity_helper::{LiquidityHelperContract, LiquidityHelperError};
+#[cfg(feature = "osmosis")]
+use osmosis_liquidity_helper::contract::OsmosisLiquidityHelper;
+#[cfg(feature = "osmosis")]
+use osmosis_liquidity_helper::state::MAX_JOIN_SLIPPAGE;
+
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+#[cfg(feature = "osmosis")]
+use crate::pool::ConcentratedLiquidityDexPool;
+use crate::pool::DexPool;
+use crate::state::{Dex, PROVIDING_DEX};
+
+/// The liquidity helper for all DEXes enabled by the cargo features
+pub struct MultiDexLiquidityHelper;
+
+impl LiquidityHelperContract for MultiDexLiquidityHelper {
+    type Pool = DexPool;
+    type Error = ContractError;
+
+    const CONTRACT_NAME: &'static str = "multi-dex-liquidity-helper";
+    const CONTRACT_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    fn parse_pool(deps: Deps, pool: PoolInput) -> Result<DexPool, ContractError> {
+        match resolve_dex(pool)? {
+            #[cfg(feature = "astroport")]
+            (Dex::Astroport, pool) => Ok(AstroportLiquidityHelper::parse_pool(deps, pool)?.into()),
+            #[cfg(feature = "osmosis")]
+            (Dex::Osmosis, pool) => Ok(OsmosisLiquidityHelper::parse_pool(deps, pool)?.into()),
+        }
+    }
+
+    fn validate_pool_identifier(
+        api: &dyn Api,
+        identifier: PoolIdentifier,
+    ) -> Result<PoolIdentifier, ContractError> {
+        match identifier_dex(&identifier)? {
+            #[cfg(feature = "astroport")]
+            Dex::Astroport => Ok(AstroportLiquidityHelper::validate_pool_identifier(
+                api, identifier,
+            )?),
+            #[cfg(feature = "osmosis")]
+            Dex::Osmosis => Ok(OsmosisLiquidityHelper::validate_pool_identifier(
+                api, identifier,
+            )?),
+        }
+    }
+
+    fn query_pool_assets(deps: Deps, pool: &PoolInput) -> Result<Vec<AssetInfo>, ContractError> {
+        match resolve_dex(pool.clone())? {
+            #[cfg(feature = "astroport")]
+            (Dex::Astroport, pool) => Ok(AstroportLiquidityHelper::query_pool_assets(deps, &pool)?),
+            #[cfg(feature = "osmosis")]
+            (Dex::Osmosis, pool) => Ok(OsmosisLiquidityHelper::query_pool_assets(deps, &pool)?),
+        }
+    }
+
+    fn balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        args: BalancingProvideLiquidityArgs,
+    ) -> Result<Response, ContractError> {
+        let (dex, pool) = resolve_dex(args.pool.clone())?;
+        PROVIDING_DEX.save(deps.storage, &dex)?;
+
+        let args = BalancingProvideLiquidityArgs { pool, ..args };
+        match dex {
+            #[cfg(feature = "astroport")]
+            Dex::Astroport => Ok(rename_events::<AstroportLiquidityHelper>(
+                AstroportLiquidityHelper::balancing_provide_liquidity(deps, env, info, args)?,
+            )),
+            #[cfg(feature = "osmosis")]
+            Dex::Osmosis => Ok(rename_events::<OsmosisLiquidityHelper>(
+                OsmosisLiquidityHelper::balancing_provide_liquidity(deps, env, info, args)?,
+            )),
+        }
+    }
+
+    fn reply_balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        msg: Reply,
+    ) -> Result<Response, ContractError> {
+        match PROVIDING_DEX.load(deps.storage)? {
+            #[cfg(feature = "astroport")]
+            Dex::Astroport => Ok(rename_events::<AstroportLiquidityHelper>(
+                AstroportLiquidityHelper::reply_balancing_provide_liquidity(deps, env, msg)?,
+            )),
+            #[cfg(feature = "osmosis")]
+            Dex::Osmosis => Ok(rename_events::<OsmosisLiquidityHelper>(
+                OsmosisLiquidityHelper::reply_balancing_provide_liquidity(deps, env, msg)?,
+            )),
+        }
+    }
+
+    fn simulate_balancing_provide_liquidity(
+        deps: Deps,
+        env: Env,
+        assets: AssetList,
+        pool: DexPool,
+        initial_price: Option<Decimal>,
+    ) -> Result<SimulateBalancingProvideLiquidityResponse, ContractError> {
+        match pool {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => Ok(
+                AstroportLiquidityHelper::simulate_balancing_provide_liquidity(
+                    deps,
+                    env,
+                    assets,
+                    pool,
+                    initial_price,
+                )?,
+            ),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => Ok(
+                OsmosisLiquidityHelper::simulate_balancing_provide_liquidity(
+                    deps,
+                    env,
+                    assets,
+                    pool,
+                    initial_price,
+                )?,
+            ),
+        }
+    }
+
+    fn record_price_observation(
+        deps: DepsMut,
+        env: Env,
+        pool: DexPool,
+    ) -> Result<Response, ContractError> {
+        match pool {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => Ok(rename_events::<AstroportLiquidityHelper>(
+                AstroportLiquidityHelper::record_price_observation(deps, env, pool)?,
+            )),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => Ok(rename_events::<OsmosisLiquidityHelper>(
+                OsmosisLiquidityHelper::record_price_observation(deps, env, pool)?,
+            )),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    mut deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    MultiDexLiquidityHelper::instantiate(deps.branch(), &msg.owner)?;
+
+    #[cfg(feature = "astroport")]
+    {
+        let astroport_factory = deps.api.addr_validate(&msg.astroport.factory)?;
+        ASTROPORT_FACTORY.save(deps.storage, &astroport_factory)?;
+        let astroport_liquidity_manager =
+            deps.api.addr_validate(&msg.astroport.liquidity_manager)?;
+        ASTROPORT_LIQUIDITY_MANAGER.save(deps.storage, &astroport_liquidity_manager)?;
+        if let Some(astroport_incentives) = msg.astroport.incentives {
+            let astroport_incentives = deps.api.addr_validate(&astroport_incentives)?;
+            ASTROPORT_INCENTIVES.save(deps.storage, &astroport_incentives)?;
+        }
+    }
+
+    #[cfg(feature = "osmosis")]
+    if let Some(max_join_slippage) = msg.osmosis.max_join_slippage {
+        MAX_JOIN_SLIPPAGE.save(deps.storage, &max_join_slippage)?;
+    }
+
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    MultiDexLiquidityHelper::execute(deps, env, info, msg)
+}
+
+/// Renames the events emitted by the liquidity helper `T` of a DEX, which are
+/// named after `T::CONTRACT_NAME`, to be named after this contract
+fn rename_events<T: LiquidityHelperContract>(mut res: Response) -> Response {
+    let prefix = format!("apollo/{}/", T::CONTRACT_NAME);
+    for event in res.events.iter_mut() {
+        if let Some(name) = event.ty.strip_prefix(&prefix) {
+            event.ty = MultiDexLiquidityHelper::event(name).ty;
+        }
+    }
+    res
+}
+
+/// Returns the DEX of the pool, and the pool as it is given to the liquidity
+/// helper of that DEX: the DEX specific pool struct serialised, or the
+/// identifier.
+fn resolve_dex(pool: PoolInput) -> Result<(Dex, PoolInput), ContractError> {
+    match pool {
+        PoolInput::Serialized(pool) => {
+            #[cfg(feature = "osmosis")]
+            if let Ok(ConcentratedLiquidityDexPool::OsmosisConcentratedLiquidity(pool)) =
+                from_json(&pool)
+            {
+                return Ok((Dex::Osmosis, PoolInput::Serialized(to_json_binary(&pool)?)));
+            }
+
+            let (dex, pool) = match from_json(pool)? {
+                #[cfg(feature = "astroport")]
+                DexPool::Astroport(pool) => (Dex::Astroport, to_json_binary(&pool)?),
+                #[cfg(feature = "osmosis")]
+                DexPool::Osmosis(pool) => (Dex::Osmosis, to_json_binary(&pool)?),
+            };
+            Ok((dex, PoolInput::Serialized(pool)))
+        }
+        PoolInput::Identifier(identifier) => Ok((identifier_dex(&identifier)?, identifier.into())),
+    }
+}
+
+/// Returns the DEX of the pool identified by `identifier`, or an error if the
+/// DEX is not enabled
+fn identifier_dex(identifier: &PoolIdentifier) -> Result<Dex, ContractError> {
+    match identifier {
+        #[cfg(feature = "astroport")]
+        PoolIdentifier::AstroportPair { .. } => Ok(Dex::Astroport),
+        #[cfg(feature = "osmosis")]
+        PoolIdentifier::OsmosisPool { .. } => Ok(Dex::Osmosis),
+        #[allow(unreachable_patterns)]
+        identifier => Err(LiquidityHelperError::UnsupportedPoolIdentifier {
+            identifier: identifier.clone(),
+        }
+        .into()),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    MultiDexLiquidityHelper::reply(deps, env, msg)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    MultiDexLiquidityHelper::query(deps, env, msg)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    MultiDexLiquidityHelper::migrate(deps, None)
+}
@@ -0,0 +1,37 @@
+use cosmwasm_std::{OverflowError, StdError};
+use cw_dex::CwDexError;
+use cw_ownable::OwnershipError;
+use liquidity_helper::LiquidityHelperError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    CwDex(#[from] CwDexError),
+
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    Ownership(#[from] OwnershipError),
+
+    #[error("{0}")]
+    LiquidityHelper(#[from] LiquidityHelperError),
+
+    #[cfg(feature = "astroport")]
+    #[error("{0}")]
+    Astroport(#[from] astroport_liquidity_helper::ContractError),
+
+    #[cfg(feature = "osmosis")]
+    #[error("{0}")]
+    Osmosis(#[from] osmosis_liquidity_helper::ContractError),
+}
+
+impl From<ContractError> for StdError {
+    fn from(e: ContractError) -> Self {
+        StdError::generic_err(e.to_string())
+    }
+}
@@ -0,0 +1,22 @@
+//! # Multi DEX Liquidity Helper
+//!
+//! This contract helps provide liquidity for the pools of several DEXes from
+//! a single address. The pool is given as a [`pool::DexPool`], which tags the
+//! DEX specific pool struct with its DEX, and providing liquidity is
+//! dispatched to the balancing logic of the liquidity helper of that DEX.
+//! Osmosis concentrated liquidity pools are given as a
+//! [`pool::ConcentratedLiquidityDexPool`] instead.
+//!
+//! The supported DEXes are selected with the `astroport` and `osmosis` cargo
+//! features.
+
+#[cfg(not(any(feature = "astroport", feature = "osmosis")))]
+compile_error!("At least one of the features `astroport` and `osmosis` must be enabled");
+
+pub mod contract;
+mod error;
+pub mod msg;
+pub mod pool;
+pub mod state;
+
+pub use crate::error::ContractError;
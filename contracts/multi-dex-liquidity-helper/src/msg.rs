@@ -0,0 +1,40 @@
+use cosmwasm_schema::cw_serde;
+#[cfg(feature = "osmosis")]
+use cosmwasm_std::Decimal;
+
+pub use liquidity_helper::msg::{ExecuteMsg, QueryMsg};
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The owner of the contract, who can sweep assets held by it
+    pub owner: String,
+    #[cfg(feature = "astroport")]
+    pub astroport: AstroportConfig,
+    #[cfg(feature = "osmosis")]
+    pub osmosis: OsmosisConfig,
+}
+
+/// The Astroport contracts used by the contract
+#[cfg(feature = "astroport")]
+#[cw_serde]
+pub struct AstroportConfig {
+    /// The factory that pairs are validated against
+    pub factory: String,
+    /// The liquidity manager used for pairs given by their address
+    pub liquidity_manager: String,
+    /// The incentives contract that LP tokens are staked in with
+    /// `PostProvideAction::Stake`
+    pub incentives: Option<String>,
+}
+
+/// The configuration of providing liquidity to Osmosis pools
+#[cfg(feature = "osmosis")]
+#[cw_serde]
+pub struct OsmosisConfig {
+    /// The tolerance on the LP tokens expected from each single sided join.
+    /// Defaults to one percent.
+    pub max_join_slippage: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {}
@@ -0,0 +1,177 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Deps, Env, Response, StdResult, Uint128};
+use cw_dex::traits::Pool;
+use cw_dex::CwDexError;
+#[cfg(feature = "astroport")]
+use cw_dex_astroport::AstroportPool;
+#[cfg(feature = "osmosis")]
+use cw_dex_osmosis::OsmosisPool;
+#[cfg(feature = "osmosis")]
+use osmosis_liquidity_helper::msg::ConcentratedLiquidityPool;
+
+use crate::state::Dex;
+
+/// A pool of one of the DEXes supported by the contract, tagged with its DEX.
+/// Given serialised as the `pool` of the messages, e.g.
+/// `{"astroport": {"pair_addr": ...}}`.
+#[cw_serde]
+pub enum DexPool {
+    #[cfg(feature = "astroport")]
+    Astroport(AstroportPool),
+    #[cfg(feature = "osmosis")]
+    Osmosis(OsmosisPool),
+}
+
+impl DexPool {
+    /// Returns the DEX of the pool
+    pub fn dex(&self) -> Dex {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(_) => Dex::Astroport,
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(_) => Dex::Osmosis,
+        }
+    }
+}
+
+/// A concentrated liquidity pool of one of the DEXes supported by the contract,
+/// tagged with its DEX. Liquidity is provided to these as a position instead of
+/// for LP tokens, so they are not a `DexPool`. Given serialised as the `pool`
+/// of `BalancingProvideLiquidity`, e.g.
+/// `{"osmosis_concentrated_liquidity": {"pool_id": 1, "tick_range": ...}}`.
+#[cfg(feature = "osmosis")]
+#[cw_serde]
+pub enum ConcentratedLiquidityDexPool {
+    OsmosisConcentratedLiquidity(ConcentratedLiquidityPool),
+}
+
+#[cfg(feature = "astroport")]
+impl From<AstroportPool> for DexPool {
+    fn from(pool: AstroportPool) -> Self {
+        DexPool::Astroport(pool)
+    }
+}
+
+#[cfg(feature = "osmosis")]
+impl From<OsmosisPool> for DexPool {
+    fn from(pool: OsmosisPool) -> Self {
+        DexPool::Osmosis(pool)
+    }
+}
+
+impl Pool for DexPool {
+    fn provide_liquidity(
+        &self,
+        deps: Deps,
+        env: &Env,
+        assets: AssetList,
+        min_out: Uint128,
+    ) -> Result<Response, CwDexError> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.provide_liquidity(deps, env, assets, min_out),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.provide_liquidity(deps, env, assets, min_out),
+        }
+    }
+
+    fn lp_token(&self) -> AssetInfo {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.lp_token(),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.lp_token(),
+        }
+    }
+
+    fn withdraw_liquidity(
+        &self,
+        deps: Deps,
+        env: &Env,
+        lp_token: Asset,
+        min_out: AssetList,
+    ) -> Result<Response, CwDexError> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.withdraw_liquidity(deps, env, lp_token, min_out),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.withdraw_liquidity(deps, env, lp_token, min_out),
+        }
+    }
+
+    fn swap(
+        &self,
+        deps: Deps,
+        env: &Env,
+        offer_asset: Asset,
+        ask_asset_info: AssetInfo,
+        min_out: Uint128,
+    ) -> Result<Response, CwDexError> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.swap(deps, env, offer_asset, ask_asset_info, min_out),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.swap(deps, env, offer_asset, ask_asset_info, min_out),
+        }
+    }
+
+    fn get_pool_liquidity(&self, deps: Deps) -> Result<AssetList, CwDexError> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.get_pool_liquidity(deps),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.get_pool_liquidity(deps),
+        }
+    }
+
+    fn simulate_provide_liquidity(
+        &self,
+        deps: Deps,
+        env: &Env,
+        assets: AssetList,
+    ) -> Result<Asset, CwDexError> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.simulate_provide_liquidity(deps, env, assets),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.simulate_provide_liquidity(deps, env, assets),
+        }
+    }
+
+    fn simulate_withdraw_liquidity(
+        &self,
+        deps: Deps,
+        lp_token: &Asset,
+    ) -> Result<AssetList, CwDexError> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.simulate_withdraw_liquidity(deps, lp_token),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.simulate_withdraw_liquidity(deps, lp_token),
+        }
+    }
+
+    fn simulate_swap(
+        &self,
+        deps: Deps,
+        offer_asset: Asset,
+        ask_asset_info: AssetInfo,
+    ) -> StdResult<Uint128> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.simulate_swap(deps, offer_asset, ask_asset_info),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.simulate_swap(deps, offer_asset, ask_asset_info),
+        }
+    }
+
+    fn pool_assets(&self, deps: Deps) -> StdResult<Vec<AssetInfo>> {
+        match self {
+            #[cfg(feature = "astroport")]
+            DexPool::Astroport(pool) => pool.pool_assets(deps),
+            #[cfg(feature = "osmosis")]
+            DexPool::Osmosis(pool) => pool.pool_assets(deps),
+        }
+    }
+}
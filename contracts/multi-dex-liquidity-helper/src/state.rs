@@ -0,0 +1,16 @@
+use cosmwasm_schema::cw_serde;
+use cw_storage_plus::Item;
+
+/// A DEX supported by the contract
+#[cw_serde]
+pub enum Dex {
+    #[cfg(feature = "astroport")]
+    Astroport,
+    #[cfg(feature = "osmosis")]
+    Osmosis,
+}
+
+/// The DEX of the last `BalancingProvideLiquidity`, whose liquidity helper the
+/// replies to its submessages are dispatched to. The liquidity helpers of
+/// different DEXes use the same reply IDs.
+pub const PROVIDING_DEX: Item<Dex> = Item::new("providing_dex");
@@ -0,0 +1,374 @@
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+
+use cosmwasm_std::{coin, to_json_binary, Addr, Coin, Decimal, Uint128};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_dex_astroport::astroport::asset::{Asset as AstroAsset, AssetInfo as AstroAssetInfo};
+use cw_dex_astroport::astroport::factory::PairType;
+use cw_dex_astroport::astroport::pair::{ExecuteMsg as PairExecuteMsg, StablePoolParams};
+use cw_dex_astroport::AstroportPool;
+use cw_it::astroport::utils::{create_astroport_pair, get_local_contracts, setup_astroport};
+use cw_it::cosmrs::proto::cosmwasm::wasm::v1::MsgExecuteContractResponse;
+use cw_it::cw_multi_test::ContractWrapper;
+use cw_it::multi_test::MultiTestRunner;
+use cw_it::osmosis_std::types::cosmos::bank::v1beta1::QueryBalanceRequest;
+use cw_it::test_tube::{Account, Bank, Module, Runner, SigningAccount, Wasm};
+use cw_it::traits::CwItRunner;
+use cw_it::{ContractType, OwnedTestRunner, TestRunner};
+use liquidity_helper::msg::{
+    PauseInfoResponse, PoolIdentifier, PoolInput, SimulateBalancingProvideLiquidityResponse,
+};
+use liquidity_helper::LiquidityHelper;
+use multi_dex_liquidity_helper::msg::{
+    AstroportConfig, ExecuteMsg, InstantiateMsg, OsmosisConfig, QueryMsg,
+};
+use multi_dex_liquidity_helper::pool::DexPool;
+use test_case::test_matrix;
+
+use std::str::FromStr;
+
+const ASTROPORT_ARTIFACTS_DIR: &str = "../astroport-liquidity-helper/tests/astroport-artifacts";
+
+pub fn get_test_runner<'a>() -> OwnedTestRunner<'a> {
+    OwnedTestRunner::MultiTest(MultiTestRunner::new("osmo"))
+}
+
+#[test_matrix(
+    [PairType::Xyk {}, PairType::Stable {}, PairType::Custom("concentrated".to_string())],
+    [true, false]
+)]
+/// Tests BalancingProvideLiquidity into an Astroport pair given as a `DexPool`
+/// or by its pair address
+pub fn test_balancing_provide_liquidity_astroport(pair_type: PairType, by_identifier: bool) {
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = init_admin(&runner);
+    let (liquidity_helper, pool) = setup_astroport_pool(&runner, &admin, pair_type);
+    let pool_input: PoolInput = if by_identifier {
+        PoolIdentifier::AstroportPair {
+            address: pool.pair_addr.to_string(),
+        }
+        .into()
+    } else {
+        to_json_binary(&DexPool::from(pool.clone())).unwrap().into()
+    };
+    let lp_balance_before =
+        query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string());
+
+    let assets: AssetList = vec![Coin::new(1_000_000, "uluna")].into();
+    let simulation: SimulateBalancingProvideLiquidityResponse = Wasm::new(&runner)
+        .query(
+            liquidity_helper.addr().as_ref(),
+            &QueryMsg::SimulateBalancingProvideLiquidity {
+                assets: assets.clone().into(),
+                pool: pool_input.clone(),
+                initial_price: None,
+            },
+        )
+        .unwrap();
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets,
+            Uint128::zero(),
+            pool_input,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    // The events of the Astroport liquidity helper are named after this
+    // contract
+    let event_types: Vec<&str> = res.events.iter().map(|e| e.ty.as_str()).collect();
+    assert!(event_types
+        .iter()
+        .any(|ty| ty.starts_with("wasm-apollo/multi-dex-liquidity-helper/")));
+    assert!(!event_types
+        .iter()
+        .any(|ty| ty.contains("apollo/astroport-liquidity-helper/")));
+
+    let lp_received = query_cw20_balance(&runner, admin.address(), &pool.lp_token.to_string())
+        - lp_balance_before;
+    assert_eq!(simulation.lp_tokens.info, pool.lp_token);
+    assert!(!lp_received.is_zero());
+
+    // The contract should not hold any of the pool assets
+    let contract_addr = liquidity_helper.addr().to_string();
+    assert_eq!(
+        query_token_balance(&runner, &contract_addr, "uluna"),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_cw20_balance(&runner, &contract_addr, &pool.pool_assets[1].to_string()),
+        Uint128::zero()
+    );
+}
+
+#[test]
+/// Tests BalancingWithdrawLiquidity from an Astroport pair given as a
+/// `DexPool`, which goes through the `Pool` implementation of `DexPool`
+pub fn test_balancing_withdraw_liquidity_astroport() {
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = init_admin(&runner);
+    let (liquidity_helper, pool) = setup_astroport_pool(&runner, &admin, PairType::Xyk {});
+    let lp_token = pool.lp_token.to_string();
+    let uluna_balance_before = query_token_balance(&runner, &admin.address(), "uluna");
+    let lp_balance_before = query_cw20_balance(&runner, admin.address(), &lp_token);
+
+    let lp_amount = lp_balance_before / Uint128::new(10);
+    let msgs = liquidity_helper
+        .balancing_withdraw_liquidity(
+            Asset::new(pool.lp_token.clone(), lp_amount),
+            to_json_binary(&DexPool::from(pool.clone())).unwrap(),
+            pool.pool_assets[0].clone(),
+            Uint128::one(),
+            None,
+        )
+        .unwrap();
+    runner
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+
+    let uluna_received =
+        query_token_balance(&runner, &admin.address(), "uluna") - uluna_balance_before;
+    let lp_balance_after = query_cw20_balance(&runner, admin.address(), &lp_token);
+    assert_eq!(lp_balance_before - lp_balance_after, lp_amount);
+    assert!(!uluna_received.is_zero());
+}
+
+#[test]
+/// Tests that pools of all DEXes can be paused in the same contract
+pub fn test_update_pause_pools_of_each_dex() {
+    let owned_runner = get_test_runner();
+    let runner = owned_runner.as_ref();
+    let admin = init_admin(&runner);
+    let (liquidity_helper, pool) = setup_astroport_pool(&runner, &admin, PairType::Xyk {});
+    let pools = vec![
+        PoolIdentifier::AstroportPair {
+            address: pool.pair_addr.to_string(),
+        },
+        PoolIdentifier::OsmosisPool { pool_id: 1 },
+    ];
+
+    for pool in &pools {
+        let msg = liquidity_helper
+            .call(
+                ExecuteMsg::UpdatePause {
+                    pool: Some(pool.clone()),
+                    paused: true,
+                },
+                vec![],
+            )
+            .unwrap();
+        runner
+            .execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], &admin)
+            .unwrap();
+    }
+
+    let pause_info: PauseInfoResponse = Wasm::new(&runner)
+        .query(liquidity_helper.addr().as_ref(), &QueryMsg::PauseInfo {})
+        .unwrap();
+    assert!(!pause_info.global);
+    for pool in &pools {
+        assert!(pause_info.pools.contains(pool));
+    }
+}
+
+fn init_admin<'a>(runner: &'a TestRunner<'a>) -> SigningAccount {
+    runner
+        .init_account(&[
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uluna"),
+            coin(1_000_000_000_000_000_000_000_000_000_000_000u128, "uosmo"),
+        ])
+        .unwrap()
+}
+
+/// Sets up Astroport and the liquidity helper, and creates a uluna/ASTRO pair
+/// of the given type with liquidity provided by `admin`
+fn setup_astroport_pool<'a>(
+    runner: &'a TestRunner<'a>,
+    admin: &SigningAccount,
+    pair_type: PairType,
+) -> (LiquidityHelper, AstroportPool) {
+    let astroport_contracts =
+        get_local_contracts(runner, &Some(ASTROPORT_ARTIFACTS_DIR), false, &None);
+    let astroport_contracts = &setup_astroport(runner, astroport_contracts, admin);
+    let wasm = Wasm::new(runner);
+
+    // Set uluna precision in native coin registry
+    wasm.execute(
+        &astroport_contracts.coin_registry.address,
+        &cw_dex_astroport::astroport::native_coin_registry::ExecuteMsg::Add {
+            native_coins: vec![("uluna".to_string(), 6)],
+        },
+        &[],
+        admin,
+    )
+    .unwrap();
+
+    let liquidity_helper_code_id = runner
+        .store_code(
+            ContractType::MultiTestContract(Box::new(
+                ContractWrapper::new(
+                    multi_dex_liquidity_helper::contract::execute,
+                    multi_dex_liquidity_helper::contract::instantiate,
+                    multi_dex_liquidity_helper::contract::query,
+                )
+                .with_reply(multi_dex_liquidity_helper::contract::reply),
+            )),
+            admin,
+        )
+        .unwrap();
+    let liquidity_helper = wasm
+        .instantiate(
+            liquidity_helper_code_id,
+            &InstantiateMsg {
+                owner: admin.address(),
+                astroport: AstroportConfig {
+                    factory: astroport_contracts.factory.address.clone(),
+                    liquidity_manager: astroport_contracts.liquidity_manager.address.clone(),
+                    incentives: Some(astroport_contracts.incentives.address.clone()),
+                },
+                osmosis: OsmosisConfig {
+                    max_join_slippage: None,
+                },
+            },
+            Some(&admin.address()),
+            Some("Multi DEX Liquidity Helper"),
+            &[],
+            admin,
+        )
+        .unwrap()
+        .data
+        .address;
+    let liquidity_helper = LiquidityHelper::new(Addr::unchecked(liquidity_helper));
+
+    // Create the pair
+    let astro_token = astroport_contracts.astro_token.address.clone();
+    let asset_infos: [AstroAssetInfo; 2] = [
+        AstroAssetInfo::NativeToken {
+            denom: "uluna".into(),
+        },
+        AstroAssetInfo::Token {
+            contract_addr: Addr::unchecked(&astro_token),
+        },
+    ];
+    let init_params = match &pair_type {
+        PairType::Stable {} => Some(
+            to_json_binary(&StablePoolParams {
+                amp: 10u64,
+                owner: None,
+            })
+            .unwrap(),
+        ),
+        PairType::Custom(_) => Some(
+            to_json_binary(
+                &cw_dex_astroport::astroport::pair_concentrated::ConcentratedPoolParams {
+                    amp: Decimal::from_str("40").unwrap(),
+                    gamma: Decimal::from_str("0.000145").unwrap(),
+                    mid_fee: Decimal::from_str("0.0026").unwrap(),
+                    out_fee: Decimal::from_str("0.0045").unwrap(),
+                    fee_gamma: Decimal::from_str("0.00023").unwrap(),
+                    repeg_profit_threshold: Decimal::from_str("0.000002").unwrap(),
+                    min_price_scale_delta: Decimal::from_str("0.000146").unwrap(),
+                    price_scale: Decimal::one(),
+                    ma_half_time: 600,
+                    track_asset_balances: None,
+                },
+            )
+            .unwrap(),
+        ),
+        _ => None,
+    };
+    let (pair_addr, lp_token) = create_astroport_pair(
+        runner,
+        &astroport_contracts.factory.address,
+        pair_type.clone(),
+        asset_infos.clone(),
+        init_params,
+        admin,
+        None,
+    );
+
+    // Add liquidity
+    let reserve = Uint128::from(1_000_000_000_000u128);
+    wasm.execute(
+        &astro_token,
+        &Cw20ExecuteMsg::IncreaseAllowance {
+            spender: pair_addr.clone(),
+            amount: reserve,
+            expires: None,
+        },
+        &[],
+        admin,
+    )
+    .unwrap();
+    wasm.execute(
+        &pair_addr,
+        &PairExecuteMsg::ProvideLiquidity {
+            assets: asset_infos
+                .into_iter()
+                .map(|info| AstroAsset {
+                    info,
+                    amount: reserve,
+                })
+                .collect(),
+            slippage_tolerance: Some(Decimal::from_str("0.02").unwrap()),
+            auto_stake: Some(false),
+            receiver: None,
+        },
+        &[coin(reserve.u128(), "uluna")],
+        admin,
+    )
+    .unwrap();
+
+    let pool = AstroportPool {
+        lp_token: AssetInfo::cw20(Addr::unchecked(&lp_token)),
+        pair_addr: Addr::unchecked(pair_addr),
+        pair_type,
+        pool_assets: vec![
+            AssetInfo::native("uluna".to_string()),
+            AssetInfo::cw20(Addr::unchecked(&astro_token)),
+        ],
+        liquidity_manager: Addr::unchecked(astroport_contracts.liquidity_manager.address.clone()),
+    };
+
+    (liquidity_helper, pool)
+}
+
+fn query_token_balance<'a, R>(runner: &'a R, address: &str, denom: &str) -> Uint128
+where
+    R: Runner<'a>,
+{
+    let balance = Bank::new(runner)
+        .query_balance(&QueryBalanceRequest {
+            address: address.to_string(),
+            denom: denom.to_string(),
+        })
+        .unwrap()
+        .balance
+        .unwrap_or_default()
+        .amount;
+    Uint128::from_str(&balance).unwrap()
+}
+
+fn query_cw20_balance<'a, R, S>(runner: &'a R, address: S, contract_addr: &str) -> Uint128
+where
+    R: Runner<'a>,
+    S: Into<String>,
+{
+    let res: BalanceResponse = Wasm::new(runner)
+        .query(
+            contract_addr,
+            &Cw20QueryMsg::Balance {
+                address: address.into(),
+            },
+        )
+        .unwrap();
+    res.balance
+}
@@ -0,0 +1,239 @@
+//! Tests of the Osmosis pools of the multi DEX liquidity helper. They need the
+//! Osmosis test app and the compiled contract, so they only run with the
+//! `osmosis-test-tube` feature, after building the contract for wasm.
+
+#![cfg(feature = "osmosis-test-tube")]
+
+use apollo_cw_asset::AssetInfo;
+use cosmwasm_std::{from_json, to_json_binary, Addr, Coin, Decimal, Uint128};
+use cw_dex_osmosis::OsmosisPool;
+use cw_it::osmosis_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use cw_it::osmosis_std::types::cosmwasm::wasm::v1::MsgExecuteContractResponse;
+use cw_it::osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
+    CreateConcentratedLiquidityPoolsProposal, MsgCreatePosition, Pool as ConcentratedPool,
+    PoolRecord, PoolsRequest, PositionByIdRequest,
+};
+use cw_it::osmosis_test_tube::{
+    Account, ConcentratedLiquidity, Gamm, GovWithAppAccess, Module, OsmosisTestApp, Runner,
+    SigningAccount, Wasm,
+};
+use liquidity_helper::msg::{PoolIdentifier, PoolInput, ProvideLiquidityResponse};
+use liquidity_helper::LiquidityHelper;
+use multi_dex_liquidity_helper::msg::{AstroportConfig, InstantiateMsg, OsmosisConfig};
+use multi_dex_liquidity_helper::pool::{ConcentratedLiquidityDexPool, DexPool};
+use osmosis_liquidity_helper::math::{MAX_TICK, MIN_INITIALIZED_TICK};
+use osmosis_liquidity_helper::msg::{ConcentratedLiquidityPool, CreatePositionResponse, TickRange};
+use test_case::test_case;
+
+pub const WASM_FILE: &str =
+    "../../target/wasm32-unknown-unknown/release/multi_dex_liquidity_helper.wasm";
+
+#[test_case(false ; "Bindings: Pool given as DexPool")]
+#[test_case(true ; "Bindings: Pool given by its ID")]
+/// Tests BalancingProvideLiquidity into an Osmosis balancer pool
+pub fn test_balancing_provide_liquidity_osmosis(by_identifier: bool) {
+    let app = OsmosisTestApp::default();
+    let admin = init_admin(&app);
+    let liquidity_helper = setup_liquidity_helper(&app, &admin);
+
+    let pool_id = Gamm::new(&app)
+        .create_basic_pool(
+            &[Coin::new(1_000_000, "uatom"), Coin::new(1_000_000, "uosmo")],
+            &admin,
+        )
+        .unwrap()
+        .data
+        .pool_id;
+    let pool_input: PoolInput = if by_identifier {
+        PoolIdentifier::OsmosisPool { pool_id }.into()
+    } else {
+        to_json_binary(&DexPool::from(OsmosisPool::unchecked(pool_id)))
+            .unwrap()
+            .into()
+    };
+
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            vec![Coin::new(100_000, "uatom")].into(),
+            Uint128::one(),
+            pool_input,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = app
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+    let provide_res: ProvideLiquidityResponse = from_json(res.data.data).unwrap();
+    assert_eq!(
+        provide_res.lp_token,
+        AssetInfo::native(format!("gamm/pool/{pool_id}"))
+    );
+    assert!(!provide_res.lp_amount.is_zero());
+
+    // The events of the Osmosis liquidity helper are named after this contract
+    assert!(res
+        .events
+        .iter()
+        .any(|e| e.ty.starts_with("wasm-apollo/multi-dex-liquidity-helper/")));
+    assert!(!res
+        .events
+        .iter()
+        .any(|e| e.ty.contains("apollo/osmosis-liquidity-helper/")));
+}
+
+#[test_case(None ; "Bindings: CL pool given by its ID")]
+#[test_case(Some(TickRange::FullRange {}) ; "Bindings: CL pool given serialised, full range")]
+#[test_case(Some(TickRange::Ticks { lower_tick: -1_000_000, upper_tick: 500_000 }) ; "Bindings: CL pool given serialised, custom range")]
+/// Tests BalancingProvideLiquidity into an Osmosis concentrated liquidity
+/// pool, given by its ID or as a `ConcentratedLiquidityDexPool` with a range
+pub fn test_create_position_osmosis(tick_range: Option<TickRange>) {
+    let app = OsmosisTestApp::default();
+    let admin = init_admin(&app);
+    let liquidity_helper = setup_liquidity_helper(&app, &admin);
+    let cl = ConcentratedLiquidity::new(&app);
+    let pool_id = create_concentrated_pool(&app, &admin);
+
+    // Add full range liquidity at a price of one
+    cl.create_position(
+        MsgCreatePosition {
+            pool_id,
+            sender: admin.address(),
+            lower_tick: MIN_INITIALIZED_TICK,
+            upper_tick: MAX_TICK,
+            tokens_provided: vec![
+                ProtoCoin {
+                    denom: "uatom".to_string(),
+                    amount: "1000000000".to_string(),
+                },
+                ProtoCoin {
+                    denom: "uosmo".to_string(),
+                    amount: "1000000000".to_string(),
+                },
+            ],
+            token_min_amount0: "0".to_string(),
+            token_min_amount1: "0".to_string(),
+        },
+        &admin,
+    )
+    .unwrap();
+
+    let pool_input: PoolInput = match tick_range {
+        Some(tick_range) => {
+            to_json_binary(&ConcentratedLiquidityDexPool::OsmosisConcentratedLiquidity(
+                ConcentratedLiquidityPool {
+                    pool_id,
+                    tick_range,
+                },
+            ))
+            .unwrap()
+            .into()
+        }
+        None => PoolIdentifier::OsmosisPool { pool_id }.into(),
+    };
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            vec![Coin::new(1_000_000, "uatom")].into(),
+            Uint128::one(),
+            pool_input,
+            None,
+            Some(Decimal::percent(1)),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = app
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &admin)
+        .unwrap();
+    let create_position_res: CreatePositionResponse = from_json(res.data.data).unwrap();
+    assert!(!create_position_res.liquidity.is_zero());
+
+    // The position should belong to the caller
+    let position = cl
+        .query_position_by_id(&PositionByIdRequest {
+            position_id: create_position_res.position_id,
+        })
+        .unwrap()
+        .position
+        .unwrap()
+        .position
+        .unwrap();
+    assert_eq!(position.address, admin.address());
+    assert_eq!(position.pool_id, pool_id);
+}
+
+fn init_admin(app: &OsmosisTestApp) -> SigningAccount {
+    app.init_account(&[
+        Coin::new(1_000_000_000_000, "uatom"),
+        Coin::new(1_000_000_000_000, "uosmo"),
+    ])
+    .unwrap()
+}
+
+/// Stores and instantiates the liquidity helper. Astroport is not deployed on
+/// the Osmosis test app, so its config is set to the admin.
+fn setup_liquidity_helper(app: &OsmosisTestApp, admin: &SigningAccount) -> LiquidityHelper {
+    let wasm = Wasm::new(app);
+    let wasm_byte_code = std::fs::read(WASM_FILE).unwrap();
+    let code_id = wasm
+        .store_code(&wasm_byte_code, None, admin)
+        .unwrap()
+        .data
+        .code_id;
+    let contract_addr = wasm
+        .instantiate(
+            code_id,
+            &InstantiateMsg {
+                owner: admin.address(),
+                astroport: AstroportConfig {
+                    factory: admin.address(),
+                    liquidity_manager: admin.address(),
+                    incentives: None,
+                },
+                osmosis: OsmosisConfig {
+                    max_join_slippage: None,
+                },
+            },
+            Some(&admin.address()),
+            Some("Multi DEX Liquidity Helper"),
+            &[],
+            admin,
+        )
+        .unwrap()
+        .data
+        .address;
+
+    LiquidityHelper::new(Addr::unchecked(contract_addr))
+}
+
+/// Creates a uatom/uosmo concentrated liquidity pool through governance and
+/// returns its id
+fn create_concentrated_pool(app: &OsmosisTestApp, signer: &SigningAccount) -> u64 {
+    GovWithAppAccess::new(app)
+        .propose_and_execute(
+            CreateConcentratedLiquidityPoolsProposal::TYPE_URL.to_string(),
+            CreateConcentratedLiquidityPoolsProposal {
+                title: "Create CL pool".to_string(),
+                description: "Create CL pool".to_string(),
+                pool_records: vec![PoolRecord {
+                    denom0: "uatom".to_string(),
+                    denom1: "uosmo".to_string(),
+                    tick_spacing: 100,
+                    spread_factor: Decimal::permille(3).atomics().to_string(),
+                }],
+            },
+            signer.address(),
+            signer,
+        )
+        .unwrap();
+    let pools = ConcentratedLiquidity::new(app)
+        .query_pools(&PoolsRequest { pagination: None })
+        .unwrap();
+    ConcentratedPool::try_from(pools.pools.last().unwrap().clone())
+        .unwrap()
+        .id
+}
@@ -1,29 +1,193 @@
-use apollo_cw_asset::{Asset, AssetList};
+use std::str::FromStr;
+
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, from_json, Addr, Binary, Deps, DepsMut, Env, Event, MessageInfo, Response, StdError,
-    StdResult, Uint128,
+    attr, from_json, to_json_binary, Addr, Api, Binary, Decimal, Decimal256, Deps, DepsMut, Env,
+    MessageInfo, Reply, Response, StdError, StdResult, SubMsg, Uint128, Uint256,
 };
-use cw2::set_contract_version;
 use cw_dex::traits::Pool;
+use cw_dex_osmosis::osmosis_std::shim::{Duration, Timestamp};
+use cw_dex_osmosis::osmosis_std::types::cosmos::authz::v1beta1::{MsgExec, MsgExecResponse};
+use cw_dex_osmosis::osmosis_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use cw_dex_osmosis::osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
+    MsgCreatePosition, MsgCreatePositionResponse, MsgTransferPositions, Pool as ConcentratedPool,
+};
+use cw_dex_osmosis::osmosis_std::types::osmosis::gamm::v1beta1::{
+    GammQuerier, Pool as BalancerPool,
+};
+use cw_dex_osmosis::osmosis_std::types::osmosis::lockup::{MsgLockTokens, MsgLockTokensResponse};
+use cw_dex_osmosis::osmosis_std::types::osmosis::poolmanager::v1beta1::{
+    MsgSwapExactAmountIn, PoolmanagerQuerier, SwapAmountInRoute,
+};
+use cw_dex_osmosis::osmosis_std::types::osmosis::superfluid::{
+    MsgLockAndSuperfluidDelegate, MsgLockAndSuperfluidDelegateResponse,
+};
+use cw_dex_osmosis::osmosis_std::types::osmosis::twap::v1beta1::TwapQuerier;
 use cw_dex_osmosis::OsmosisPool;
+use liquidity_helper::contract::{
+    query_balance_increase, query_balances, reply_on_last_message, BalancingProvideLiquidityArgs,
+};
+use liquidity_helper::msg::{
+    BalancingSwap, PoolIdentifier, PoolInput, PostProvideAction, ProvideLiquidityResponse,
+    SimulateBalancingProvideLiquidityResponse,
+};
+use liquidity_helper::{LiquidityHelperContract, LiquidityHelperError};
 
 use crate::error::ContractError;
-use crate::msg::{CallbackMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::math::{
+    calc_cl_balancing_swap, calc_cl_position_amounts, calc_initial_provide_amounts,
+    calc_noswap_join, calc_weighted_balancing_swap, tick_to_price, MAX_TICK, MIN_INITIALIZED_TICK,
+};
+use crate::msg::{
+    ConcentratedLiquidityPool, CreatePositionResponse, ExecuteMsg, InstantiateMsg, MigrateMsg,
+    QueryMsg, TickRange,
+};
+use crate::state::{
+    CreatePositionState, PostProvideState, ProvideLiquidityState, SingleSidedJoin,
+    CREATE_POSITION_STATE, DEFAULT_MAX_JOIN_SLIPPAGE, MAX_JOIN_SLIPPAGE, POST_PROVIDE_STATE,
+    PROVIDE_LIQUIDITY_STATE,
+};
+
+const PROVIDE_LIQUIDITY_REPLY_ID: u64 = 1;
+const BALANCING_SWAP_REPLY_ID: u64 = 4;
+const CREATE_POSITION_SWAP_REPLY_ID: u64 = 5;
+const CREATE_POSITION_REPLY_ID: u64 = 6;
+const POST_PROVIDE_REPLY_ID: u64 = 9;
+
+/// The number of seconds that the TWAP `max_price_deviation` is checked
+/// against is calculated over
+pub const TWAP_WINDOW: u64 = 600;
+
+/// The Osmosis liquidity helper
+pub struct OsmosisLiquidityHelper;
+
+impl LiquidityHelperContract for OsmosisLiquidityHelper {
+    type Pool = OsmosisPool;
+    type Error = ContractError;
+
+    const CONTRACT_NAME: &'static str = "osmosis-liquidity-helper";
+    const CONTRACT_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    fn parse_pool(deps: Deps, pool: PoolInput) -> Result<OsmosisPool, ContractError> {
+        parse_pool(deps, pool)
+    }
+
+    fn validate_pool_identifier(
+        _api: &dyn Api,
+        identifier: PoolIdentifier,
+    ) -> Result<PoolIdentifier, ContractError> {
+        match identifier {
+            PoolIdentifier::OsmosisPool { .. } => Ok(identifier),
+            identifier => {
+                Err(LiquidityHelperError::UnsupportedPoolIdentifier { identifier }.into())
+            }
+        }
+    }
+
+    fn query_pool_assets(deps: Deps, pool: &PoolInput) -> Result<Vec<AssetInfo>, ContractError> {
+        match parse_concentrated_liquidity_pool(deps, pool) {
+            Some(pool) => {
+                let cl_pool = query_concentrated_pool(deps, pool.pool_id)?;
+                Ok(vec![
+                    AssetInfo::native(&cl_pool.token0),
+                    AssetInfo::native(&cl_pool.token1),
+                ])
+            }
+            None => Ok(parse_pool(deps, pool.clone())?.pool_assets(deps)?),
+        }
+    }
+
+    fn balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        args: BalancingProvideLiquidityArgs,
+    ) -> Result<Response, ContractError> {
+        if let Some(pool) = parse_concentrated_liquidity_pool(deps.as_ref(), &args.pool) {
+            // Positions are not LP tokens that could be locked
+            if let Some(action) = args.post_provide {
+                return Err(LiquidityHelperError::UnsupportedPostProvideAction { action }.into());
+            }
+            return execute_create_position(
+                deps,
+                env,
+                info,
+                args.assets,
+                args.min_out,
+                pool,
+                args.recipient,
+                args.max_swap_slippage,
+                args.initial_price,
+                args.max_price_deviation,
+                args.assets_held,
+            );
+        }
+        // Balancer and stableswap pools are created with liquidity, so
+        // `initial_price` does not apply to them
+        let pool = parse_pool(deps.as_ref(), args.pool)?;
+        execute_balancing_provide_liquidity(
+            deps,
+            env,
+            info,
+            args.assets,
+            args.min_out,
+            pool,
+            args.recipient,
+            args.max_swap_slippage,
+            args.max_price_deviation,
+            args.post_provide,
+            args.assets_held,
+        )
+    }
 
-// version info for migration info
-const CONTRACT_NAME: &str = "crates.io:osmosis-liquidity-helper";
-const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+    fn reply_balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        msg: Reply,
+    ) -> Result<Response, ContractError> {
+        match msg.id {
+            PROVIDE_LIQUIDITY_REPLY_ID => reply_provide_liquidity(deps, env),
+            BALANCING_SWAP_REPLY_ID => reply_balancing_swap(deps, env),
+            CREATE_POSITION_SWAP_REPLY_ID => reply_create_position_swap(deps, env),
+            CREATE_POSITION_REPLY_ID => reply_create_position(deps, env, msg),
+            POST_PROVIDE_REPLY_ID => reply_post_provide(deps, msg),
+            id => Err(LiquidityHelperError::UnknownReplyId { id }.into()),
+        }
+    }
+
+    fn simulate_balancing_provide_liquidity(
+        deps: Deps,
+        env: Env,
+        assets: AssetList,
+        pool: OsmosisPool,
+        _initial_price: Option<Decimal>,
+    ) -> Result<SimulateBalancingProvideLiquidityResponse, ContractError> {
+        // Positions are not LP tokens, so they don't fit the response
+        if query_concentrated_pool(deps, pool.pool_id()).is_ok() {
+            return Err(ContractError::UnsupportedConcentratedLiquiditySimulation {
+                pool_id: pool.pool_id(),
+            });
+        }
+        query_simulate_balancing_provide_liquidity(deps, env, assets, pool)
+    }
+
+    // The twap module keeps the price history, so there is nothing to record
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     _env: Env,
     _info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    OsmosisLiquidityHelper::instantiate(deps.branch(), &msg.owner)?;
+
+    if let Some(max_join_slippage) = msg.max_join_slippage {
+        MAX_JOIN_SLIPPAGE.save(deps.storage, &max_join_slippage)?;
+    }
 
     Ok(Response::default())
 }
@@ -35,46 +199,52 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    match msg {
-        ExecuteMsg::BalancingProvideLiquidity {
-            assets,
-            min_out,
-            pool,
-            recipient,
-        } => {
-            let assets = assets.check(deps.api)?;
-            let pool: OsmosisPool = from_json(pool)?;
-            execute_balancing_provide_liquidity(deps, env, info, assets, min_out, pool, recipient)
-        }
-        ExecuteMsg::Callback(msg) => {
-            // Only contract can call callbacks
-            if info.sender != env.contract.address {
-                return Err(ContractError::Unauthorized {});
-            }
+    OsmosisLiquidityHelper::execute(deps, env, info, msg)
+}
 
-            match msg {
-                CallbackMsg::SingleSidedJoin { asset, pool } => {
-                    execute_callback_single_sided_join(deps, env, info, asset, pool)
-                }
-                CallbackMsg::ReturnLpTokens {
-                    pool,
-                    balance_before,
-                    recipient,
-                    min_out,
-                } => execute_callback_return_lp_tokens(
-                    deps,
-                    env,
-                    info,
-                    pool,
-                    balance_before,
-                    recipient,
-                    min_out,
-                ),
-            }
+/// Deserialises the pool or looks it up by its ID
+fn parse_pool(deps: Deps, pool: PoolInput) -> Result<OsmosisPool, ContractError> {
+    match pool {
+        PoolInput::Serialized(pool) => Ok(from_json(pool)?),
+        PoolInput::Identifier(PoolIdentifier::OsmosisPool { pool_id }) => {
+            Ok(OsmosisPool::new(pool_id, deps)?)
+        }
+        PoolInput::Identifier(identifier) => {
+            Err(LiquidityHelperError::UnsupportedPoolIdentifier { identifier }.into())
         }
     }
 }
 
+/// Returns the concentrated liquidity pool to create a position in, if `pool`
+/// is one. A concentrated liquidity pool given by its ID is provided to in the
+/// full range.
+fn parse_concentrated_liquidity_pool(
+    deps: Deps,
+    pool: &PoolInput,
+) -> Option<ConcentratedLiquidityPool> {
+    match pool {
+        PoolInput::Serialized(pool) => from_json(pool).ok(),
+        PoolInput::Identifier(PoolIdentifier::OsmosisPool { pool_id }) => {
+            query_concentrated_pool(deps, *pool_id)
+                .ok()
+                .map(|_| ConcentratedLiquidityPool {
+                    pool_id: *pool_id,
+                    tick_range: TickRange::FullRange {},
+                })
+        }
+        PoolInput::Identifier(_) => None,
+    }
+}
+
+/// Provides liquidity with the given assets. If more than one asset is given
+/// to a two asset balancer pool, the excess of one asset is swapped into the
+/// other and the assets are provided double sided in the reply. Otherwise as
+/// much as possible is provided double sided and the remainder is provided
+/// single sided in the replies. If `max_swap_slippage` is given, the balancing
+/// swap must return at least the amount expected when executing this message,
+/// minus the slippage. Each single sided join must return at least the amount
+/// expected when executing this message, minus `MAX_JOIN_SLIPPAGE`.
+#[allow(clippy::too_many_arguments)]
 pub fn execute_balancing_provide_liquidity(
     deps: DepsMut,
     env: Env,
@@ -82,142 +252,1127 @@ pub fn execute_balancing_provide_liquidity(
     mut assets: AssetList,
     min_out: Uint128,
     pool: OsmosisPool,
-    recipient: Option<String>,
+    recipient: Addr,
+    max_swap_slippage: Option<Decimal>,
+    max_price_deviation: Option<Decimal>,
+    post_provide: Option<PostProvideAction>,
+    assets_held: bool,
 ) -> Result<Response, ContractError> {
-    // Assert that sent funds match input assets
-    if assets != info.funds.clone().into() {
-        return Err(ContractError::InputTokenMismatch {
+    OsmosisLiquidityHelper::assert_not_paused(
+        deps.as_ref(),
+        &PoolIdentifier::OsmosisPool {
+            pool_id: pool.pool_id(),
+        },
+    )?;
+    if let Some(action @ PostProvideAction::Stake {}) = post_provide {
+        return Err(LiquidityHelperError::UnsupportedPostProvideAction { action }.into());
+    }
+
+    // Assert that sent funds match input assets, unless they were routed into
+    // pool assets first and are already held by the contract
+    if !assets_held && assets != info.funds.clone().into() {
+        return Err(LiquidityHelperError::InputTokenMismatch {
             expected: info.funds.iter().map(|a| a.into()).collect(),
             received: assets.to_vec(),
-        });
+        }
+        .into());
     }
 
-    // Unwrap recipient or use caller's address
-    let recipient = recipient.map_or(Ok(info.sender), |x| deps.api.addr_validate(&x))?;
-
     let mut event_attrs = vec![attr("assets", assets.to_string())];
 
-    let response = if assets.len() == 1 {
+    // Query current contract LP token balance
+    let lp_token_balance = pool
+        .lp_token()
+        .query_balance(&deps.querier, env.contract.address.to_string())?;
+
+    // Check the balances of the pool assets before the funds were received, so
+    // that anything not used can be refunded in the last reply
+    let pool_assets: Vec<AssetInfo> = pool
+        .get_pool_liquidity(deps.as_ref())?
+        .into_iter()
+        .map(|x| x.info.clone())
+        .collect();
+    let received: AssetList = if assets_held {
+        assets.clone()
+    } else {
+        info.funds.clone().into()
+    };
+    let mut balances_before = query_balances(deps.as_ref(), &env, &pool_assets)?;
+    for asset in received.iter().filter(|x| pool_assets.contains(&x.info)) {
+        balances_before.deduct(asset)?;
+    }
+
+    // Check the spot price against the TWAP before the balancing swap is
+    // calculated from the reserves
+    if let Some(max_price_deviation) = max_price_deviation {
+        assert_price_deviation(
+            deps.as_ref(),
+            &env,
+            pool.pool_id(),
+            &pool_assets[0].to_string(),
+            &pool_assets[1].to_string(),
+            max_price_deviation,
+        )?;
+    }
+
+    let mut state = ProvideLiquidityState {
+        pool,
+        lp_balance_before: lp_token_balance,
+        balances_before,
+        swapped: None,
+        return_balance_before: Uint128::zero(),
+        provide_assets: AssetList::new(),
+        single_sided_joins: vec![],
+        min_out,
+        recipient,
+        post_provide,
+    };
+
+    let balancing_swap = if assets.len() > 1 {
+        calc_balancing_swap(deps.as_ref(), &assets, &pool)?
+    } else {
+        None
+    };
+
+    let res = if let Some((offer_asset, return_asset)) = balancing_swap {
+        event_attrs.push(attr("action", "balancing_swap_provide_liquidity"));
+
+        // Swap the excess of one asset into the other, and then provide the
+        // balanced assets double sided in the reply
+        let swap_min_out = max_swap_slippage.map_or(Uint128::one(), |x| {
+            (return_asset.amount * Decimal::one().saturating_sub(x)).max(Uint128::one())
+        });
+        let swap_res = pool.swap(
+            deps.as_ref(),
+            &env,
+            offer_asset.clone(),
+            return_asset.info.clone(),
+            swap_min_out,
+        )?;
+
+        assets.deduct(&offer_asset)?;
+        state.return_balance_before = return_asset
+            .info
+            .query_balance(&deps.querier, &env.contract.address)?;
+        state.provide_assets = assets;
+        state.swapped = Some(BalancingSwap {
+            offer_asset,
+            return_asset,
+        });
+
+        reply_on_last_message(swap_res, BALANCING_SWAP_REPLY_ID)?
+    } else if assets.len() == 1 {
         event_attrs.push(attr("action", "single_sided_provide_liquidity"));
 
         // Provide single sided
-        pool.provide_liquidity(deps.as_ref(), &env, assets.clone(), min_out)?
+        let provide_res = pool.provide_liquidity(deps.as_ref(), &env, assets, min_out)?;
+        reply_on_last_message(provide_res, PROVIDE_LIQUIDITY_REPLY_ID)?
     } else {
         event_attrs.push(attr("action", "double_sided_provide_liquidity"));
 
-        // Provide as much as possible double sided, and then issue callbacks to
-        // provide the remainder single sided
+        // Provide as much as possible double sided, and then provide the
+        // remainder single sided in the replies
         let (lp_tokens_received, tokens_used) =
             pool.simulate_noswap_join(&deps.querier, &assets)?;
 
         // Get response with msg to provide double sided
-        let mut provide_res =
+        let provide_res =
             pool.provide_liquidity(deps.as_ref(), &env, assets.clone(), lp_tokens_received)?;
 
         // Deduct tokens used to get remaining tokens
         assets.deduct_many(&tokens_used)?;
 
-        // For each of the remaining tokens, issue a callback to provide
-        // liquidity single sided. These must be done as a callbacks, because
-        // the simulation inside pool.provide_liquidity will use the current
-        // reserves, which will be altered by each of the single sided joins,
-        // so the simulations will be incorrect unless we do them one at a time.
-        for asset in assets.into_iter() {
-            if asset.amount > Uint128::zero() {
-                let msg = CallbackMsg::SingleSidedJoin {
-                    asset: asset.clone(),
-                    pool,
-                }
-                .into_cosmos_msg(&env)?;
-                provide_res = provide_res.add_message(msg);
-            }
-        }
+        // Plan the single sided joins of the remaining tokens with the amount
+        // of LP tokens we expect from each of them
+        let max_join_slippage = MAX_JOIN_SLIPPAGE
+            .may_load(deps.storage)?
+            .unwrap_or(DEFAULT_MAX_JOIN_SLIPPAGE);
+        state.single_sided_joins =
+            simulate_single_sided_joins(deps.as_ref(), &pool, &assets, lp_tokens_received)?
+                .into_iter()
+                .map(|(asset, expected_lp_amount)| SingleSidedJoin {
+                    asset,
+                    min_out: single_sided_join_min_out(expected_lp_amount, max_join_slippage),
+                })
+                .collect();
 
-        provide_res
+        reply_on_last_message(provide_res, PROVIDE_LIQUIDITY_REPLY_ID)?
     };
 
-    // Query current contract LP token balance
-    let lp_token_balance = pool
-        .lp_token()
-        .query_balance(&deps.querier, env.contract.address.to_string())?;
+    // Save state to provide the remaining assets and return the LP tokens in
+    // the replies
+    PROVIDE_LIQUIDITY_STATE.save(deps.storage, &state)?;
 
-    // Callback to return LP tokens
-    let callback_msg = CallbackMsg::ReturnLpTokens {
-        pool,
-        balance_before: lp_token_balance,
-        recipient,
-        min_out,
+    let event = OsmosisLiquidityHelper::event("execute_balancing_provide_liquidity")
+        .add_attributes(event_attrs);
+    Ok(res.add_event(event))
+}
+
+/// Calculates the swap needed to balance the assets with the reserves of the
+/// pool, so that they can be provided with one double sided join. Returns
+/// `None` where the balancer math does not apply, i.e. if the pool is not a
+/// two asset balancer pool, if the assets are already balanced or if the swap
+/// would be too large for the pool. The assets are then provided with single
+/// sided joins instead.
+pub fn calc_balancing_swap(
+    deps: Deps,
+    assets: &AssetList,
+    pool: &OsmosisPool,
+) -> Result<Option<(Asset, Asset)>, ContractError> {
+    let pool_res = PoolmanagerQuerier::new(&deps.querier).pool(pool.pool_id())?;
+    let balancer_pool = match pool_res.pool {
+        Some(any) if any.type_url == BalancerPool::TYPE_URL => BalancerPool::try_from(any)
+            .map_err(|e| StdError::parse_err("osmosis.gamm.v1beta1.Pool", e.to_string()))?,
+        _ => return Ok(None),
+    };
+    if balancer_pool.pool_assets.len() != 2 {
+        return Ok(None);
     }
-    .into_cosmos_msg(&env)?;
 
-    let event = Event::new("apollo/osmosis-liquidity-helper/execute_balancing_provide_liquidity")
-        .add_attributes(event_attrs);
-    Ok(response.add_message(callback_msg).add_event(event))
+    let mut reserves: Vec<Asset> = vec![];
+    let mut weights: Vec<Uint128> = vec![];
+    for pool_asset in balancer_pool.pool_assets {
+        let token = pool_asset
+            .token
+            .ok_or_else(|| StdError::generic_err("Pool asset without token"))?;
+        reserves.push(Asset::new(
+            AssetInfo::native(token.denom),
+            Uint128::from_str(&token.amount)?,
+        ));
+        weights.push(Uint128::from_str(&pool_asset.weight)?);
+    }
+    if reserves.iter().any(|x| x.amount.is_zero())
+        || assets
+            .iter()
+            .any(|x| !reserves.iter().any(|y| y.info == x.info))
+    {
+        return Ok(None);
+    }
+
+    let swap_fee = balancer_pool
+        .pool_params
+        .map(|x| parse_dec(&x.swap_fee))
+        .transpose()?
+        .unwrap_or_default();
+
+    let assets_slice: [Asset; 2] = [0, 1].map(|i| {
+        assets
+            .find(&reserves[i].info)
+            .cloned()
+            .unwrap_or_else(|| Asset::new(reserves[i].info.clone(), Uint128::zero()))
+    });
+
+    let balancing_swap = calc_weighted_balancing_swap(
+        assets_slice,
+        [reserves[0].amount, reserves[1].amount],
+        [Decimal::from_ratio(weights[0], weights[1]), Decimal::one()],
+        swap_fee,
+    )?;
+
+    // If the assets are already balanced, or the return amount of the swap
+    // would be zero, there is nothing to swap
+    Ok(balancing_swap.filter(|(offer_asset, return_asset)| {
+        !offer_asset.amount.is_zero() && !return_asset.amount.is_zero()
+    }))
 }
 
-/// CallbackMsg handler to provide liquidity with the given assets. This needs
-/// to be a callback, rather than doing in the first ExecuteMsg, because
-/// pool.provide_liquidity does a simulation with current reserves, and our
-/// actions in the top level ExecuteMsg will alter the reserves, which means the
-/// reserves would be wrong in the provide liquidity simulation.
-pub fn execute_callback_single_sided_join(
-    deps: DepsMut,
-    env: Env,
-    _info: MessageInfo,
-    asset: Asset,
-    pool: OsmosisPool,
-) -> Result<Response, ContractError> {
-    let assets = AssetList::from(vec![asset.clone()]);
+/// Parses a decimal returned by a stargate query. Decimals are encoded as
+/// integers scaled by 10^18 in protobuf, but may also be returned with a
+/// decimal point.
+fn parse_dec256(value: &str) -> StdResult<Decimal256> {
+    if value.contains('.') {
+        Decimal256::from_str(value)
+    } else {
+        Ok(Decimal256::new(Uint256::from_str(value)?))
+    }
+}
 
-    let res = pool.provide_liquidity(deps.as_ref(), &env, assets, Uint128::one())?;
+/// Parses a decimal returned by a stargate query into a `Decimal`
+fn parse_dec(value: &str) -> StdResult<Decimal> {
+    Decimal::try_from(parse_dec256(value)?).map_err(|e| StdError::generic_err(e.to_string()))
+}
 
-    let event = Event::new("apollo/osmosis-liquidity-helper/execute_callback_single_sided_join")
-        .add_attribute("asset", asset.to_string());
+/// Returns an error if the spot price of `base_denom` in units of `quote_denom`
+/// deviates from its arithmetic TWAP over the last `TWAP_WINDOW` seconds by
+/// more than `max_price_deviation`. Fails if the pool is younger than that.
+fn assert_price_deviation(
+    deps: Deps,
+    env: &Env,
+    pool_id: u64,
+    base_denom: &str,
+    quote_denom: &str,
+    max_price_deviation: Decimal,
+) -> Result<(), ContractError> {
+    let start_time = env.block.time.minus_seconds(TWAP_WINDOW);
+    let twap = TwapQuerier::new(&deps.querier)
+        .arithmetic_twap_to_now(
+            pool_id,
+            base_denom.to_string(),
+            quote_denom.to_string(),
+            Some(Timestamp {
+                seconds: start_time.seconds() as i64,
+                nanos: 0,
+            }),
+        )?
+        .arithmetic_twap;
+    let spot = PoolmanagerQuerier::new(&deps.querier)
+        .spot_price(pool_id, base_denom.to_string(), quote_denom.to_string())?
+        .spot_price;
+    let twap = parse_dec(&twap)?;
+    let spot = parse_dec(&spot)?;
 
-    Ok(res.add_event(event))
+    let deviation = spot
+        .abs_diff(twap)
+        .checked_div(twap)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    if deviation > max_price_deviation {
+        return Err(ContractError::PriceDeviationExceeded {
+            max_price_deviation,
+            deviation,
+        });
+    }
+
+    Ok(())
 }
 
-pub fn execute_callback_return_lp_tokens(
+/// Creates a concentrated liquidity position with the given assets in the
+/// given tick range. If the assets are not in the ratio required by the range
+/// at the current price, the excess of one asset is swapped into the other
+/// first and the position is created in the reply. The position is
+/// transferred to the recipient, together with any assets that were not used.
+/// `min_out` is the minimum liquidity of the position.
+///
+/// The first position in an empty pool sets its price, so it is created
+/// without a balancing swap, with the assets trimmed to `initial_price` if one
+/// is given.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_position(
     deps: DepsMut,
     env: Env,
-    _info: MessageInfo,
-    pool: OsmosisPool,
-    balance_before: Uint128,
-    recipient: Addr,
+    info: MessageInfo,
+    mut assets: AssetList,
     min_out: Uint128,
+    pool: ConcentratedLiquidityPool,
+    recipient: Addr,
+    max_swap_slippage: Option<Decimal>,
+    initial_price: Option<Decimal>,
+    max_price_deviation: Option<Decimal>,
+    assets_held: bool,
 ) -> Result<Response, ContractError> {
-    let lp_token = pool.lp_token();
-    let lp_token_balance = lp_token.query_balance(&deps.querier, env.contract.address)?;
+    OsmosisLiquidityHelper::assert_not_paused(
+        deps.as_ref(),
+        &PoolIdentifier::OsmosisPool {
+            pool_id: pool.pool_id,
+        },
+    )?;
 
-    let return_amount = lp_token_balance.checked_sub(balance_before)?;
+    // Assert that sent funds match input assets, unless they were routed into
+    // pool assets first and are already held by the contract
+    if !assets_held && assets != info.funds.clone().into() {
+        return Err(LiquidityHelperError::InputTokenMismatch {
+            expected: info.funds.iter().map(|a| a.into()).collect(),
+            received: assets.to_vec(),
+        }
+        .into());
+    }
+
+    let cl_pool = query_concentrated_pool(deps.as_ref(), pool.pool_id)?;
+    let pool_assets = [
+        AssetInfo::native(&cl_pool.token0),
+        AssetInfo::native(&cl_pool.token1),
+    ];
+    if let Some(asset) = assets.iter().find(|x| !pool_assets.contains(&x.info)) {
+        return Err(ContractError::AssetNotInPool {
+            asset: asset.info.clone(),
+        });
+    }
+
+    let (lower_tick, upper_tick) = match pool.tick_range {
+        TickRange::FullRange {} => (MIN_INITIALIZED_TICK, MAX_TICK),
+        TickRange::Ticks {
+            lower_tick,
+            upper_tick,
+        } => (lower_tick, upper_tick),
+    };
+    if lower_tick >= upper_tick {
+        return Err(ContractError::InvalidTickRange {
+            lower_tick,
+            upper_tick,
+        });
+    }
+
+    let assets_slice = pool_assets.clone().map(|info| {
+        assets
+            .find(&info)
+            .cloned()
+            .unwrap_or_else(|| Asset::new(info, Uint128::zero()))
+    });
+
+    // The sqrt price of a pool is zero until the first position is created
+    let sqrt_price = parse_dec256(&cl_pool.current_sqrt_price)?;
+    let is_empty = sqrt_price.is_zero();
+
+    // Check the spot price against the TWAP before the balancing swap is
+    // calculated from the current price. Empty pools don't have a price yet.
+    if let (false, Some(max_price_deviation)) = (is_empty, max_price_deviation) {
+        assert_price_deviation(
+            deps.as_ref(),
+            &env,
+            pool.pool_id,
+            &cl_pool.token0,
+            &cl_pool.token1,
+            max_price_deviation,
+        )?;
+    }
+
+    // Calculate the swap needed to get the ratio of assets required by the
+    // range at the current price
+    let balancing_swap = if is_empty {
+        None
+    } else {
+        calc_cl_balancing_swap(
+            assets_slice.clone(),
+            sqrt_price,
+            tick_to_price(lower_tick)?.sqrt(),
+            tick_to_price(upper_tick)?.sqrt(),
+            parse_dec(&cl_pool.spread_factor)?,
+        )?
+    };
+
+    let mut state = CreatePositionState {
+        pool_id: pool.pool_id,
+        token0: cl_pool.token0,
+        token1: cl_pool.token1,
+        lower_tick,
+        upper_tick,
+        swapped: None,
+        return_balance_before: Uint128::zero(),
+        provide_assets: AssetList::new(),
+        min_out,
+        max_join_slippage: MAX_JOIN_SLIPPAGE
+            .may_load(deps.storage)?
+            .unwrap_or(DEFAULT_MAX_JOIN_SLIPPAGE),
+        recipient,
+    };
+
+    let event = OsmosisLiquidityHelper::event("execute_create_position")
+        .add_attribute("assets", assets.to_string())
+        .add_attribute("pool_id", pool.pool_id.to_string())
+        .add_attribute("lower_tick", lower_tick.to_string())
+        .add_attribute("upper_tick", upper_tick.to_string());
+
+    let Some((offer_asset, return_asset)) = balancing_swap else {
+        // The assets in excess of the initial price are not sent with the
+        // message, so they are returned as leftover in the reply
+        let position_assets: AssetList = match initial_price {
+            Some(price) if is_empty => {
+                let amounts =
+                    calc_initial_provide_amounts(assets_slice.clone().map(|x| x.amount), price)?;
+                assets_slice
+                    .into_iter()
+                    .zip(amounts)
+                    .map(|(asset, amount)| Asset::new(asset.info, amount))
+                    .collect::<Vec<_>>()
+                    .into()
+            }
+            _ => assets.clone(),
+        };
+        let msg = create_position_msg(&env, &state, &position_assets, sqrt_price)?;
+        state.provide_assets = assets;
+        CREATE_POSITION_STATE.save(deps.storage, &state)?;
+
+        return Ok(Response::new()
+            .add_submessage(SubMsg::reply_on_success(msg, CREATE_POSITION_REPLY_ID))
+            .add_event(event));
+    };
+
+    // Swap the excess of one asset into the other, and then create the
+    // position in the reply
+    let swap_min_out = max_swap_slippage.map_or(Uint128::one(), |x| {
+        (return_asset.amount * Decimal::one().saturating_sub(x)).max(Uint128::one())
+    });
+    let swap_msg = MsgSwapExactAmountIn {
+        sender: env.contract.address.to_string(),
+        routes: vec![SwapAmountInRoute {
+            pool_id: pool.pool_id,
+            token_out_denom: return_asset.info.to_string(),
+        }],
+        token_in: Some(ProtoCoin {
+            denom: offer_asset.info.to_string(),
+            amount: offer_asset.amount.to_string(),
+        }),
+        token_out_min_amount: swap_min_out.to_string(),
+    };
+
+    assets.deduct(&offer_asset)?;
+    state.return_balance_before = return_asset
+        .info
+        .query_balance(&deps.querier, &env.contract.address)?;
+    state.provide_assets = assets;
+    state.swapped = Some(BalancingSwap {
+        offer_asset,
+        return_asset,
+    });
+    CREATE_POSITION_STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(
+            swap_msg,
+            CREATE_POSITION_SWAP_REPLY_ID,
+        ))
+        .add_event(event))
+}
+
+/// Queries the concentrated liquidity pool with the given id
+fn query_concentrated_pool(deps: Deps, pool_id: u64) -> Result<ConcentratedPool, ContractError> {
+    match PoolmanagerQuerier::new(&deps.querier).pool(pool_id)?.pool {
+        Some(any) if any.type_url == ConcentratedPool::TYPE_URL => {
+            Ok(ConcentratedPool::try_from(any).map_err(|e| {
+                StdError::parse_err("osmosis.concentratedliquidity.v1beta1.Pool", e.to_string())
+            })?)
+        }
+        _ => Err(ContractError::NotConcentratedLiquidityPool { pool_id }),
+    }
+}
+
+/// Returns a message to create a concentrated liquidity position with the
+/// given assets in the range of the state. The position must use at least the
+/// amounts it is expected to use at the current `sqrt_price` of the pool, minus
+/// the `max_join_slippage` of the state.
+fn create_position_msg(
+    env: &Env,
+    state: &CreatePositionState,
+    assets: &AssetList,
+    sqrt_price: Decimal256,
+) -> StdResult<MsgCreatePosition> {
+    let amounts = [&state.token0, &state.token1].map(|denom| {
+        assets
+            .find(&AssetInfo::native(denom))
+            .map_or(Uint128::zero(), |x| x.amount)
+    });
+    let [token_min_amount0, token_min_amount1] = calc_cl_position_amounts(
+        amounts,
+        sqrt_price,
+        tick_to_price(state.lower_tick)?.sqrt(),
+        tick_to_price(state.upper_tick)?.sqrt(),
+    )?
+    .map(|x| x * Decimal::one().saturating_sub(state.max_join_slippage));
+
+    // The tokens must be sorted by denom and nonzero
+    let mut tokens_provided: Vec<ProtoCoin> = assets
+        .iter()
+        .filter(|x| !x.amount.is_zero())
+        .map(|x| ProtoCoin {
+            denom: x.info.to_string(),
+            amount: x.amount.to_string(),
+        })
+        .collect();
+    tokens_provided.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    Ok(MsgCreatePosition {
+        pool_id: state.pool_id,
+        sender: env.contract.address.to_string(),
+        lower_tick: state.lower_tick,
+        upper_tick: state.upper_tick,
+        tokens_provided,
+        token_min_amount0: token_min_amount0.to_string(),
+        token_min_amount1: token_min_amount1.to_string(),
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    OsmosisLiquidityHelper::reply(deps, env, msg)
+}
+
+/// Reply handler for the balancing swap of a `BalancingProvideLiquidity`.
+/// Provides the balanced assets with one double sided join. Anything that can
+/// not be used, e.g. due to rounding or the taker fee of the swap, is returned
+/// to the recipient in the last reply.
+pub fn reply_balancing_swap(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let state = PROVIDE_LIQUIDITY_STATE.load(deps.storage)?;
+    let return_asset_info = state
+        .swapped
+        .as_ref()
+        .map(|x| x.return_asset.info.clone())
+        .ok_or_else(|| StdError::generic_err("No balancing swap to reply to"))?;
+
+    // Add the amount returned from the swap to the assets to provide
+    let balance = return_asset_info.query_balance(&deps.querier, &env.contract.address)?;
+    let returned = Asset::new(
+        return_asset_info,
+        balance.checked_sub(state.return_balance_before)?,
+    );
+    let mut assets = state.provide_assets.clone();
+    assets.add(&returned)?;
+
+    let (lp_tokens_received, tokens_used) =
+        state.pool.simulate_noswap_join(&deps.querier, &assets)?;
+    if lp_tokens_received.is_zero() {
+        // Nothing can be provided, so everything is returned to the recipient
+        return reply_provide_liquidity(deps, env);
+    }
+
+    let res =
+        state
+            .pool
+            .provide_liquidity(deps.as_ref(), &env, assets.clone(), lp_tokens_received)?;
+
+    assets.deduct_many(&tokens_used)?;
+
+    let mut event = OsmosisLiquidityHelper::event("reply_balancing_swap")
+        .add_attribute("returned", returned.to_string());
+    // Attribute values must not be empty
+    if assets.len() > 0 {
+        event = event.add_attribute("leftover", assets.to_string());
+    }
+
+    Ok(reply_on_last_message(res, PROVIDE_LIQUIDITY_REPLY_ID)?.add_event(event))
+}
+
+/// Reply handler for the join pool messages of a `BalancingProvideLiquidity`.
+/// Provides the next remaining asset single sided, or sends the LP tokens to
+/// the recipient if there are no assets left, and locks them for it or deposits
+/// them in a vault if a post provide action is given. Each single sided join
+/// needs to be done in its own reply, because pool.provide_liquidity does a
+/// simulation with the current reserves, which are altered by each join.
+pub fn reply_provide_liquidity(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let mut state = PROVIDE_LIQUIDITY_STATE.load(deps.storage)?;
+
+    if !state.single_sided_joins.is_empty() {
+        let SingleSidedJoin { asset, min_out } = state.single_sided_joins.remove(0);
+        PROVIDE_LIQUIDITY_STATE.save(deps.storage, &state)?;
+
+        // Fails if the reserves have moved so that we would get less than the
+        // planned minimum amount of LP tokens
+        let res = state.pool.provide_liquidity(
+            deps.as_ref(),
+            &env,
+            vec![asset.clone()].into(),
+            min_out,
+        )?;
+
+        let event = OsmosisLiquidityHelper::event("reply_single_sided_join")
+            .add_attribute("asset", asset.to_string())
+            .add_attribute("min_out", min_out);
+
+        return Ok(reply_on_last_message(res, PROVIDE_LIQUIDITY_REPLY_ID)?.add_event(event));
+    }
+
+    PROVIDE_LIQUIDITY_STATE.remove(deps.storage);
+
+    let lp_token = state.pool.lp_token();
+    let lp_token_balance = lp_token.query_balance(&deps.querier, &env.contract.address)?;
+
+    let return_amount = lp_token_balance.checked_sub(state.lp_balance_before)?;
 
     // Assert return_amount is greater than min_out
-    if return_amount < min_out {
+    if return_amount < state.min_out {
         return Err(ContractError::InsufficientLpTokens {
-            expected: min_out,
+            expected: state.min_out,
             received: return_amount,
         });
     }
 
-    let return_asset = Asset::new(lp_token, return_amount);
-    let msg = return_asset.transfer_msg(&recipient)?;
+    let return_asset = Asset::new(lp_token.clone(), return_amount);
+    // LP tokens deposited in a vault are kept until the deposit below
+    let msg = match state.post_provide {
+        Some(PostProvideAction::VaultDeposit { .. }) => None,
+        _ => Some(return_asset.transfer_msg(&state.recipient)?),
+    };
+
+    // Refund anything left of the pool assets, e.g. assets that could not be
+    // used after the balancing swap or dust from rounding in the joins, so that
+    // nothing is stuck in the contract
+    let pool_assets: Vec<AssetInfo> = state
+        .pool
+        .get_pool_liquidity(deps.as_ref())?
+        .into_iter()
+        .map(|x| x.info.clone())
+        .collect();
+    let refunded =
+        query_balance_increase(deps.as_ref(), &env, &pool_assets, &state.balances_before)?;
+    let refund_msgs = refunded.transfer_msgs(&state.recipient)?;
 
-    let event = Event::new("apollo/osmosis-liquidity-helper/execute_callback_return_lp_tokens")
+    let mut event = OsmosisLiquidityHelper::event("reply_provide_liquidity")
         .add_attribute("return_asset", return_asset.to_string())
-        .add_attribute("recipient", recipient);
+        .add_attribute("recipient", &state.recipient);
+    // Attribute values must not be empty
+    if refunded.len() > 0 {
+        event = event.add_attribute("refunded", refunded.to_string());
+    }
+
+    let data = ProvideLiquidityResponse {
+        lp_token,
+        lp_amount: return_amount,
+        swapped: state.swapped,
+        leftover: refunded,
+        lock_id: None,
+        vault_tokens: None,
+    };
 
-    Ok(Response::new().add_message(msg).add_event(event))
+    let res = Response::new()
+        .add_messages(msg)
+        .add_messages(refund_msgs)
+        .add_event(event);
+
+    match state.post_provide {
+        // The data is set in the reply to the deposit, with the vault tokens
+        Some(PostProvideAction::VaultDeposit {
+            vault,
+            min_vault_tokens,
+        }) => {
+            let deposit_res = OsmosisLiquidityHelper::vault_deposit(
+                deps,
+                &env,
+                &vault,
+                &return_asset,
+                min_vault_tokens,
+                state.recipient,
+                data,
+            )?;
+            Ok(res
+                .add_submessages(deposit_res.messages)
+                .add_events(deposit_res.events))
+        }
+        // The LP tokens are sent to the recipient first, who then locks them
+        // through the authz message. The data is set in the reply, with the
+        // lock ID.
+        Some(action) => {
+            let exec_msg = post_provide_msg(&env, &action, &return_asset, &state.recipient)?;
+            POST_PROVIDE_STATE.save(
+                deps.storage,
+                &PostProvideState {
+                    action,
+                    response: data,
+                },
+            )?;
+            Ok(res.add_submessage(SubMsg::reply_on_success(exec_msg, POST_PROVIDE_REPLY_ID)))
+        }
+        None => Ok(res.set_data(to_json_binary(&data)?)),
+    }
+}
+
+/// Returns the authz `MsgExec` doing the post provide `action` with
+/// `lp_tokens` for the recipient, which must have granted the contract an
+/// authorization for it
+fn post_provide_msg(
+    env: &Env,
+    action: &PostProvideAction,
+    lp_tokens: &Asset,
+    recipient: &Addr,
+) -> Result<MsgExec, ContractError> {
+    let coins = vec![ProtoCoin {
+        denom: lp_tokens.info.to_string(),
+        amount: lp_tokens.amount.to_string(),
+    }];
+    let msg = match action {
+        PostProvideAction::LockTokens { duration } => MsgLockTokens {
+            owner: recipient.to_string(),
+            duration: Some(Duration {
+                seconds: *duration as i64,
+                nanos: 0,
+            }),
+            coins,
+        }
+        .to_any(),
+        PostProvideAction::SuperfluidDelegate { validator } => MsgLockAndSuperfluidDelegate {
+            sender: recipient.to_string(),
+            coins,
+            val_addr: validator.clone(),
+        }
+        .to_any(),
+        action => {
+            return Err(LiquidityHelperError::UnsupportedPostProvideAction {
+                action: action.clone(),
+            }
+            .into())
+        }
+    };
+
+    Ok(MsgExec {
+        grantee: env.contract.address.to_string(),
+        msgs: vec![msg],
+    })
+}
+
+/// Reply handler for the authz message doing the post provide action of a
+/// `BalancingProvideLiquidity`. Returns the `ProvideLiquidityResponse` with the
+/// ID of the created lock.
+pub fn reply_post_provide(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let PostProvideState {
+        action,
+        mut response,
+    } = POST_PROVIDE_STATE.load(deps.storage)?;
+    POST_PROVIDE_STATE.remove(deps.storage);
+
+    let data = msg
+        .result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("No data in post provide reply"))?;
+    let result: Binary = MsgExecResponse::try_from(data)?
+        .results
+        .into_iter()
+        .next()
+        .ok_or_else(|| StdError::generic_err("No result in authz exec response"))?
+        .into();
+    let lock_id = match action {
+        PostProvideAction::SuperfluidDelegate { .. } => {
+            MsgLockAndSuperfluidDelegateResponse::try_from(result)?.id
+        }
+        _ => MsgLockTokensResponse::try_from(result)?.id,
+    };
+    response.lock_id = Some(lock_id);
+
+    let event = OsmosisLiquidityHelper::event("reply_post_provide")
+        .add_attribute("lock_id", lock_id.to_string());
+
+    Ok(Response::new()
+        .add_event(event)
+        .set_data(to_json_binary(&response)?))
+}
+
+/// Reply handler for the balancing swap of a `BalancingProvideLiquidity` into
+/// a concentrated liquidity pool. Creates the position with the balanced
+/// assets.
+pub fn reply_create_position_swap(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let mut state = CREATE_POSITION_STATE.load(deps.storage)?;
+    let return_asset_info = state
+        .swapped
+        .as_ref()
+        .map(|x| x.return_asset.info.clone())
+        .ok_or_else(|| StdError::generic_err("No balancing swap to reply to"))?;
+
+    // Add the amount returned from the swap to the assets to provide
+    let balance = return_asset_info.query_balance(&deps.querier, &env.contract.address)?;
+    let returned = Asset::new(
+        return_asset_info,
+        balance.checked_sub(state.return_balance_before)?,
+    );
+    state.provide_assets.add(&returned)?;
+    CREATE_POSITION_STATE.save(deps.storage, &state)?;
+
+    // The balancing swap moved the price of the pool
+    let sqrt_price =
+        parse_dec256(&query_concentrated_pool(deps.as_ref(), state.pool_id)?.current_sqrt_price)?;
+    let msg = create_position_msg(&env, &state, &state.provide_assets, sqrt_price)?;
+
+    let event = OsmosisLiquidityHelper::event("reply_create_position_swap")
+        .add_attribute("returned", returned.to_string());
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(msg, CREATE_POSITION_REPLY_ID))
+        .add_event(event))
+}
+
+/// Reply handler for the create position message of a
+/// `BalancingProvideLiquidity` into a concentrated liquidity pool. Asserts
+/// that the position has at least `min_out` liquidity and transfers it to the
+/// recipient, together with any assets that were not used.
+pub fn reply_create_position(
+    deps: DepsMut,
+    env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    let state = CREATE_POSITION_STATE.load(deps.storage)?;
+    CREATE_POSITION_STATE.remove(deps.storage);
+
+    let data = msg
+        .result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("No data in create position reply"))?;
+    let response = MsgCreatePositionResponse::try_from(data)?;
+    let liquidity = parse_dec256(&response.liquidity_created)?;
+
+    // Assert liquidity is greater than min_out
+    if liquidity < Decimal256::from_ratio(state.min_out, 1u8) {
+        return Err(ContractError::InsufficientLiquidity {
+            expected: state.min_out,
+            received: liquidity,
+        });
+    }
+
+    // Deduct the amounts used by the position to get the leftover assets
+    let used: AssetList = [
+        (&state.token0, &response.amount0),
+        (&state.token1, &response.amount1),
+    ]
+    .into_iter()
+    .map(|(denom, amount)| Ok(Asset::native(denom, Uint128::from_str(amount)?)))
+    .collect::<StdResult<Vec<_>>>()?
+    .into_iter()
+    .filter(|x| !x.amount.is_zero())
+    .collect::<Vec<_>>()
+    .into();
+    let mut leftover = state.provide_assets.clone();
+    leftover.deduct_many(&used)?;
+
+    let transfer_msg = MsgTransferPositions {
+        position_ids: vec![response.position_id],
+        sender: env.contract.address.to_string(),
+        new_owner: state.recipient.to_string(),
+    };
+    let leftover_msgs = leftover.transfer_msgs(&state.recipient)?;
+
+    let mut event = OsmosisLiquidityHelper::event("reply_create_position")
+        .add_attribute("position_id", response.position_id.to_string())
+        .add_attribute("liquidity", liquidity.to_string())
+        .add_attribute("recipient", state.recipient);
+    // Attribute values must not be empty
+    if leftover.len() > 0 {
+        event = event.add_attribute("leftover", leftover.to_string());
+    }
+
+    let data = CreatePositionResponse {
+        position_id: response.position_id,
+        liquidity,
+        swapped: state.swapped,
+        leftover,
+    };
+
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_messages(leftover_msgs)
+        .add_event(event)
+        .set_data(to_json_binary(&data)?))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(_deps: Deps, _env: Env, _msg: QueryMsg) -> StdResult<Binary> {
-    Err(StdError::generic_err("Queries are not supported."))
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    OsmosisLiquidityHelper::query(deps, env, msg)
+}
+
+pub fn query_simulate_balancing_provide_liquidity(
+    deps: Deps,
+    env: Env,
+    assets: AssetList,
+    pool: OsmosisPool,
+) -> Result<SimulateBalancingProvideLiquidityResponse, ContractError> {
+    let balancing_swap = if assets.len() > 1 {
+        calc_balancing_swap(deps, &assets, &pool)?
+    } else {
+        None
+    };
+
+    if let Some((offer_asset, return_asset)) = balancing_swap {
+        let mut provide_assets = assets.clone();
+        provide_assets.deduct(&offer_asset)?;
+        provide_assets.add(&return_asset)?;
+
+        // The swap changes the reserves, so we simulate the join against the
+        // reserves after the swap instead of querying the pool. The swap fee
+        // stays in the pool.
+        let mut reserves = pool.get_pool_liquidity(deps)?;
+        reserves.add(&offer_asset)?;
+        reserves.deduct(&return_asset)?;
+        let pool_assets: Vec<AssetInfo> = reserves.iter().map(|x| x.info.clone()).collect();
+        let amounts = |list: &AssetList| -> [Uint128; 2] {
+            [0, 1].map(|i| {
+                list.find(&pool_assets[i])
+                    .map_or_else(Uint128::zero, |x| x.amount)
+            })
+        };
+
+        let (lp_amount, used_amounts) = calc_noswap_join(
+            amounts(&provide_assets),
+            amounts(&reserves),
+            query_total_shares(deps, &pool)?,
+        )?;
+
+        let mut leftover = provide_assets.clone();
+        for (info, used_amount) in pool_assets.iter().zip(used_amounts) {
+            leftover.deduct(&Asset::new(info.clone(), used_amount))?;
+        }
+        provide_assets.deduct_many(&leftover)?;
+
+        return Ok(SimulateBalancingProvideLiquidityResponse {
+            swap: Some(BalancingSwap {
+                offer_asset,
+                return_asset,
+            }),
+            provide_assets,
+            lp_tokens: Asset::new(pool.lp_token(), lp_amount),
+            leftover,
+        });
+    }
+
+    let lp_tokens = if assets.len() == 1 {
+        pool.simulate_provide_liquidity(deps, &env, assets.clone())?
+    } else {
+        let (lp_tokens_received, tokens_used) =
+            pool.simulate_noswap_join(&deps.querier, &assets)?;
+
+        // The remaining assets are provided single sided, one at a time, after
+        // the double sided join
+        let mut remaining_assets = assets.clone();
+        remaining_assets.deduct_many(&tokens_used)?;
+        let mut lp_amount = lp_tokens_received;
+        for (_, shares) in
+            simulate_single_sided_joins(deps, &pool, &remaining_assets, lp_tokens_received)?
+        {
+            lp_amount = lp_amount.checked_add(shares)?;
+        }
+
+        Asset::new(pool.lp_token(), lp_amount)
+    };
+
+    Ok(SimulateBalancingProvideLiquidityResponse {
+        swap: None,
+        provide_assets: assets,
+        lp_tokens,
+        leftover: AssetList::new(),
+    })
+}
+
+/// Simulates providing each of `assets` single sided, one at a time, after a
+/// double sided join that minted `lp_amount` LP tokens, and returns the amount
+/// of LP tokens expected from each join. Shares of a single sided join scale
+/// with the size of the pool, so we simulate a scaled down join against the
+/// current reserves and scale the result back up. This is exact for the first
+/// join, but only an estimate for the following ones.
+fn simulate_single_sided_joins(
+    deps: Deps,
+    pool: &OsmosisPool,
+    assets: &AssetList,
+    mut lp_amount: Uint128,
+) -> Result<Vec<(Asset, Uint128)>, ContractError> {
+    let total_shares = query_total_shares(deps, pool)?;
+    let mut joins = vec![];
+    for asset in assets.iter().filter(|x| !x.amount.is_zero()) {
+        let pool_shares = total_shares.checked_add(lp_amount)?;
+        let scaled_asset = Asset::new(
+            asset.info.clone(),
+            asset.amount.multiply_ratio(total_shares, pool_shares),
+        );
+        let shares = pool
+            .simulate_single_sided_join(&deps.querier, &scaled_asset)?
+            .multiply_ratio(pool_shares, total_shares);
+        lp_amount = lp_amount.checked_add(shares)?;
+        joins.push((asset.clone(), shares));
+    }
+    Ok(joins)
+}
+
+/// Returns the minimum amount of LP tokens to accept from a single sided join
+/// that is expected to return `expected_lp_amount`
+fn single_sided_join_min_out(expected_lp_amount: Uint128, max_join_slippage: Decimal) -> Uint128 {
+    (expected_lp_amount * Decimal::one().saturating_sub(max_join_slippage)).max(Uint128::one())
+}
+
+/// Queries the total amount of LP tokens of the pool
+fn query_total_shares(deps: Deps, pool: &OsmosisPool) -> StdResult<Uint128> {
+    let total_shares = GammQuerier::new(&deps.querier)
+        .total_shares(pool.pool_id())?
+        .total_shares
+        .ok_or_else(|| StdError::generic_err("Pool has no total shares"))?;
+    Uint128::from_str(&total_shares.amount)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    Ok(Response::default())
+pub fn migrate(mut deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let res = OsmosisLiquidityHelper::migrate(deps.branch(), msg.owner)?;
+
+    if let Some(max_join_slippage) = msg.max_join_slippage {
+        MAX_JOIN_SLIPPAGE.save(deps.storage, &max_join_slippage)?;
+    }
+
+    Ok(res)
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::marker::PhantomData;
+
+    use apollo_cw_asset::{Asset, AssetList};
+    use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+    use cosmwasm_std::{
+        from_json, to_json_binary, Addr, ContractResult, Empty, OwnedDeps, Querier, QuerierResult,
+        QueryRequest, SystemResult, Uint128,
+    };
+    use cw_dex::CwDexError;
+    use cw_dex_osmosis::osmosis_std::types::osmosis::gamm::v1beta1::QueryCalcJoinPoolSharesResponse;
+    use cw_dex_osmosis::OsmosisPool;
+    use test_case::test_case;
+
+    use super::{reply_provide_liquidity, single_sided_join_min_out};
+    use crate::error::ContractError;
+    use crate::state::{
+        ProvideLiquidityState, SingleSidedJoin, DEFAULT_MAX_JOIN_SLIPPAGE, PROVIDE_LIQUIDITY_STATE,
+    };
+
+    /// Answers the join simulation of the pool with a fixed amount of LP
+    /// tokens, as if the reserves had been moved since the join was planned
+    struct JoinPoolQuerier {
+        share_out_amount: Uint128,
+    }
+
+    impl Querier for JoinPoolQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_json(bin_request).unwrap();
+            match request {
+                QueryRequest::Stargate { path, .. }
+                    if path == "/osmosis.gamm.v1beta1.Query/CalcJoinPoolShares" =>
+                {
+                    let res = QueryCalcJoinPoolSharesResponse {
+                        share_out_amount: self.share_out_amount.to_string(),
+                        tokens_out: vec![],
+                    };
+                    SystemResult::Ok(ContractResult::Ok(to_json_binary(&res).unwrap()))
+                }
+                _ => panic!("Unexpected query: {request:?}"),
+            }
+        }
+    }
+
+    #[test_case(1_000, false ; "return as expected")]
+    #[test_case(990, false ; "return at the tolerance")]
+    #[test_case(989, true ; "return below the tolerance")]
+    /// Tests that a single sided join reverts if it would return less than the
+    /// LP tokens expected when it was planned, minus the default tolerance
+    fn test_single_sided_join_min_out(share_out_amount: u128, reverts: bool) {
+        let mut deps = OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier: JoinPoolQuerier {
+                share_out_amount: Uint128::new(share_out_amount),
+            },
+            custom_query_type: PhantomData::<Empty>,
+        };
+
+        // Plan a join expected to return 1000 LP tokens
+        let min_out = single_sided_join_min_out(Uint128::new(1_000), DEFAULT_MAX_JOIN_SLIPPAGE);
+        assert_eq!(min_out, Uint128::new(990));
+        let state = ProvideLiquidityState {
+            pool: OsmosisPool::unchecked(1),
+            lp_balance_before: Uint128::zero(),
+            balances_before: AssetList::new(),
+            swapped: None,
+            return_balance_before: Uint128::zero(),
+            provide_assets: AssetList::new(),
+            single_sided_joins: vec![SingleSidedJoin {
+                asset: Asset::native("uatom", 1_000u128),
+                min_out,
+            }],
+            min_out: Uint128::one(),
+            recipient: Addr::unchecked("recipient"),
+            post_provide: None,
+        };
+        PROVIDE_LIQUIDITY_STATE
+            .save(deps.as_mut().storage, &state)
+            .unwrap();
+
+        let res = reply_provide_liquidity(deps.as_mut(), mock_env());
+        if reverts {
+            assert!(matches!(
+                res.unwrap_err(),
+                ContractError::CwDex(CwDexError::MinOutNotReceived { min_out: m, .. }) if m == min_out
+            ));
+        } else {
+            assert_eq!(res.unwrap().messages.len(), 1);
+        }
+    }
+}
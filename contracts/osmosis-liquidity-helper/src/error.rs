@@ -1,6 +1,8 @@
-use apollo_cw_asset::Asset;
-use cosmwasm_std::{OverflowError, StdError, Uint128};
+use apollo_cw_asset::AssetInfo;
+use cosmwasm_std::{Decimal, Decimal256, OverflowError, StdError, Uint128};
 use cw_dex::CwDexError;
+use cw_ownable::OwnershipError;
+use liquidity_helper::LiquidityHelperError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,20 +16,45 @@ pub enum ContractError {
     #[error("{0}")]
     Overflow(#[from] OverflowError),
 
+    #[error("{0}")]
+    Ownership(#[from] OwnershipError),
+
+    #[error("{0}")]
+    LiquidityHelper(#[from] LiquidityHelperError),
+
     #[error("Insufficient LP tokens. Expected a minumum of {expected} but got {received}")]
     InsufficientLpTokens {
         expected: Uint128,
         received: Uint128,
     },
 
-    #[error("Received funds don't match input assets. Expected {expected:?} but got {received:?}")]
-    InputTokenMismatch {
-        expected: Vec<Asset>,
-        received: Vec<Asset>,
+    #[error("Insufficient liquidity. Expected a minumum of {expected} but got {received}")]
+    InsufficientLiquidity {
+        expected: Uint128,
+        received: Decimal256,
+    },
+
+    #[error(
+        "Price deviation of {deviation} from the TWAP exceeds max_price_deviation of {max_price_deviation}"
+    )]
+    PriceDeviationExceeded {
+        max_price_deviation: Decimal,
+        deviation: Decimal,
     },
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("Pool {pool_id} is not a concentrated liquidity pool")]
+    NotConcentratedLiquidityPool { pool_id: u64 },
+
+    #[error("Simulating a provide to concentrated liquidity pool {pool_id} is not supported")]
+    UnsupportedConcentratedLiquiditySimulation { pool_id: u64 },
+
+    #[error(
+        "Invalid tick range. Lower tick {lower_tick} must be less than upper tick {upper_tick}"
+    )]
+    InvalidTickRange { lower_tick: i64, upper_tick: i64 },
+
+    #[error("Asset {asset} is not in the pool")]
+    AssetNotInPool { asset: AssetInfo },
 }
 
 impl From<ContractError> for StdError {
@@ -4,9 +4,15 @@
 //! supplying liquidity with imbalanced assets. If the assets provided are not
 //! in the correct ratio, the contract will swap some of the assets so that the
 //! ratio of assets are the same as the pools reserves after the swap.
+//!
+//! Concentrated liquidity pools are supported by passing a
+//! [`msg::ConcentratedLiquidityPool`] as the pool, in which case a position is
+//! created in the given tick range and transferred to the recipient.
 
 pub mod contract;
 mod error;
+pub mod math;
 pub mod msg;
+pub mod state;
 
 pub use crate::error::ContractError;
@@ -0,0 +1,712 @@
+//! Module containing implementations of calculations needed for balancing
+//! assets in Osmosis balancer and concentrated liquidity pools
+
+use apollo_cw_asset::Asset;
+use cosmwasm_std::{Decimal, Decimal256, StdError, StdResult, Uint128, Uint256};
+
+/// The maximum amount of a reserve that can be swapped in at once in an
+/// Osmosis balancer pool, as a ratio of the reserve.
+pub const MAX_IN_RATIO: Decimal = Decimal::percent(50);
+
+/// The lowest tick that can be used in a concentrated liquidity position
+pub const MIN_INITIALIZED_TICK: i64 = -108_000_000;
+
+/// The highest tick that can be used in a concentrated liquidity position
+pub const MAX_TICK: i64 = 342_000_000;
+
+/// The exponent of the additive increment between ticks at a price of one
+const EXPONENT_AT_PRICE_ONE: i64 = -6;
+
+/// The number of ticks between each change in the exponent of the additive
+/// increment, i.e. `9 * 10^(-EXPONENT_AT_PRICE_ONE)`
+const GEOMETRIC_EXPONENT_INCREMENT_DISTANCE_IN_TICKS: i64 = 9_000_000;
+
+/// The precision at which the series in [`pow_approx`] is truncated
+const POW_PRECISION: Decimal = Decimal::raw(100_000_000);
+
+/// Calculates `base^exp` for `base` in `(0, 2)`, using the same binomial
+/// series as the Balancer and Osmosis `pow` implementations for the fractional
+/// part of the exponent.
+pub fn pow_approx(base: Decimal, exp: Decimal) -> StdResult<Decimal> {
+    if base.is_zero() || base >= Decimal::from_ratio(2u128, 1u128) {
+        return Err(StdError::generic_err("Base must be between 0 and 2"));
+    }
+
+    let whole = exp.to_uint_floor();
+    let whole_pow = base.checked_pow(
+        whole
+            .u128()
+            .try_into()
+            .map_err(|_| StdError::generic_err("Exponent too large"))?,
+    )?;
+    let remain = exp - Decimal::from_ratio(whole, 1u128);
+    if remain.is_zero() {
+        return Ok(whole_pow);
+    }
+
+    // (1 + x)^a = 1 + a*x + a(a-1)/2!*x^2 + a(a-1)(a-2)/3!*x^3 + ...
+    // Decimal is unsigned, so we keep track of the signs separately.
+    let (x, x_neg) = if base >= Decimal::one() {
+        (base - Decimal::one(), false)
+    } else {
+        (Decimal::one() - base, true)
+    };
+    let mut term = Decimal::one();
+    let mut sum = Decimal::one();
+    let mut sum_neg = false;
+    let mut term_neg = false;
+    let mut k = 1u128;
+    while term >= POW_PRECISION {
+        let k_minus_one = Decimal::from_ratio(k - 1, 1u128);
+        let (c, c_neg) = if remain >= k_minus_one {
+            (remain - k_minus_one, false)
+        } else {
+            (k_minus_one - remain, true)
+        };
+        term = term * c * x / Decimal::from_ratio(k, 1u128);
+        if term.is_zero() {
+            break;
+        }
+        term_neg ^= x_neg ^ c_neg;
+
+        // Add the signed term to the signed sum
+        if term_neg == sum_neg {
+            sum += term;
+        } else if sum >= term {
+            sum -= term;
+        } else {
+            sum = term - sum;
+            sum_neg = !sum_neg;
+        }
+        k += 1;
+    }
+    if sum_neg {
+        return Err(StdError::generic_err("Negative result in pow_approx"));
+    }
+
+    Ok(whole_pow * sum)
+}
+
+/// Calculates how much will be returned from a swap in a balancer pool, where
+/// `weights` are `[offer_weight, ask_weight]`. The swap fee is taken from the
+/// offer amount.
+///
+/// `return = ask_reserve * (1 - (offer_reserve / (offer_reserve + offer *
+/// (1 - fee)))^(offer_weight / ask_weight))`
+pub fn weighted_out_given_in(
+    offer_reserve: Uint128,
+    ask_reserve: Uint128,
+    offer_amount: Uint128,
+    weights: [Decimal; 2],
+    swap_fee: Decimal,
+) -> StdResult<Uint128> {
+    let offer_after_fee = offer_amount * (Decimal::one() - swap_fee);
+    let base = Decimal::from_ratio(offer_reserve, offer_reserve.checked_add(offer_after_fee)?);
+    let exp = weights[0] / weights[1];
+    let ratio = Decimal::one() - pow_approx(base, exp)?;
+    Ok(ask_reserve * ratio)
+}
+
+/// For a two asset balancer pool, calculates how much of one asset we need to
+/// swap to the other in order to have the same ratio of assets as the pool, so
+/// that we can then provide liquidity double sided without any remainder.
+/// There is no closed form solution for arbitrary weights, so the amount is
+/// found by bisection.
+///
+/// Returns `Some((offer_asset, return_asset))` containing the amount and info
+/// of the asset we need to swap, and the asset that will be returned from the
+/// swap, or `None` if balancing would require swapping more than
+/// [`MAX_IN_RATIO`] of the offer reserve.
+pub fn calc_weighted_balancing_swap(
+    assets: [Asset; 2],
+    reserves: [Uint128; 2],
+    weights: [Decimal; 2],
+    swap_fee: Decimal,
+) -> StdResult<Option<(Asset, Asset)>> {
+    // Make sure there is liquidity in the pool
+    if reserves[0].is_zero() || reserves[1].is_zero() {
+        return Err(StdError::generic_err("No liquidity in pool"));
+    }
+
+    // Offer the asset that we have more of relative to the pool
+    let (offer_idx, ask_idx) =
+        if assets[0].amount.full_mul(reserves[1]) > assets[1].amount.full_mul(reserves[0]) {
+            (0, 1)
+        } else {
+            (1, 0)
+        };
+    let offer_reserve = reserves[offer_idx];
+    let ask_reserve = reserves[ask_idx];
+    let offer_balance = assets[offer_idx].amount;
+    let ask_balance = assets[ask_idx].amount;
+    let weights = [weights[offer_idx], weights[ask_idx]];
+
+    // Returns true if after swapping `offer_amount` our ratio of offer to ask
+    // asset is still at least the ratio of the reserves, i.e.
+    // (offer_balance - x) / (ask_balance + y) >= (offer_reserve + x) /
+    // (ask_reserve - y), where y is the return amount of swapping x.
+    let has_excess_offer = |offer_amount: Uint128| -> StdResult<bool> {
+        let return_amount =
+            weighted_out_given_in(offer_reserve, ask_reserve, offer_amount, weights, swap_fee)?;
+        let lhs = (offer_balance - offer_amount).full_mul(ask_reserve - return_amount);
+        let rhs = (ask_balance + return_amount).full_mul(offer_reserve + offer_amount);
+        Ok(lhs >= rhs)
+    };
+
+    // The pool does not allow swapping in more than MAX_IN_RATIO of the reserve
+    let max_offer_amount = offer_reserve * MAX_IN_RATIO;
+    let mut high = if offer_balance > max_offer_amount {
+        if has_excess_offer(max_offer_amount)? {
+            return Ok(None);
+        }
+        max_offer_amount
+    } else {
+        offer_balance
+    };
+
+    // Find the largest offer amount that does not swap too much
+    let mut low = Uint128::zero();
+    while high - low > Uint128::one() {
+        let mid = low + (high - low) / Uint128::new(2);
+        if has_excess_offer(mid)? {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    let return_amount = weighted_out_given_in(offer_reserve, ask_reserve, low, weights, swap_fee)?;
+
+    Ok(Some((
+        Asset::new(assets[offer_idx].info.clone(), low),
+        Asset::new(assets[ask_idx].info.clone(), return_amount),
+    )))
+}
+
+/// Calculates the amount of LP tokens minted when providing `assets` to a
+/// balancer pool with the given `reserves` and total shares without swapping.
+///
+/// Returns `(lp_amount, used_assets): (Uint128, [Uint128; 2])` containing the
+/// amount of LP tokens and the amounts of each asset that will be taken by
+/// the pool.
+pub fn calc_noswap_join(
+    assets: [Uint128; 2],
+    reserves: [Uint128; 2],
+    total_shares: Uint128,
+) -> StdResult<(Uint128, [Uint128; 2])> {
+    // Make sure there is liquidity in the pool
+    if reserves[0].is_zero() || reserves[1].is_zero() || total_shares.is_zero() {
+        return Err(StdError::generic_err("No liquidity in pool"));
+    }
+
+    let lp_amount = std::cmp::min(
+        assets[0].multiply_ratio(total_shares, reserves[0]),
+        assets[1].multiply_ratio(total_shares, reserves[1]),
+    );
+    let used_assets = [
+        std::cmp::min(
+            assets[0],
+            lp_amount.multiply_ratio(reserves[0], total_shares),
+        ),
+        std::cmp::min(
+            assets[1],
+            lp_amount.multiply_ratio(reserves[1], total_shares),
+        ),
+    ];
+
+    Ok((lp_amount, used_assets))
+}
+
+/// Returns `10^exp` as a `Decimal256`
+fn pow_ten(exp: i64) -> StdResult<Decimal256> {
+    let pow = Uint256::from(10u8).checked_pow(exp.unsigned_abs() as u32)?;
+    if exp >= 0 {
+        Ok(Decimal256::from_ratio(pow, 1u8))
+    } else {
+        Ok(Decimal256::from_ratio(1u8, pow))
+    }
+}
+
+/// Converts a tick of a concentrated liquidity pool to the price of token0 in
+/// token1, following `TickToPrice` in the Osmosis concentrated liquidity
+/// module. Prices are truncated to the 18 decimals of `Decimal256`, which only
+/// loses precision for prices below 10^-12.
+pub fn tick_to_price(tick: i64) -> StdResult<Decimal256> {
+    if !(MIN_INITIALIZED_TICK..=MAX_TICK).contains(&tick) {
+        return Err(StdError::generic_err(format!("Tick {tick} out of range")));
+    }
+
+    let geometric_exponent_delta = tick / GEOMETRIC_EXPONENT_INCREMENT_DISTANCE_IN_TICKS;
+    let mut exponent_at_current_tick = EXPONENT_AT_PRICE_ONE + geometric_exponent_delta;
+    if tick < 0 {
+        // Below a price of one the precision steps up immediately
+        exponent_at_current_tick -= 1;
+    }
+    let num_additive_ticks =
+        tick - geometric_exponent_delta * GEOMETRIC_EXPONENT_INCREMENT_DISTANCE_IN_TICKS;
+
+    let additive = pow_ten(exponent_at_current_tick)?.checked_mul(Decimal256::from_ratio(
+        num_additive_ticks.unsigned_abs(),
+        1u8,
+    ))?;
+    let base = pow_ten(geometric_exponent_delta)?;
+    if num_additive_ticks >= 0 {
+        Ok(base.checked_add(additive)?)
+    } else {
+        Ok(base.checked_sub(additive)?)
+    }
+}
+
+/// Calculates the amounts of `assets` to create the first position in an empty
+/// concentrated liquidity pool with, so that token0 is priced at `price` units
+/// of token1. The asset in excess of this ratio is reduced, the other is used
+/// in full.
+pub fn calc_initial_provide_amounts(
+    assets: [Uint128; 2],
+    price: Decimal,
+) -> StdResult<[Uint128; 2]> {
+    if price.is_zero() {
+        return Err(StdError::generic_err(
+            "Initial price must be greater than zero",
+        ));
+    }
+
+    match assets[0].checked_mul_floor(price) {
+        Ok(amount) if amount <= assets[1] => Ok([assets[0], amount]),
+        _ => Ok([
+            assets[1]
+                .checked_div_floor(price)
+                .map_err(|e| StdError::generic_err(e.to_string()))?,
+            assets[1],
+        ]),
+    }
+}
+
+/// For a concentrated liquidity position between `lower_sqrt_price` and
+/// `upper_sqrt_price`, calculates how much of one token we need to swap to the
+/// other at the current `sqrt_price` in order to have the ratio of tokens
+/// required by the position. The price impact of the swap is not taken into
+/// account, so a small amount of one token may not be used by the position.
+///
+/// Returns `Some((offer_asset, return_asset))` containing the amount and info
+/// of the asset we need to swap, and the asset expected to be returned from the
+/// swap, or `None` if no swap is needed.
+pub fn calc_cl_balancing_swap(
+    assets: [Asset; 2],
+    sqrt_price: Decimal256,
+    lower_sqrt_price: Decimal256,
+    upper_sqrt_price: Decimal256,
+    spread_factor: Decimal,
+) -> StdResult<Option<(Asset, Asset)>> {
+    if sqrt_price.is_zero() || lower_sqrt_price >= upper_sqrt_price {
+        return Err(StdError::generic_err("Invalid sqrt prices"));
+    }
+
+    let amounts = [
+        Decimal256::from_ratio(assets[0].amount, 1u8),
+        Decimal256::from_ratio(assets[1].amount, 1u8),
+    ];
+    // The price of token0 in token1, and the part of the offer amount that is
+    // swapped after the spread factor is taken
+    let price = sqrt_price.checked_mul(sqrt_price)?;
+    let one_minus_fee = Decimal256::one() - Decimal256::from(spread_factor);
+
+    let (offer_idx, offer_amount) = if sqrt_price <= lower_sqrt_price {
+        // Below the range the position only holds token0
+        (1, amounts[1])
+    } else if sqrt_price >= upper_sqrt_price {
+        // Above the range the position only holds token1
+        (0, amounts[0])
+    } else {
+        // The ratio of the amounts per unit of liquidity is the amount of
+        // token1 needed per token0
+        let (amount0_per_liquidity, amount1_per_liquidity) =
+            cl_amounts_per_liquidity(sqrt_price, lower_sqrt_price, upper_sqrt_price)?;
+        let ratio = amount1_per_liquidity
+            .checked_div(amount0_per_liquidity)
+            .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+        let required_amount1 = ratio.checked_mul(amounts[0])?;
+        if amounts[1] < required_amount1 {
+            // Swap x of token0 so that amount1 + x * price * (1 - fee) =
+            // ratio * (amount0 - x)
+            let x = (required_amount1 - amounts[1])
+                .checked_div(price.checked_mul(one_minus_fee)?.checked_add(ratio)?)
+                .map_err(|e| StdError::generic_err(e.to_string()))?;
+            (0, x)
+        } else {
+            // Swap x of token1 so that amount1 - x = ratio * (amount0 + x *
+            // (1 - fee) / price)
+            let x = (amounts[1] - required_amount1)
+                .checked_div(
+                    Decimal256::one().checked_add(
+                        ratio
+                            .checked_mul(one_minus_fee)?
+                            .checked_div(price)
+                            .map_err(|e| StdError::generic_err(e.to_string()))?,
+                    )?,
+                )
+                .map_err(|e| StdError::generic_err(e.to_string()))?;
+            (1, x)
+        }
+    };
+
+    let return_amount = if offer_idx == 0 {
+        offer_amount
+            .checked_mul(price)?
+            .checked_mul(one_minus_fee)?
+    } else {
+        offer_amount
+            .checked_mul(one_minus_fee)?
+            .checked_div(price)
+            .map_err(|e| StdError::generic_err(e.to_string()))?
+    };
+
+    let offer_amount: Uint128 = offer_amount.to_uint_floor().try_into()?;
+    let return_amount: Uint128 = return_amount.to_uint_floor().try_into()?;
+    if offer_amount.is_zero() || return_amount.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        Asset::new(assets[offer_idx].info.clone(), offer_amount),
+        Asset::new(assets[1 - offer_idx].info.clone(), return_amount),
+    )))
+}
+
+/// Returns the amounts of token0 and token1 per unit of liquidity of a
+/// position between `lower_sqrt_price` and `upper_sqrt_price` that is in range
+/// at `sqrt_price`. They are `(upper - p) / (p * upper)` and `(p - lower)`,
+/// where `p` is the sqrt price.
+fn cl_amounts_per_liquidity(
+    sqrt_price: Decimal256,
+    lower_sqrt_price: Decimal256,
+    upper_sqrt_price: Decimal256,
+) -> StdResult<(Decimal256, Decimal256)> {
+    let amount0_per_liquidity = (upper_sqrt_price - sqrt_price)
+        .checked_div(sqrt_price.checked_mul(upper_sqrt_price)?)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    Ok((amount0_per_liquidity, sqrt_price - lower_sqrt_price))
+}
+
+/// Calculates the amounts of token0 and token1 that a concentrated liquidity
+/// position between `lower_sqrt_price` and `upper_sqrt_price` uses when created
+/// with `amounts` at the current `sqrt_price`. The liquidity of the position
+/// is limited by the token that is short of the ratio required by the range,
+/// and the excess of the other token is not used. The first position in an
+/// empty pool, whose sqrt price is zero, sets the price and uses all of the
+/// amounts.
+pub fn calc_cl_position_amounts(
+    amounts: [Uint128; 2],
+    sqrt_price: Decimal256,
+    lower_sqrt_price: Decimal256,
+    upper_sqrt_price: Decimal256,
+) -> StdResult<[Uint128; 2]> {
+    if lower_sqrt_price >= upper_sqrt_price {
+        return Err(StdError::generic_err("Invalid sqrt prices"));
+    }
+
+    if sqrt_price.is_zero() {
+        return Ok(amounts);
+    } else if sqrt_price <= lower_sqrt_price {
+        // Below the range the position only holds token0
+        return Ok([amounts[0], Uint128::zero()]);
+    } else if sqrt_price >= upper_sqrt_price {
+        // Above the range the position only holds token1
+        return Ok([Uint128::zero(), amounts[1]]);
+    }
+
+    let (amount0_per_liquidity, amount1_per_liquidity) =
+        cl_amounts_per_liquidity(sqrt_price, lower_sqrt_price, upper_sqrt_price)?;
+    let liquidity = Decimal256::from_ratio(amounts[0], 1u8)
+        .checked_div(amount0_per_liquidity)
+        .map_err(|e| StdError::generic_err(e.to_string()))?
+        .min(
+            Decimal256::from_ratio(amounts[1], 1u8)
+                .checked_div(amount1_per_liquidity)
+                .map_err(|e| StdError::generic_err(e.to_string()))?,
+        );
+
+    Ok([
+        liquidity
+            .checked_mul(amount0_per_liquidity)?
+            .to_uint_floor()
+            .try_into()?,
+        liquidity
+            .checked_mul(amount1_per_liquidity)?
+            .to_uint_floor()
+            .try_into()?,
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use apollo_cw_asset::{Asset, AssetInfo};
+    use cosmwasm_std::{Decimal, Decimal256, Uint128};
+    use test_case::test_case;
+
+    use crate::math::{
+        calc_cl_balancing_swap, calc_cl_position_amounts, calc_initial_provide_amounts,
+        calc_noswap_join, calc_weighted_balancing_swap, pow_approx, tick_to_price, MAX_TICK,
+        MIN_INITIALIZED_TICK,
+    };
+
+    /// Assert that two Decimals are almost the same (diff smaller than three
+    /// permille)
+    fn assert_decimal_almost_eq(a: Decimal, b: Decimal) {
+        let diff = if a > b { (a - b) / a } else { (b - a) / b };
+        let max_allowed_diff = Decimal::permille(3);
+        if diff > max_allowed_diff {
+            panic!("Failed assert decimal almost eq for a: {a}, b: {b}. diff: {diff}, max allowed: {max_allowed_diff}");
+        }
+    }
+
+    #[test_case("0.5", "1", "0.5" ; "whole exponent")]
+    #[test_case("0.25", "0.5", "0.5" ; "square root")]
+    #[test_case("1.44", "0.5", "1.2" ; "square root of base above one")]
+    #[test_case("0.8", "4", "0.4096" ; "larger whole exponent")]
+    #[test_case("0.729", "0.333333333333333333", "0.9" ; "cube root")]
+    #[test_case("0.64", "1.5", "0.512" ; "whole and fractional exponent")]
+    fn test_pow_approx(base: &str, exp: &str, expected: &str) {
+        let result = pow_approx(base.parse().unwrap(), exp.parse().unwrap()).unwrap();
+        assert_decimal_almost_eq(result, expected.parse().unwrap());
+    }
+
+    #[test_case(
+        [1_000_000, 2_000_000],
+        [1_000_000_000_000, 1_000_000_000_000],
+        [1, 1],
+        true,
+        1;
+        "1:1 ratio, equal weights, double amount of asset 2"
+    )]
+    #[test_case(
+        [1_000_000_000_000, 1_000_000_000_000],
+        [1_000_000_000_000, 3_000_000_000_000],
+        [1, 1],
+        true,
+        0;
+        "1:3 pool ratio, equal weights, a lot of assets compared to pool"
+    )]
+    #[test_case(
+        [0, 1_000_000],
+        [1_000_000_000, 4_000_000_000],
+        [4, 1],
+        true,
+        1;
+        "80/20 pool, only asset 2"
+    )]
+    #[test_case(
+        [5_000_000, 0],
+        [1_000_000_000, 4_000_000_000],
+        [1, 4],
+        true,
+        0;
+        "20/80 pool, only asset 1"
+    )]
+    #[test_case(
+        [1_000_000, 1_000_000],
+        [1_000_000_000, 1_000_000_000],
+        [1, 3],
+        false,
+        1;
+        "Assets in same ratio as pool"
+    )]
+    #[test_case(
+        [1_000_000, 1_000_000],
+        [1_000_000_000, 0],
+        [1, 1],
+        false,
+        0
+        => panics "No liquidity in pool";
+        "No liquidity in pool"
+    )]
+    fn test_calc_weighted_balancing_swap(
+        assets: [u128; 2],
+        reserves: [u128; 2],
+        weights: [u128; 2],
+        should_swap: bool,
+        offer_asset_idx: usize,
+    ) {
+        let assets = [
+            Asset::new(AssetInfo::native("uatom"), assets[0]),
+            Asset::new(AssetInfo::native("uosmo"), assets[1]),
+        ];
+        let reserves = reserves.map(Uint128::new);
+        let weights = weights.map(|x| Decimal::from_ratio(x, 1u128));
+
+        let (offer_asset, return_asset) =
+            calc_weighted_balancing_swap(assets.clone(), reserves, weights, Decimal::permille(3))
+                .unwrap()
+                .unwrap();
+
+        if !should_swap {
+            assert!(offer_asset.amount <= Uint128::one());
+            return;
+        }
+        assert_eq!(offer_asset.info, assets[offer_asset_idx].info);
+
+        // Assert that the asset ratio and the pool ratio are the same after the swap
+        let ask_asset_idx = 1 - offer_asset_idx;
+        let asset_ratio_after_swap = Decimal::from_ratio(
+            assets[ask_asset_idx].amount + return_asset.amount,
+            assets[offer_asset_idx].amount - offer_asset.amount,
+        );
+        let reserve_ratio_after_swap = Decimal::from_ratio(
+            reserves[ask_asset_idx] - return_asset.amount,
+            reserves[offer_asset_idx] + offer_asset.amount,
+        );
+        assert_decimal_almost_eq(asset_ratio_after_swap, reserve_ratio_after_swap);
+    }
+
+    #[test]
+    fn test_calc_weighted_balancing_swap_exceeds_max_in_ratio() {
+        let assets = [
+            Asset::new(AssetInfo::native("uatom"), 10_000_000u128),
+            Asset::new(AssetInfo::native("uosmo"), 0u128),
+        ];
+        let reserves = [Uint128::new(1_000_000), Uint128::new(1_000_000)];
+        let weights = [Decimal::one(), Decimal::one()];
+
+        let res =
+            calc_weighted_balancing_swap(assets, reserves, weights, Decimal::permille(3)).unwrap();
+
+        assert_eq!(res, None);
+    }
+
+    #[test_case(
+        [1_000, 2_000],
+        [1_000_000, 2_000_000],
+        1_000_000,
+        1_000,
+        [1_000, 2_000];
+        "Assets in same ratio as pool"
+    )]
+    #[test_case(
+        [1_000, 5_000],
+        [1_000_000, 2_000_000],
+        1_000_000,
+        1_000,
+        [1_000, 2_000];
+        "Excess of second asset is not used"
+    )]
+    fn test_calc_noswap_join(
+        assets: [u128; 2],
+        reserves: [u128; 2],
+        total_shares: u128,
+        expected_lp_amount: u128,
+        expected_used: [u128; 2],
+    ) {
+        let (lp_amount, used) = calc_noswap_join(
+            assets.map(Uint128::new),
+            reserves.map(Uint128::new),
+            Uint128::new(total_shares),
+        )
+        .unwrap();
+
+        assert_eq!(lp_amount, Uint128::new(expected_lp_amount));
+        assert_eq!(used, expected_used.map(Uint128::new));
+    }
+
+    #[test_case([1_000, 5_000], "2", [1_000, 2_000] ; "excess of token1")]
+    #[test_case([1_000, 1_000], "2", [500, 1_000] ; "excess of token0")]
+    #[test_case([1_000, 2_000], "2", [1_000, 2_000] ; "assets at the price")]
+    fn test_calc_initial_provide_amounts(assets: [u128; 2], price: &str, expected: [u128; 2]) {
+        let amounts = calc_initial_provide_amounts(
+            assets.map(Uint128::new),
+            price.parse::<Decimal>().unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(amounts, expected.map(Uint128::new));
+    }
+
+    #[test_case(0, "1" ; "price one")]
+    #[test_case(1, "1.000001" ; "one tick above price one")]
+    #[test_case(-1, "0.9999999" ; "one tick below price one")]
+    #[test_case(9_000_000, "10" ; "price ten")]
+    #[test_case(-9_000_000, "0.1" ; "price one tenth")]
+    #[test_case(38_000_000, "30000" ; "price thirty thousand")]
+    #[test_case(MIN_INITIALIZED_TICK, "0.000000000001" ; "min initialized tick")]
+    #[test_case(MAX_TICK, "100000000000000000000000000000000000000" ; "max tick")]
+    fn test_tick_to_price(tick: i64, expected: &str) {
+        assert_eq!(
+            tick_to_price(tick).unwrap(),
+            expected.parse::<Decimal256>().unwrap()
+        );
+    }
+
+    #[test_case([1_000_000, 1_000_000], "1", "0.5", "2", None ; "balanced assets in symmetric range")]
+    #[test_case([2_000_000, 0], "1", "0.5", "2", Some(0) ; "only token0 in symmetric range")]
+    #[test_case([0, 2_000_000], "1", "0.5", "2", Some(1) ; "only token1 in symmetric range")]
+    #[test_case([1_000_000, 1_000_000], "1", "2", "3", Some(1) ; "price below range")]
+    #[test_case([1_000_000, 1_000_000], "4", "2", "3", Some(0) ; "price above range")]
+    #[test_case([3_000_000, 1_000_000], "2", "1", "5", Some(0) ; "excess token0 at price four")]
+    fn test_calc_cl_balancing_swap(
+        amounts: [u128; 2],
+        sqrt_price: &str,
+        lower_sqrt_price: &str,
+        upper_sqrt_price: &str,
+        expected_offer_idx: Option<usize>,
+    ) {
+        let assets = [
+            Asset::new(AssetInfo::native("uatom"), amounts[0]),
+            Asset::new(AssetInfo::native("uosmo"), amounts[1]),
+        ];
+        let sqrt_price: Decimal256 = sqrt_price.parse().unwrap();
+        let lower_sqrt_price: Decimal256 = lower_sqrt_price.parse().unwrap();
+        let upper_sqrt_price: Decimal256 = upper_sqrt_price.parse().unwrap();
+
+        let res = calc_cl_balancing_swap(
+            assets.clone(),
+            sqrt_price,
+            lower_sqrt_price,
+            upper_sqrt_price,
+            Decimal::zero(),
+        )
+        .unwrap();
+
+        let Some(offer_idx) = expected_offer_idx else {
+            assert_eq!(res, None);
+            return;
+        };
+        let (offer_asset, return_asset) = res.unwrap();
+        assert_eq!(offer_asset.info, assets[offer_idx].info);
+
+        // Outside of the range everything is swapped into the one token needed
+        if sqrt_price <= lower_sqrt_price || sqrt_price >= upper_sqrt_price {
+            assert_eq!(offer_asset.amount, assets[offer_idx].amount);
+            return;
+        }
+
+        // Assert that the ratio of the assets after the swap is the ratio
+        // required by the position
+        let mut amounts = [assets[0].amount, assets[1].amount];
+        amounts[offer_idx] -= offer_asset.amount;
+        amounts[1 - offer_idx] += return_asset.amount;
+        let amount0_per_liquidity =
+            (upper_sqrt_price - sqrt_price) / (sqrt_price * upper_sqrt_price);
+        let amount1_per_liquidity = sqrt_price - lower_sqrt_price;
+        let required_ratio =
+            Decimal::try_from(amount1_per_liquidity / amount0_per_liquidity).unwrap();
+        assert_decimal_almost_eq(Decimal::from_ratio(amounts[1], amounts[0]), required_ratio);
+    }
+
+    #[test_case([1_000, 1_000], "1", [1_000, 1_000] ; "assets in the ratio of the range")]
+    #[test_case([1_000, 3_000], "1", [1_000, 1_000] ; "excess of token1")]
+    #[test_case([3_000, 1_000], "1", [1_000, 1_000] ; "excess of token0")]
+    #[test_case([1_000, 1_000], "0.25", [1_000, 0] ; "price below range")]
+    #[test_case([1_000, 1_000], "4", [0, 1_000] ; "price above range")]
+    #[test_case([1_000, 3_000], "0", [1_000, 3_000] ; "empty pool")]
+    fn test_calc_cl_position_amounts(amounts: [u128; 2], sqrt_price: &str, expected: [u128; 2]) {
+        let used = calc_cl_position_amounts(
+            amounts.map(Uint128::new),
+            sqrt_price.parse().unwrap(),
+            Decimal256::percent(50),
+            Decimal256::percent(200),
+        )
+        .unwrap();
+
+        assert_eq!(used, expected.map(Uint128::new));
+    }
+}
@@ -1,41 +1,61 @@
-use apollo_cw_asset::Asset;
-use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{to_json_binary, Addr, CosmosMsg, Env, StdResult, Uint128, WasmMsg};
-use cw_dex_osmosis::OsmosisPool;
-use liquidity_helper::msg::ExecuteMsg as GenericExcuteMsg;
+use apollo_cw_asset::AssetList;
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal, Decimal256};
+use liquidity_helper::msg::BalancingSwap;
 
-#[cw_serde]
-pub struct InstantiateMsg {}
+pub use liquidity_helper::msg::{ExecuteMsg, QueryMsg};
 
-pub type ExecuteMsg = GenericExcuteMsg<CallbackMsg>;
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The owner of the contract, who can sweep assets held by it
+    pub owner: String,
+    /// The tolerance on the LP tokens expected from each single sided join of
+    /// a `BalancingProvideLiquidity`. Defaults to one percent.
+    pub max_join_slippage: Option<Decimal>,
+}
 
 #[cw_serde]
-pub enum CallbackMsg {
-    SingleSidedJoin {
-        asset: Asset,
-        pool: OsmosisPool,
-    },
-    ReturnLpTokens {
-        pool: OsmosisPool,
-        balance_before: Uint128,
-        recipient: Addr,
-        min_out: Uint128,
-    },
+pub struct MigrateMsg {
+    /// Sets the owner of the contract, which contracts instantiated before
+    /// ownership was added don't have. Fails with `OwnerAlreadySet` if the
+    /// contract has an owner.
+    pub owner: Option<String>,
+    /// Sets the tolerance on the LP tokens expected from each single sided
+    /// join of a `BalancingProvideLiquidity`. Left unchanged if `None`.
+    pub max_join_slippage: Option<Decimal>,
 }
 
-impl CallbackMsg {
-    pub fn into_cosmos_msg(&self, env: &Env) -> StdResult<CosmosMsg> {
-        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: env.contract.address.to_string(),
-            msg: to_json_binary(&ExecuteMsg::Callback(self.clone()))?,
-            funds: vec![],
-        }))
-    }
+/// A concentrated liquidity pool and the range to provide liquidity in. Can be
+/// given as the `pool` of `BalancingProvideLiquidity` instead of an
+/// `OsmosisPool`, in which case a position is created and transferred to the
+/// recipient.
+#[cw_serde]
+pub struct ConcentratedLiquidityPool {
+    pub pool_id: u64,
+    pub tick_range: TickRange,
 }
 
+/// The range of ticks to create a concentrated liquidity position in
 #[cw_serde]
-#[derive(QueryResponses)]
-pub enum QueryMsg {}
+pub enum TickRange {
+    /// The full range of prices supported by the pool
+    FullRange {},
+    /// A custom range of ticks. Both ticks must be multiples of the tick
+    /// spacing of the pool.
+    Ticks { lower_tick: i64, upper_tick: i64 },
+}
 
+/// Returned as `Response::data` from `BalancingProvideLiquidity` into a
+/// concentrated liquidity pool
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct CreatePositionResponse {
+    /// The id of the position transferred to the recipient
+    pub position_id: u64,
+    /// The liquidity of the position
+    pub liquidity: Decimal256,
+    /// The swap done before creating the position, if any
+    pub swapped: Option<BalancingSwap>,
+    /// Any assets that were returned instead of being used to create the
+    /// position
+    pub leftover: AssetList,
+}
@@ -0,0 +1,90 @@
+use apollo_cw_asset::{Asset, AssetList};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw_dex_osmosis::OsmosisPool;
+use cw_storage_plus::Item;
+use liquidity_helper::msg::{BalancingSwap, PostProvideAction, ProvideLiquidityResponse};
+
+/// The tolerance on the LP tokens expected from each single sided join of a
+/// `BalancingProvideLiquidity`. `DEFAULT_MAX_JOIN_SLIPPAGE` is used if it was
+/// not set when instantiating or migrating the contract.
+pub const MAX_JOIN_SLIPPAGE: Item<Decimal> = Item::new("max_join_slippage");
+
+pub const DEFAULT_MAX_JOIN_SLIPPAGE: Decimal = Decimal::percent(1);
+
+/// A single sided join planned when executing a `BalancingProvideLiquidity`
+#[cw_serde]
+pub struct SingleSidedJoin {
+    pub asset: Asset,
+    /// The minimum amount of LP tokens to receive from this join, based on
+    /// the amount expected when the join was planned
+    pub min_out: Uint128,
+}
+
+/// State of a `BalancingProvideLiquidity` that is waiting for the replies to
+/// the join pool submessages. Removed again in the last reply.
+#[cw_serde]
+pub struct ProvideLiquidityState {
+    pub pool: OsmosisPool,
+    /// The LP token balance of the contract before providing liquidity
+    pub lp_balance_before: Uint128,
+    /// The balances of the pool assets held by the contract before the funds
+    /// were received. Anything above these is refunded in the last reply.
+    pub balances_before: AssetList,
+    /// The balancing swap done before providing liquidity, if any
+    pub swapped: Option<BalancingSwap>,
+    /// The balance of the return asset of the balancing swap held by the
+    /// contract before the swap
+    pub return_balance_before: Uint128,
+    /// The assets to provide double sided after the balancing swap, not
+    /// including the amount returned from the swap
+    pub provide_assets: AssetList,
+    /// The single sided joins that are still to be done, one per reply
+    pub single_sided_joins: Vec<SingleSidedJoin>,
+    pub min_out: Uint128,
+    pub recipient: Addr,
+    pub post_provide: Option<PostProvideAction>,
+}
+
+pub const PROVIDE_LIQUIDITY_STATE: Item<ProvideLiquidityState> =
+    Item::new("provide_liquidity_state");
+
+/// State of a `BalancingProvideLiquidity` into a concentrated liquidity pool
+/// that is waiting for the replies to the swap and create position
+/// submessages. Removed again in the last reply.
+#[cw_serde]
+pub struct CreatePositionState {
+    pub pool_id: u64,
+    pub token0: String,
+    pub token1: String,
+    pub lower_tick: i64,
+    pub upper_tick: i64,
+    /// The balancing swap done before creating the position, if any
+    pub swapped: Option<BalancingSwap>,
+    /// The balance of the return asset of the balancing swap held by the
+    /// contract before the swap
+    pub return_balance_before: Uint128,
+    /// The assets to create the position with, not including the amount
+    /// returned from the balancing swap
+    pub provide_assets: AssetList,
+    /// The minimum liquidity of the created position
+    pub min_out: Uint128,
+    /// The tolerance on the amounts of the assets that the position is
+    /// expected to use
+    pub max_join_slippage: Decimal,
+    pub recipient: Addr,
+}
+
+pub const CREATE_POSITION_STATE: Item<CreatePositionState> = Item::new("create_position_state");
+
+/// State of a `BalancingProvideLiquidity` that is waiting for the reply to the
+/// authz message doing the post provide action for the recipient. Removed
+/// again in the reply.
+#[cw_serde]
+pub struct PostProvideState {
+    pub action: PostProvideAction,
+    /// The response to return, to which the lock ID is added in the reply
+    pub response: ProvideLiquidityResponse,
+}
+
+pub const POST_PROVIDE_STATE: Item<PostProvideState> = Item::new("post_provide_state");
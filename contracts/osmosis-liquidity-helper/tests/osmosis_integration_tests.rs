@@ -1,17 +1,41 @@
 use std::str::FromStr;
 use std::vec;
 
-use apollo_cw_asset::{Asset, AssetList};
-use cosmwasm_std::{to_json_binary, Addr, Coin, StdError, Uint128};
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use cosmwasm_std::{
+    assert_approx_eq, from_json, to_json_binary, Addr, BankMsg, Coin, Decimal, StdError, Uint128,
+};
 use cw_dex_osmosis::OsmosisPool;
-use cw_it::osmosis_std::types::cosmos::bank::v1beta1::QueryBalanceRequest;
+use cw_it::osmosis_std::types::cosmos::authz::v1beta1::{
+    GenericAuthorization, Grant, MsgGrant, MsgGrantResponse,
+};
+use cw_it::osmosis_std::types::cosmos::bank::v1beta1::{
+    QueryAllBalancesRequest, QueryBalanceRequest,
+};
+use cw_it::osmosis_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
 use cw_it::osmosis_std::types::cosmwasm::wasm::v1::MsgExecuteContractResponse;
+use cw_it::osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
+    CreateConcentratedLiquidityPoolsProposal, MsgCreatePosition, Pool as ConcentratedPool,
+    PoolRecord, PoolsRequest, PositionByIdRequest,
+};
+use cw_it::osmosis_std::types::osmosis::lockup::{LockedRequest, LockedResponse, MsgLockTokens};
+use cw_it::osmosis_std::types::osmosis::poolmanager::v1beta1::{
+    MsgSwapExactAmountIn, SwapAmountInRoute,
+};
 use cw_it::osmosis_test_tube::{
-    Account, Bank, Gamm, Module, OsmosisTestApp, Runner, RunnerError, RunnerResult, SigningAccount,
-    Wasm,
+    Account, Bank, ConcentratedLiquidity, Gamm, GovWithAppAccess, Module, OsmosisTestApp,
+    PoolManager, Runner, RunnerError, RunnerResult, SigningAccount, Wasm,
+};
+use liquidity_helper::msg::{
+    ExecuteMsg, PauseInfoResponse, PoolIdentifier, PostProvideAction, ProvideLiquidityResponse,
+    SimulateBalancingProvideLiquidityResponse,
 };
 use liquidity_helper::LiquidityHelper;
-use osmosis_liquidity_helper::msg::InstantiateMsg;
+use osmosis_liquidity_helper::contract::TWAP_WINDOW;
+use osmosis_liquidity_helper::math::{MAX_TICK, MIN_INITIALIZED_TICK};
+use osmosis_liquidity_helper::msg::{
+    ConcentratedLiquidityPool, CreatePositionResponse, InstantiateMsg, QueryMsg, TickRange,
+};
 
 use test_case::test_case;
 
@@ -89,7 +113,701 @@ pub fn test_with_osmosis_bindings(
         )
         .unwrap();
 
-    test_balancing_provide_liquidity(&app, accs, assets, pool_liquidity, min_out)
+    test_balancing_provide_liquidity(&app, accs, assets, pool_liquidity, min_out, None)
+}
+
+#[test_case(vec![Coin::new(3_000, "uatom"), Coin::new(1_000, "uosmo")].into(), "0.01" ; "Bindings: Unbalanced assets with max swap slippage")]
+#[test_case(vec![Coin::new(1_800_000, "uatom"), Coin::new(2_000_000, "uosmo")].into(), "0.01" ; "Bindings: Unbalanced assets with max swap slippage, high slippage")]
+#[test_case(vec![Coin::new(100, "uatom"), Coin::new(400_000, "uosmo")].into(), "0.01" ; "Bindings: Very unbalanced assets with max swap slippage")]
+/// Tests that providing liquidity with `max_swap_slippage` succeeds when the
+/// reserves have not moved since the message was executed
+pub fn test_balancing_provide_liquidity_max_swap_slippage(
+    assets: AssetList,
+    max_swap_slippage: &str,
+) -> RunnerResult<()> {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            2,
+        )
+        .unwrap();
+
+    test_balancing_provide_liquidity(
+        &app,
+        accs,
+        assets,
+        assets_native("uatom", Some("uosmo"), 1_000_000),
+        ONE,
+        Some(Decimal::from_str(max_swap_slippage).unwrap()),
+    )
+}
+
+#[test_case("uatom" ; "Bindings: Withdraw into uatom")]
+#[test_case("uosmo" ; "Bindings: Withdraw into uosmo")]
+/// Tests the BalancingWithdrawLiquidity message against the Osmosis bindings
+pub fn test_balancing_withdraw_liquidity(target_denom: &str) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            1,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let gamm = Gamm::new(&app);
+    let bank = Bank::new(&app);
+
+    // Create 1:1 pool
+    let pool_id = gamm
+        .create_basic_pool(&assets_native("uatom", Some("uosmo"), 1_000_000), &accs[0])
+        .unwrap()
+        .data
+        .pool_id;
+    let pool = OsmosisPool::unchecked(pool_id);
+    let lp_token_denom = format!("gamm/pool/{pool_id}");
+
+    let query_balance = |denom: &str| {
+        Uint128::from_str(
+            &bank
+                .query_balance(&QueryBalanceRequest {
+                    address: accs[0].address(),
+                    denom: denom.to_string(),
+                })
+                .unwrap()
+                .balance
+                .unwrap()
+                .amount,
+        )
+        .unwrap()
+    };
+    let other_denom = if target_denom == "uatom" {
+        "uosmo"
+    } else {
+        "uatom"
+    };
+    let target_balance_before = query_balance(target_denom);
+    let other_balance_before = query_balance(other_denom);
+    let lp_token_balance_before = query_balance(&lp_token_denom);
+
+    // Withdraw a tenth of the LP tokens into a single asset
+    let lp_amount = lp_token_balance_before / Uint128::new(10);
+    let msgs = liquidity_helper
+        .balancing_withdraw_liquidity(
+            Asset::native(lp_token_denom.clone(), lp_amount),
+            to_json_binary(&pool).unwrap(),
+            AssetInfo::native(target_denom),
+            Uint128::one(),
+            None,
+        )
+        .unwrap();
+    app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[0])
+        .unwrap();
+
+    // A tenth of the LP tokens is worth a tenth of each reserve, so we should
+    // get a bit less than a fifth of one reserve after swap fees and slippage.
+    let target_received = query_balance(target_denom) - target_balance_before;
+    assert_eq!(query_balance(other_denom), other_balance_before);
+    assert_eq!(
+        lp_token_balance_before - query_balance(&lp_token_denom),
+        lp_amount
+    );
+    assert!(target_received < Uint128::new(200_000));
+    assert!(target_received > Uint128::new(180_000));
+}
+
+#[test]
+/// Tests BalancingProvideLiquidity with the pool given by its ID instead of
+/// the serialised pool
+pub fn test_balancing_provide_liquidity_by_pool_id() {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            1,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let gamm = Gamm::new(&app);
+
+    // Create 1:1 pool
+    let pool_id = gamm
+        .create_basic_pool(&assets_native("uatom", Some("uosmo"), 1_000_000), &accs[0])
+        .unwrap()
+        .data
+        .pool_id;
+
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets_native("uatom", None, 100_000).into(),
+            Uint128::one(),
+            PoolIdentifier::OsmosisPool { pool_id },
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = app
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[0])
+        .unwrap();
+    let provide_res: ProvideLiquidityResponse = from_json(res.data.data).unwrap();
+
+    assert_eq!(
+        provide_res.lp_token,
+        AssetInfo::native(format!("gamm/pool/{pool_id}"))
+    );
+    assert!(!provide_res.lp_amount.is_zero());
+}
+
+#[test_case(true ; "Bindings: Lock tokens with authz grant")]
+#[test_case(false => panics "authorization not found" ; "Bindings: Lock tokens without authz grant")]
+/// Tests BalancingProvideLiquidity with the `LockTokens` post provide action,
+/// which locks the LP tokens for the recipient through its authz grant
+pub fn test_balancing_provide_liquidity_lock_tokens(grant: bool) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            1,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let gamm = Gamm::new(&app);
+
+    // Create 1:1 pool
+    let pool_id = gamm
+        .create_basic_pool(&assets_native("uatom", Some("uosmo"), 1_000_000), &accs[0])
+        .unwrap()
+        .data
+        .pool_id;
+
+    // Allow the liquidity helper to lock tokens for the recipient
+    if grant {
+        app.execute::<_, MsgGrantResponse>(
+            MsgGrant {
+                granter: accs[0].address(),
+                grantee: liquidity_helper.addr().to_string(),
+                grant: Some(Grant {
+                    authorization: Some(
+                        GenericAuthorization {
+                            msg: MsgLockTokens::TYPE_URL.to_string(),
+                        }
+                        .to_any(),
+                    ),
+                    expiration: None,
+                }),
+            },
+            MsgGrant::TYPE_URL,
+            &accs[0],
+        )
+        .unwrap();
+    }
+
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets_native("uatom", None, 100_000).into(),
+            Uint128::one(),
+            PoolIdentifier::OsmosisPool { pool_id },
+            None,
+            None,
+            None,
+            None,
+            Some(PostProvideAction::LockTokens { duration: 86400 }),
+        )
+        .unwrap();
+    let res = app
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[0])
+        .unwrap();
+    let provide_res: ProvideLiquidityResponse = from_json(res.data.data).unwrap();
+
+    // The LP tokens should be locked by the recipient
+    let lock = app
+        .query::<_, LockedResponse>(
+            "/osmosis.lockup.Query/LockedByID",
+            &LockedRequest {
+                lock_id: provide_res.lock_id.unwrap(),
+            },
+        )
+        .unwrap()
+        .lock
+        .unwrap();
+    assert_eq!(lock.owner, accs[0].address());
+    assert_eq!(
+        lock.coins,
+        vec![ProtoCoin {
+            denom: format!("gamm/pool/{pool_id}"),
+            amount: provide_res.lp_amount.to_string(),
+        }]
+    );
+}
+
+#[test_case(false, None ; "Bindings: Not manipulated, no max price deviation")]
+#[test_case(false, Some("0.01") ; "Bindings: Not manipulated, max price deviation")]
+#[test_case(true, None ; "Bindings: Manipulated, no max price deviation")]
+#[test_case(true, Some("0.01") => panics "exceeds max_price_deviation" ; "Bindings: Manipulated, max price deviation")]
+/// Tests that BalancingProvideLiquidity fails if `max_price_deviation` is given
+/// and the spot price of the pool was moved away from its TWAP
+pub fn test_balancing_provide_liquidity_max_price_deviation(
+    manipulated: bool,
+    max_price_deviation: Option<&str>,
+) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            1,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let gamm = Gamm::new(&app);
+
+    // Create 1:1 pool and let the TWAP settle
+    let pool_id = gamm
+        .create_basic_pool(&assets_native("uatom", Some("uosmo"), 1_000_000), &accs[0])
+        .unwrap()
+        .data
+        .pool_id;
+    app.increase_time(TWAP_WINDOW);
+
+    // Move the spot price with a large swap right before the provide
+    if manipulated {
+        PoolManager::new(&app)
+            .swap_exact_amount_in(
+                MsgSwapExactAmountIn {
+                    sender: accs[0].address(),
+                    routes: vec![SwapAmountInRoute {
+                        pool_id,
+                        token_out_denom: "uosmo".to_string(),
+                    }],
+                    token_in: Some(ProtoCoin {
+                        denom: "uatom".to_string(),
+                        amount: "500000".to_string(),
+                    }),
+                    token_out_min_amount: "1".to_string(),
+                },
+                &accs[0],
+            )
+            .unwrap();
+    }
+
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets_native("uatom", None, 100_000).into(),
+            Uint128::one(),
+            PoolIdentifier::OsmosisPool { pool_id },
+            None,
+            None,
+            None,
+            max_price_deviation.map(|x| Decimal::from_str(x).unwrap()),
+            None,
+        )
+        .unwrap();
+    app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[0])
+        .unwrap();
+}
+
+#[test_case(true ; "Bindings: Owner can sweep")]
+#[test_case(false => panics "Caller is not the contract's current owner" ; "Bindings: Non-owner can't sweep")]
+/// Tests that the owner can recover assets sent to the contract by mistake
+pub fn test_sweep(sender_is_owner: bool) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            2,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let bank = Bank::new(&app);
+    let contract_addr = liquidity_helper.addr().to_string();
+
+    // Send some assets to the liquidity helper by mistake
+    app.execute_cosmos_msgs::<MsgExecuteContractResponse>(
+        &[BankMsg::Send {
+            to_address: contract_addr.clone(),
+            amount: vec![Coin::new(1_000_000, "uatom")],
+        }
+        .into()],
+        &accs[1],
+    )
+    .unwrap();
+
+    let query_balance = |address: &str| {
+        Uint128::from_str(
+            &bank
+                .query_balance(&QueryBalanceRequest {
+                    address: address.to_string(),
+                    denom: "uatom".to_string(),
+                })
+                .unwrap()
+                .balance
+                .unwrap()
+                .amount,
+        )
+        .unwrap()
+    };
+    let recipient_balance_before = query_balance(&accs[1].address());
+
+    let msg = liquidity_helper
+        .call(
+            ExecuteMsg::Sweep {
+                assets: AssetList::from(assets_native("uatom", None, 1_000_000)).into(),
+                recipient: Some(accs[1].address()),
+            },
+            vec![],
+        )
+        .unwrap();
+    let sender = if sender_is_owner { &accs[0] } else { &accs[1] };
+    app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], sender)
+        .unwrap();
+
+    assert_eq!(query_balance(&contract_addr), Uint128::zero());
+    assert_eq!(
+        query_balance(&accs[1].address()) - recipient_balance_before,
+        Uint128::new(1_000_000)
+    );
+}
+
+#[test_case(TickRange::FullRange {}, vec![Coin::new(1_000_000, "uatom"), Coin::new(1_000_000, "uosmo")] ; "Bindings: CL full range, balanced assets")]
+#[test_case(TickRange::FullRange {}, vec![Coin::new(3_000_000, "uatom"), Coin::new(1_000_000, "uosmo")] ; "Bindings: CL full range, unbalanced assets")]
+#[test_case(TickRange::Ticks { lower_tick: -1_000_000, upper_tick: 500_000 }, vec![Coin::new(1_000_000, "uatom")] ; "Bindings: CL custom range, single asset")]
+#[test_case(TickRange::Ticks { lower_tick: 1_000_000, upper_tick: 2_000_000 }, vec![Coin::new(1_000_000, "uatom"), Coin::new(1_000_000, "uosmo")] ; "Bindings: CL range above current price")]
+/// Tests BalancingProvideLiquidity into a concentrated liquidity pool
+pub fn test_create_position(tick_range: TickRange, coins: Vec<Coin>) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            2,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let cl = ConcentratedLiquidity::new(&app);
+
+    let pool_id = create_concentrated_pool(&app, &accs[0]);
+
+    // Add full range liquidity at a price of one
+    cl.create_position(
+        MsgCreatePosition {
+            pool_id,
+            sender: accs[0].address(),
+            lower_tick: MIN_INITIALIZED_TICK,
+            upper_tick: MAX_TICK,
+            tokens_provided: vec![
+                ProtoCoin {
+                    denom: "uatom".to_string(),
+                    amount: "1000000000".to_string(),
+                },
+                ProtoCoin {
+                    denom: "uosmo".to_string(),
+                    amount: "1000000000".to_string(),
+                },
+            ],
+            token_min_amount0: "0".to_string(),
+            token_min_amount1: "0".to_string(),
+        },
+        &accs[0],
+    )
+    .unwrap();
+
+    // Provide liquidity through the helper
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            coins.clone().into(),
+            Uint128::one(),
+            to_json_binary(&ConcentratedLiquidityPool {
+                pool_id,
+                tick_range,
+            })
+            .unwrap(),
+            None,
+            Some(Decimal::percent(1)),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = app
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[1])
+        .unwrap();
+    let create_position_res: CreatePositionResponse = from_json(res.data.data).unwrap();
+
+    // The position should belong to the caller
+    let position = cl
+        .query_position_by_id(&PositionByIdRequest {
+            position_id: create_position_res.position_id,
+        })
+        .unwrap()
+        .position
+        .unwrap()
+        .position
+        .unwrap();
+    assert_eq!(position.address, accs[1].address());
+    assert_eq!(position.pool_id, pool_id);
+    assert!(!create_position_res.liquidity.is_zero());
+
+    // Only dust should be left over, as the price impact of the swap is not
+    // taken into account
+    let total_provided: Uint128 = coins.iter().map(|x| x.amount).sum();
+    for asset in create_position_res.leftover.iter() {
+        assert!(asset.amount <= total_provided / Uint128::new(100) + Uint128::new(10));
+    }
+}
+
+#[test]
+/// Tests that SimulateBalancingProvideLiquidity fails for a concentrated
+/// liquidity pool
+pub fn test_simulate_create_position_unsupported() {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            2,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let pool_id = create_concentrated_pool(&app, &accs[0]);
+
+    let err = Wasm::new(&app)
+        .query::<_, SimulateBalancingProvideLiquidityResponse>(
+            liquidity_helper.addr().as_ref(),
+            &QueryMsg::SimulateBalancingProvideLiquidity {
+                assets: AssetList::from(vec![Coin::new(1_000_000, "uatom")]).into(),
+                pool: PoolIdentifier::OsmosisPool { pool_id }.into(),
+                initial_price: None,
+            },
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains(&format!(
+        "Simulating a provide to concentrated liquidity pool {pool_id} is not supported"
+    )));
+}
+
+#[test_case(None ; "Bindings: CL empty pool, ratio of the assets")]
+#[test_case(Some("2") ; "Bindings: CL empty pool, initial price")]
+/// Tests that BalancingProvideLiquidity creates the first position in an
+/// empty concentrated liquidity pool without swapping
+pub fn test_create_position_empty_pool(initial_price: Option<&str>) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            2,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let cl = ConcentratedLiquidity::new(&app);
+    let pool_id = create_concentrated_pool(&app, &accs[0]);
+    let initial_price = initial_price.map(|x| Decimal::from_str(x).unwrap());
+
+    let coins = vec![Coin::new(1_000_000, "uatom"), Coin::new(3_000_000, "uosmo")];
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            coins.into(),
+            Uint128::one(),
+            to_json_binary(&ConcentratedLiquidityPool {
+                pool_id,
+                tick_range: TickRange::FullRange {},
+            })
+            .unwrap(),
+            None,
+            None,
+            initial_price,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = app
+        .execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[1])
+        .unwrap();
+    let create_position_res: CreatePositionResponse = from_json(res.data.data).unwrap();
+    assert_eq!(create_position_res.swapped, None);
+
+    // The position sets the price of the pool to the ratio of its assets,
+    // which is the initial price if one is given
+    let position = cl
+        .query_position_by_id(&PositionByIdRequest {
+            position_id: create_position_res.position_id,
+        })
+        .unwrap()
+        .position
+        .unwrap();
+    assert_eq!(position.position.unwrap().address, accs[1].address());
+    let amount0 = Uint128::from_str(&position.asset0.unwrap().amount).unwrap();
+    let amount1 = Uint128::from_str(&position.asset1.unwrap().amount).unwrap();
+    let expected_price = initial_price.unwrap_or(Decimal::from_ratio(3u8, 1u8));
+    assert_approx_eq!(amount1, amount0 * expected_price, "0.001");
+
+    // The excess of uosmo is returned if an initial price is given
+    let leftover = create_position_res
+        .leftover
+        .find(&AssetInfo::native("uosmo"))
+        .map_or_else(Uint128::zero, |x| x.amount);
+    if initial_price.is_some() {
+        assert_approx_eq!(leftover, Uint128::new(1_000_000), "0.001");
+    } else {
+        assert!(leftover <= Uint128::new(10));
+    }
+}
+
+/// Creates a uatom/uosmo concentrated liquidity pool through governance and
+/// returns its id
+fn create_concentrated_pool(app: &OsmosisTestApp, signer: &SigningAccount) -> u64 {
+    GovWithAppAccess::new(app)
+        .propose_and_execute(
+            CreateConcentratedLiquidityPoolsProposal::TYPE_URL.to_string(),
+            CreateConcentratedLiquidityPoolsProposal {
+                title: "Create CL pool".to_string(),
+                description: "Create CL pool".to_string(),
+                pool_records: vec![PoolRecord {
+                    denom0: "uatom".to_string(),
+                    denom1: "uosmo".to_string(),
+                    tick_spacing: 100,
+                    spread_factor: Decimal::permille(3).atomics().to_string(),
+                }],
+            },
+            signer.address(),
+            signer,
+        )
+        .unwrap();
+    let pools = ConcentratedLiquidity::new(app)
+        .query_pools(&PoolsRequest { pagination: None })
+        .unwrap();
+    ConcentratedPool::try_from(pools.pools.last().unwrap().clone())
+        .unwrap()
+        .id
+}
+
+#[test_case(None, false ; "Bindings: Not paused")]
+#[test_case(Some(None), true => panics "Providing liquidity is paused" ; "Bindings: All pools paused")]
+#[test_case(Some(Some(0)), true => panics "Providing liquidity is paused" ; "Bindings: Pool paused")]
+#[test_case(Some(Some(1)), true ; "Bindings: Other pool paused")]
+#[test_case(Some(None), false => panics "Unauthorized" ; "Bindings: Guardian can't unpause")]
+/// Tests that BalancingProvideLiquidity fails while paused globally or for the
+/// pool. `pause` is the pool to pause by the guardian, as an offset from the
+/// ID of the pool provided to, or `None` for all pools. `unpause_first` has
+/// the owner pause and the guardian try to unpause instead.
+pub fn test_update_pause(pause: Option<Option<u64>>, unpause_first: bool) {
+    let app = OsmosisTestApp::default();
+
+    let accs = app
+        .init_accounts(
+            &[
+                Coin::new(1_000_000_000_000, "uatom"),
+                Coin::new(1_000_000_000_000, "uosmo"),
+            ],
+            2,
+        )
+        .unwrap();
+    let liquidity_helper = setup_osmosis_liquidity_provider_tests(&app, &accs);
+    let gamm = Gamm::new(&app);
+    let (owner, guardian) = (&accs[0], &accs[1]);
+
+    // Create 1:1 pool
+    let pool_id = gamm
+        .create_basic_pool(&assets_native("uatom", Some("uosmo"), 1_000_000), owner)
+        .unwrap()
+        .data
+        .pool_id;
+
+    let execute = |msg: ExecuteMsg, signer: &SigningAccount| {
+        let msg = liquidity_helper.call(msg, vec![]).unwrap();
+        app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&[msg], signer)
+    };
+    execute(
+        ExecuteMsg::UpdateGuardian {
+            guardian: Some(guardian.address()),
+        },
+        owner,
+    )
+    .unwrap();
+    if let Some(offset) = pause {
+        let pool = offset.map(|offset| PoolIdentifier::OsmosisPool {
+            pool_id: pool_id + offset,
+        });
+        if unpause_first {
+            execute(
+                ExecuteMsg::UpdatePause {
+                    pool: pool.clone(),
+                    paused: true,
+                },
+                owner,
+            )
+            .unwrap();
+        }
+        execute(
+            ExecuteMsg::UpdatePause {
+                pool,
+                paused: !unpause_first,
+            },
+            guardian,
+        )
+        .unwrap();
+    }
+
+    let pause_info: PauseInfoResponse = Wasm::new(&app)
+        .query(liquidity_helper.addr().as_ref(), &QueryMsg::PauseInfo {})
+        .unwrap();
+    assert_eq!(
+        pause_info.guardian,
+        Some(Addr::unchecked(guardian.address()))
+    );
+    let pool_identifier = PoolIdentifier::OsmosisPool { pool_id };
+    let is_paused = pause_info.global || pause_info.pools.contains(&pool_identifier);
+
+    let msgs = liquidity_helper
+        .balancing_provide_liquidity(
+            assets_native("uatom", None, 100_000).into(),
+            Uint128::one(),
+            pool_identifier,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let res = app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, owner);
+    assert_eq!(res.is_err(), is_paused);
+    res.unwrap();
 }
 
 /// Instantiates the liquidity helper contract
@@ -115,7 +833,10 @@ where
     let contract_addr = wasm
         .instantiate(
             code_id,
-            &InstantiateMsg {},
+            &InstantiateMsg {
+                owner: admin.address(),
+                max_join_slippage: None,
+            },
             Some(&admin.address()), // contract admin used for migration
             Some("Osmosis Liquidity Helper"), // contract label
             &[],                    // funds
@@ -135,6 +856,7 @@ pub fn test_balancing_provide_liquidity<R>(
     assets: AssetList,
     initial_pool_liquidity: Vec<Coin>,
     min_out: Uint128,
+    max_swap_slippage: Option<Decimal>,
 ) -> RunnerResult<()>
 where
     R: for<'a> Runner<'a>,
@@ -170,6 +892,28 @@ where
     )
     .unwrap();
 
+    // Check the balances of the liquidity helper before
+    let helper_balances_request = QueryAllBalancesRequest {
+        address: liquidity_helper.addr().to_string(),
+        pagination: None,
+    };
+    let helper_balances_before = bank
+        .query_all_balances(&helper_balances_request)
+        .unwrap()
+        .balances;
+
+    // Simulate balancing provide liquidity
+    let simulation: SimulateBalancingProvideLiquidityResponse = Wasm::new(app).query(
+        liquidity_helper.addr().as_ref(),
+        &QueryMsg::SimulateBalancingProvideLiquidity {
+            assets: assets.clone().into(),
+            pool: to_json_binary(&pool)
+                .map_err(|e| RunnerError::GenericError(e.to_string()))?
+                .into(),
+            initial_price: None,
+        },
+    )?;
+
     // Balancing Provide liquidity
     let msgs = liquidity_helper
         .balancing_provide_liquidity(
@@ -177,15 +921,26 @@ where
             min_out,
             to_json_binary(&pool).map_err(|e| RunnerError::GenericError(e.to_string()))?,
             None,
+            max_swap_slippage,
+            None,
+            None,
+            None,
         )
         .map_err(|e| RunnerError::GenericError(e.to_string()))?;
-    app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[1])?;
+    let res = app.execute_cosmos_msgs::<MsgExecuteContractResponse>(&msgs, &accs[1])?;
+    let provide_res: ProvideLiquidityResponse =
+        from_json(res.data.data).map_err(|e| RunnerError::GenericError(e.to_string()))?;
 
-    // Convert assets to native coins
+    // Convert assets to native coins, without the leftover that was returned
+    let mut provided = assets.clone();
+    provided
+        .deduct_many(&provide_res.leftover)
+        .map_err(|e| RunnerError::GenericError(e.to_string()))?;
     let mut coins: Vec<Coin> = vec![];
-    for a in assets.into_iter() {
+    for a in provided.into_iter() {
         coins.push(
-            a.try_into()
+            a.clone()
+                .try_into()
                 .map_err(|e: StdError| RunnerError::GenericError(e.to_string()))?,
         )
     }
@@ -197,6 +952,12 @@ where
         merge_coins(&[&initial_pool_liquidity, &coins])
     );
 
+    // Only dust should be left over, e.g. due to rounding or the taker fee
+    for asset in provide_res.leftover.iter() {
+        let provided_amount = assets.find(&asset.info).unwrap().amount;
+        assert!(asset.amount <= provided_amount / Uint128::new(100) + Uint128::new(10));
+    }
+
     // Make sure caller got all LP tokens
     let lp_token_supply_after = Uint128::from_str(
         &gamm
@@ -224,6 +985,18 @@ where
         lp_token_balance_after,
         lp_token_balance_before + lp_tokens_added
     );
+    assert_eq!(provide_res.lp_amount, lp_tokens_added);
+
+    // Make sure the simulation was accurate
+    assert_approx_eq!(lp_tokens_added, simulation.lp_tokens.amount, "0.001");
+
+    // Anything not provided should have been refunded, so the liquidity helper
+    // holds the same balances as before
+    let helper_balances_after = bank
+        .query_all_balances(&helper_balances_request)
+        .unwrap()
+        .balances;
+    assert_eq!(helper_balances_after, helper_balances_before);
 
     Ok(())
 }
@@ -0,0 +1,1052 @@
+//! A framework for liquidity helper contracts. A contract implements
+//! [`LiquidityHelperContract`] for its DEX, providing the pool type, the
+//! balancing strategy and how to provide liquidity, and gets the handling of
+//! the other messages, the withdraw replies, the queries and the migration from
+//! the provided methods. The entry points of the contract then only delegate to
+//! the trait.
+
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
+use apollo_utils::assets::receive_assets;
+use apollo_utils::responses::merge_responses;
+use cosmwasm_schema::serde::de::DeserializeOwned;
+use cosmwasm_schema::serde::Serialize;
+use cosmwasm_std::{
+    to_json_binary, Addr, Api, Binary, CosmosMsg, Decimal, Deps, DepsMut, Env, Event, MessageInfo,
+    Order, OverflowError, Reply, ReplyOn, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg,
+};
+use cw2::set_contract_version;
+use cw20::Cw20ExecuteMsg;
+use cw_dex::traits::Pool;
+use cw_dex::CwDexError;
+use cw_dex_router::helpers::CwDexRouter;
+use cw_ownable::OwnershipError;
+use cw_vault_standard::{VaultContract, VaultContractUnchecked};
+
+use crate::error::LiquidityHelperError;
+use crate::msg::{
+    ExecuteMsg, PauseInfoResponse, PoolIdentifier, PoolInput, PostProvideAction,
+    ProvideLiquidityResponse, QueryMsg, SimulateBalancingProvideLiquidityResponse,
+};
+use crate::state::{
+    withdraw_liquidity_state, MigrateLiquidityState, VaultDepositState, WithdrawLiquidityState,
+    ZapState, GUARDIAN, MIGRATE_LIQUIDITY_STATE, PAUSED, PAUSED_POOLS, ROUTER, VAULT_DEPOSIT_STATE,
+    ZAP_STATE,
+};
+
+/// The reply ID of the withdraw liquidity submessage of a
+/// `BalancingWithdrawLiquidity`. Implementations must not use it for their own
+/// submessages.
+pub const WITHDRAW_LIQUIDITY_REPLY_ID: u64 = 2;
+/// The reply ID of the last swap submessage of a `BalancingWithdrawLiquidity`.
+/// Implementations must not use it for their own submessages.
+pub const SWAP_TO_TARGET_ASSET_REPLY_ID: u64 = 3;
+/// The reply ID of the last submessage routing the assets not in the pool into
+/// pool assets before providing liquidity. Implementations must not use it for
+/// their own submessages.
+pub const ZAP_REPLY_ID: u64 = 7;
+/// The reply ID of the withdraw liquidity submessage of a `MigrateLiquidity`.
+/// Implementations must not use it for their own submessages.
+pub const MIGRATE_LIQUIDITY_REPLY_ID: u64 = 8;
+/// The reply ID of the deposit into the vault of a `VaultDeposit` post provide
+/// action. Implementations must not use it for their own submessages.
+pub const VAULT_DEPOSIT_REPLY_ID: u64 = 10;
+
+/// The fields of `ExecuteMsg::BalancingProvideLiquidity`, with the assets and
+/// the recipient validated
+pub struct BalancingProvideLiquidityArgs {
+    pub assets: AssetList,
+    pub min_out: Uint128,
+    /// The pool as given in the message, since implementations may accept
+    /// other pool structs than `LiquidityHelperContract::Pool`
+    pub pool: PoolInput,
+    pub recipient: Addr,
+    pub max_swap_slippage: Option<Decimal>,
+    pub initial_price: Option<Decimal>,
+    pub max_price_deviation: Option<Decimal>,
+    pub post_provide: Option<PostProvideAction>,
+    /// Whether `assets` are already held by the contract, because they were
+    /// routed into pool assets first. They must then not be received from the
+    /// sender, and any assets returned go to the recipient.
+    pub assets_held: bool,
+}
+
+/// A liquidity helper contract for a DEX.
+///
+/// Implementations provide the DEX specific parts, which are resolving pools
+/// and providing liquidity. Everything else is handled by the provided
+/// methods, which implementations normally don't override.
+pub trait LiquidityHelperContract {
+    /// The pool type of the DEX
+    type Pool: Pool + Serialize + DeserializeOwned;
+    /// The error type of the contract
+    type Error: From<StdError>
+        + From<OverflowError>
+        + From<CwDexError>
+        + From<OwnershipError>
+        + From<LiquidityHelperError>
+        + Into<StdError>;
+
+    /// The name of the crate. Used in event names and, prefixed with
+    /// `crates.io:`, as the cw2 contract name.
+    const CONTRACT_NAME: &'static str;
+    /// The version of the crate, stored with cw2
+    const CONTRACT_VERSION: &'static str;
+
+    /// Deserialises the pool or looks it up on chain by its identifier
+    fn parse_pool(deps: Deps, pool: PoolInput) -> Result<Self::Pool, Self::Error>;
+
+    /// Validates an identifier of a pool of this DEX, e.g. to pause it.
+    /// Returns an error for identifiers of other DEXes.
+    fn validate_pool_identifier(
+        api: &dyn Api,
+        identifier: PoolIdentifier,
+    ) -> Result<PoolIdentifier, Self::Error>;
+
+    /// Returns the assets of the pool, which assets not in it are routed into
+    /// before providing liquidity. Implementations that accept other pool
+    /// structs than `Self::Pool` should override it.
+    fn query_pool_assets(deps: Deps, pool: &PoolInput) -> Result<Vec<AssetInfo>, Self::Error> {
+        Ok(Self::parse_pool(deps, pool.clone())?.pool_assets(deps)?)
+    }
+
+    /// Handles `ExecuteMsg::BalancingProvideLiquidity`. Implementations should
+    /// call `assert_not_paused` for the pool first.
+    fn balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        args: BalancingProvideLiquidityArgs,
+    ) -> Result<Response, Self::Error>;
+
+    /// Handles the replies to the submessages sent by
+    /// `balancing_provide_liquidity`
+    fn reply_balancing_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        msg: Reply,
+    ) -> Result<Response, Self::Error>;
+
+    /// Handles `QueryMsg::SimulateBalancingProvideLiquidity`
+    fn simulate_balancing_provide_liquidity(
+        deps: Deps,
+        env: Env,
+        assets: AssetList,
+        pool: Self::Pool,
+        initial_price: Option<Decimal>,
+    ) -> Result<SimulateBalancingProvideLiquidityResponse, Self::Error>;
+
+    /// Handles `ExecuteMsg::RecordPriceObservation`. Does nothing by default,
+    /// for DEXes that keep their own price history.
+    fn record_price_observation(
+        _deps: DepsMut,
+        _env: Env,
+        _pool: Self::Pool,
+    ) -> Result<Response, Self::Error> {
+        Ok(Response::default())
+    }
+
+    /// Returns an event named `apollo/<CONTRACT_NAME>/<name>`
+    fn event(name: &str) -> Event {
+        Event::new(format!("apollo/{}/{name}", Self::CONTRACT_NAME))
+    }
+
+    /// Stores the cw2 version info and the owner. To be called from the
+    /// `instantiate` entry point.
+    fn instantiate(deps: DepsMut, owner: &str) -> Result<Response, Self::Error> {
+        set_contract_version(
+            deps.storage,
+            format!("crates.io:{}", Self::CONTRACT_NAME),
+            Self::CONTRACT_VERSION,
+        )?;
+        cw_ownable::initialize_owner(deps.storage, deps.api, Some(owner))?;
+
+        Ok(Response::default())
+    }
+
+    fn execute(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response, Self::Error> {
+        match msg {
+            ExecuteMsg::BalancingProvideLiquidity {
+                assets,
+                min_out,
+                pool,
+                recipient,
+                max_swap_slippage,
+                initial_price,
+                max_price_deviation,
+                post_provide,
+            } => {
+                let args = BalancingProvideLiquidityArgs {
+                    assets: assets.check(deps.api)?,
+                    min_out,
+                    pool,
+                    recipient: resolve_recipient(deps.api, &info, recipient)?,
+                    max_swap_slippage,
+                    initial_price,
+                    max_price_deviation,
+                    post_provide,
+                    assets_held: false,
+                };
+                Self::route_and_provide_liquidity(deps, env, info, args)
+            }
+            ExecuteMsg::BalancingWithdrawLiquidity {
+                lp_token,
+                pool,
+                target_asset,
+                min_out,
+                recipient,
+            } => {
+                let lp_token = lp_token.check(deps.api)?;
+                let target_asset = target_asset.check(deps.api)?;
+                let pool = Self::parse_pool(deps.as_ref(), pool)?;
+                let recipient = resolve_recipient(deps.api, &info, recipient)?;
+                Self::execute_balancing_withdraw_liquidity(
+                    deps,
+                    env,
+                    info,
+                    lp_token,
+                    pool,
+                    target_asset,
+                    min_out,
+                    recipient,
+                )
+            }
+            ExecuteMsg::MigrateLiquidity {
+                from_pool,
+                to_pool,
+                lp_amount,
+                min_out,
+                recipient,
+                min_withdrawn,
+                max_swap_slippage,
+                max_price_deviation,
+            } => {
+                let from_pool = Self::parse_pool(deps.as_ref(), from_pool)?;
+                let min_withdrawn = match min_withdrawn {
+                    Some(min_withdrawn) => min_withdrawn.check(deps.api)?,
+                    None => AssetList::new(),
+                };
+                let args = BalancingProvideLiquidityArgs {
+                    assets: AssetList::new(),
+                    min_out,
+                    pool: to_pool,
+                    recipient: resolve_recipient(deps.api, &info, recipient)?,
+                    max_swap_slippage,
+                    initial_price: None,
+                    max_price_deviation,
+                    post_provide: None,
+                    assets_held: true,
+                };
+                Self::execute_migrate_liquidity(
+                    deps,
+                    env,
+                    info,
+                    from_pool,
+                    lp_amount,
+                    min_withdrawn,
+                    args,
+                )
+            }
+            ExecuteMsg::RecordPriceObservation { pool } => {
+                let pool = Self::parse_pool(deps.as_ref(), pool)?;
+                Self::record_price_observation(deps, env, pool)
+            }
+            ExecuteMsg::Sweep { assets, recipient } => {
+                let assets = assets.check(deps.api)?;
+                let recipient = resolve_recipient(deps.api, &info, recipient)?;
+                Self::execute_sweep(deps, info, assets, recipient)
+            }
+            ExecuteMsg::UpdateOwnership(action) => {
+                let ownership =
+                    cw_ownable::update_ownership(deps, &env.block, &info.sender, action)?;
+                Ok(Response::new().add_attributes(ownership.into_attributes()))
+            }
+            ExecuteMsg::UpdatePause { pool, paused } => {
+                Self::execute_update_pause(deps, info, pool, paused)
+            }
+            ExecuteMsg::UpdateGuardian { guardian } => {
+                Self::execute_update_guardian(deps, info, guardian)
+            }
+            ExecuteMsg::UpdateRouter { router } => Self::execute_update_router(deps, info, router),
+        }
+    }
+
+    /// Provides liquidity with the assets, routing those that are not in the
+    /// pool into pool assets first if a router is set
+    fn route_and_provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        args: BalancingProvideLiquidityArgs,
+    ) -> Result<Response, Self::Error> {
+        if let Some(router) = ROUTER.may_load(deps.storage)? {
+            let pool_assets = Self::query_pool_assets(deps.as_ref(), &args.pool)?;
+            if args.assets.iter().any(|x| !pool_assets.contains(&x.info)) {
+                return Self::execute_zap(deps, env, info, args, pool_assets, router);
+            }
+        }
+        Self::balancing_provide_liquidity(deps, env, info, args)
+    }
+
+    /// Receives the assets and routes those that are not in the pool into pool
+    /// assets through the router. Each asset is routed into the first pool
+    /// asset that the router has a path to. The pool assets are provided in
+    /// the reply to the last routing message.
+    fn execute_zap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        args: BalancingProvideLiquidityArgs,
+        pool_assets: Vec<AssetInfo>,
+        router: Addr,
+    ) -> Result<Response, Self::Error> {
+        // Get response with message to do TransferFrom on any Cw20s and assert
+        // that native tokens have been received already, unless the contract
+        // already holds the assets
+        let (receive_res, received) = if args.assets_held {
+            (Response::new(), args.assets.clone())
+        } else {
+            (
+                receive_assets(&info, &env, &args.assets)?,
+                info.funds.clone().into(),
+            )
+        };
+
+        // Check the balances of the pool assets before the assets were
+        // received, so that everything received or routed into them is
+        // provided in the reply
+        let mut balances_before = query_balances(deps.as_ref(), &env, &pool_assets)?;
+        for asset in received.iter().filter(|x| pool_assets.contains(&x.info)) {
+            balances_before.deduct(asset)?;
+        }
+
+        let router = CwDexRouter::new(&router);
+        let mut routes: Vec<(AssetInfo, AssetList)> = vec![];
+        let mut routed = AssetList::new();
+        for asset in args
+            .assets
+            .iter()
+            .filter(|x| !pool_assets.contains(&x.info))
+        {
+            let ask_assets = router.query_supported_ask_assets(&deps.querier, &asset.info)?;
+            let receive_asset = pool_assets
+                .iter()
+                .find(|x| ask_assets.contains(x))
+                .ok_or_else(|| LiquidityHelperError::NoRoute {
+                    asset: asset.info.clone(),
+                })?;
+            match routes.iter_mut().find(|(x, _)| x == receive_asset) {
+                Some((_, offer_assets)) => {
+                    offer_assets.add(asset)?;
+                }
+                None => routes.push((receive_asset.clone(), vec![asset.clone()].into())),
+            }
+            routed.add(asset)?;
+        }
+        let mut route_msgs = vec![];
+        for (receive_asset, offer_assets) in routes {
+            route_msgs.extend(router.basket_liquidate_msgs(
+                offer_assets,
+                &receive_asset,
+                None,
+                None,
+            )?);
+        }
+        let route_res =
+            reply_on_last_message(Response::new().add_messages(route_msgs), ZAP_REPLY_ID)?;
+
+        ZAP_STATE.save(
+            deps.storage,
+            &ZapState {
+                pool: args.pool,
+                pool_assets,
+                balances_before,
+                min_out: args.min_out,
+                recipient: args.recipient,
+                max_swap_slippage: args.max_swap_slippage,
+                initial_price: args.initial_price,
+                max_price_deviation: args.max_price_deviation,
+                post_provide: args.post_provide,
+            },
+        )?;
+
+        let event = Self::event("execute_zap")
+            .add_attribute("assets", args.assets.to_string())
+            .add_attribute("routed", routed.to_string());
+
+        Ok(merge_responses(vec![receive_res, route_res]).add_event(event))
+    }
+
+    /// Withdraws liquidity and swaps the withdrawn assets other than
+    /// `target_asset` into it in the replies
+    #[allow(clippy::too_many_arguments)]
+    fn execute_balancing_withdraw_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        lp_token: Asset,
+        pool: Self::Pool,
+        target_asset: AssetInfo,
+        min_out: Uint128,
+        recipient: Addr,
+    ) -> Result<Response, Self::Error> {
+        if lp_token.info != pool.lp_token() {
+            return Err(CwDexError::InvalidLpToken {}.into());
+        }
+        let pool_assets = pool.pool_assets(deps.as_ref())?;
+        if !pool_assets.contains(&target_asset) {
+            return Err(LiquidityHelperError::TargetAssetNotInPool { target_asset }.into());
+        }
+
+        let receive_res = Self::receive_lp_token(&env, &info, &lp_token)?;
+
+        // Check balances of the pool assets before withdrawing, so that we know
+        // how much was withdrawn in the reply
+        let balances_before = query_balances(deps.as_ref(), &env, &pool_assets)?;
+
+        let withdraw_res =
+            pool.withdraw_liquidity(deps.as_ref(), &env, lp_token.clone(), AssetList::new())?;
+        let withdraw_res = reply_on_last_message(withdraw_res, WITHDRAW_LIQUIDITY_REPLY_ID)?;
+
+        withdraw_liquidity_state().save(
+            deps.storage,
+            &WithdrawLiquidityState {
+                pool,
+                target_asset: target_asset.clone(),
+                balances_before,
+                min_out,
+                recipient,
+            },
+        )?;
+
+        let event = Self::event("execute_balancing_withdraw_liquidity")
+            .add_attribute("lp_token", lp_token.to_string())
+            .add_attribute("target_asset", target_asset.to_string())
+            .add_attribute("min_out", min_out);
+
+        Ok(merge_responses(vec![receive_res, withdraw_res]).add_event(event))
+    }
+
+    /// Withdraws `lp_amount` of the LP token from `from_pool`, and provides
+    /// the withdrawn assets to the pool of `args` in the reply
+    fn execute_migrate_liquidity(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        from_pool: Self::Pool,
+        lp_amount: Uint128,
+        min_withdrawn: AssetList,
+        args: BalancingProvideLiquidityArgs,
+    ) -> Result<Response, Self::Error> {
+        let withdrawn_assets = from_pool.pool_assets(deps.as_ref())?;
+        if let Some(min) = min_withdrawn
+            .iter()
+            .find(|min| !withdrawn_assets.contains(&min.info))
+        {
+            return Err(LiquidityHelperError::MinWithdrawnAssetNotInPool {
+                asset: min.info.clone(),
+            }
+            .into());
+        }
+
+        let lp_token = Asset::new(from_pool.lp_token(), lp_amount);
+        let receive_res = Self::receive_lp_token(&env, &info, &lp_token)?;
+
+        // Check balances of the pool assets before withdrawing, so that we know
+        // how much was withdrawn in the reply
+        let balances_before = query_balances(deps.as_ref(), &env, &withdrawn_assets)?;
+
+        let withdraw_res = from_pool.withdraw_liquidity(
+            deps.as_ref(),
+            &env,
+            lp_token.clone(),
+            AssetList::new(),
+        )?;
+        let withdraw_res = reply_on_last_message(withdraw_res, MIGRATE_LIQUIDITY_REPLY_ID)?;
+
+        MIGRATE_LIQUIDITY_STATE.save(
+            deps.storage,
+            &MigrateLiquidityState {
+                to_pool: args.pool,
+                withdrawn_assets,
+                balances_before,
+                min_withdrawn,
+                min_out: args.min_out,
+                recipient: args.recipient,
+                max_swap_slippage: args.max_swap_slippage,
+                max_price_deviation: args.max_price_deviation,
+            },
+        )?;
+
+        let event = Self::event("execute_migrate_liquidity")
+            .add_attribute("lp_token", lp_token.to_string())
+            .add_attribute("min_out", args.min_out);
+
+        Ok(merge_responses(vec![receive_res, withdraw_res]).add_event(event))
+    }
+
+    /// Returns a response receiving the LP token if it is a Cw20. Asserts that
+    /// a native LP token was sent and that no other funds were, since they
+    /// would be stuck in the contract.
+    fn receive_lp_token(
+        env: &Env,
+        info: &MessageInfo,
+        lp_token: &Asset,
+    ) -> Result<Response, Self::Error> {
+        let expected_funds: AssetList = match &lp_token.info {
+            AssetInfo::Native(_) => vec![lp_token.clone()].into(),
+            AssetInfo::Cw20(_) => AssetList::new(),
+        };
+        if expected_funds != info.funds.clone().into() {
+            return Err(LiquidityHelperError::InputTokenMismatch {
+                expected: info.funds.iter().map(|a| a.into()).collect(),
+                received: vec![lp_token.clone()],
+            }
+            .into());
+        }
+
+        // Get response with message to do TransferFrom on the LP token if it is a
+        // Cw20
+        Ok(receive_assets(info, env, &vec![lp_token.clone()].into())?)
+    }
+
+    /// Returns a response depositing `lp_tokens`, which the contract holds,
+    /// in the cw-vault-standard `vault` for a `VaultDeposit` post provide
+    /// action. The vault tokens are sent to `recipient` in the reply, which
+    /// sets `response` with the vault tokens as the data. To be called by the
+    /// implementation instead of sending the LP tokens to the recipient.
+    fn vault_deposit(
+        deps: DepsMut,
+        env: &Env,
+        vault: &str,
+        lp_tokens: &Asset,
+        min_vault_tokens: Uint128,
+        recipient: Addr,
+        response: ProvideLiquidityResponse,
+    ) -> Result<Response, Self::Error> {
+        let vault: VaultContract = VaultContractUnchecked::new(vault).check(deps.as_ref())?;
+        let base_token = AssetInfo::from_str(deps.api, &vault.base_token);
+        if base_token != lp_tokens.info {
+            return Err(LiquidityHelperError::VaultBaseTokenMismatch {
+                vault: vault.addr,
+                base_token,
+                lp_token: lp_tokens.info.clone(),
+            }
+            .into());
+        }
+
+        let vault_token = AssetInfo::from_str(deps.api, &vault.vault_token);
+        let balance_before = vault_token.query_balance(&deps.querier, &env.contract.address)?;
+        VAULT_DEPOSIT_STATE.save(
+            deps.storage,
+            &VaultDepositState {
+                vault_token,
+                balance_before,
+                min_vault_tokens,
+                recipient,
+                response,
+            },
+        )?;
+
+        // Cw20 LP tokens are transferred by the vault, so it needs an allowance
+        let mut msgs = vec![];
+        let deposit_msg = match &lp_tokens.info {
+            AssetInfo::Native(_) => vault.deposit(lp_tokens.amount, None)?,
+            AssetInfo::Cw20(addr) => {
+                msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: addr.to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: vault.addr.to_string(),
+                        amount: lp_tokens.amount,
+                        expires: None,
+                    })?,
+                    funds: vec![],
+                }));
+                vault.deposit_cw20(lp_tokens.amount, None)?
+            }
+        };
+
+        let event = Self::event("vault_deposit")
+            .add_attribute("vault", &vault.addr)
+            .add_attribute("lp_tokens", lp_tokens.to_string());
+
+        Ok(Response::new()
+            .add_messages(msgs)
+            .add_submessage(SubMsg::reply_on_success(
+                deposit_msg,
+                VAULT_DEPOSIT_REPLY_ID,
+            ))
+            .add_event(event))
+    }
+
+    /// Sends assets held by the contract to the recipient. Only callable by
+    /// the owner.
+    fn execute_sweep(
+        deps: DepsMut,
+        info: MessageInfo,
+        assets: AssetList,
+        recipient: Addr,
+    ) -> Result<Response, Self::Error> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let mut event = Self::event("execute_sweep").add_attribute("recipient", &recipient);
+        // Attribute values must not be empty
+        if assets.len() > 0 {
+            event = event.add_attribute("assets", assets.to_string());
+        }
+
+        Ok(Response::new()
+            .add_messages(assets.transfer_msgs(&recipient)?)
+            .add_event(event))
+    }
+
+    /// Pauses or unpauses providing liquidity to a pool, or to all pools if
+    /// `pool` is `None`. The guardian can only pause, so that only the owner
+    /// can resume providing liquidity.
+    fn execute_update_pause(
+        deps: DepsMut,
+        info: MessageInfo,
+        pool: Option<PoolIdentifier>,
+        paused: bool,
+    ) -> Result<Response, Self::Error> {
+        let is_owner = cw_ownable::is_owner(deps.storage, &info.sender)?;
+        let is_guardian = GUARDIAN.may_load(deps.storage)?.as_ref() == Some(&info.sender);
+        if !(is_owner || paused && is_guardian) {
+            return Err(LiquidityHelperError::Unauthorized {}.into());
+        }
+
+        let target = match pool {
+            None => {
+                PAUSED.save(deps.storage, &paused)?;
+                "all".to_string()
+            }
+            Some(identifier) => {
+                let identifier = Self::validate_pool_identifier(deps.api, identifier)?;
+                let key = identifier.key();
+                if paused {
+                    PAUSED_POOLS.save(deps.storage, key.clone(), &identifier)?;
+                } else {
+                    PAUSED_POOLS.remove(deps.storage, key.clone());
+                }
+                key
+            }
+        };
+
+        let event = Self::event("execute_update_pause")
+            .add_attribute("pool", target)
+            .add_attribute("paused", paused.to_string());
+
+        Ok(Response::new().add_event(event))
+    }
+
+    /// Sets or removes the guardian. Only callable by the owner.
+    fn execute_update_guardian(
+        deps: DepsMut,
+        info: MessageInfo,
+        guardian: Option<String>,
+    ) -> Result<Response, Self::Error> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let mut event = Self::event("execute_update_guardian");
+        match guardian {
+            Some(guardian) => {
+                let guardian = deps.api.addr_validate(&guardian)?;
+                GUARDIAN.save(deps.storage, &guardian)?;
+                event = event.add_attribute("guardian", guardian);
+            }
+            None => GUARDIAN.remove(deps.storage),
+        }
+
+        Ok(Response::new().add_event(event))
+    }
+
+    /// Sets or removes the router. Only callable by the owner.
+    fn execute_update_router(
+        deps: DepsMut,
+        info: MessageInfo,
+        router: Option<String>,
+    ) -> Result<Response, Self::Error> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let mut event = Self::event("execute_update_router");
+        match router {
+            Some(router) => {
+                let router = deps.api.addr_validate(&router)?;
+                ROUTER.save(deps.storage, &router)?;
+                event = event.add_attribute("router", router);
+            }
+            None => ROUTER.remove(deps.storage),
+        }
+
+        Ok(Response::new().add_event(event))
+    }
+
+    /// Returns an error if providing liquidity is paused for all pools or for
+    /// the given pool
+    fn assert_not_paused(deps: Deps, pool: &PoolIdentifier) -> Result<(), Self::Error> {
+        if PAUSED.may_load(deps.storage)?.unwrap_or_default()
+            || PAUSED_POOLS.has(deps.storage, pool.key())
+        {
+            return Err(LiquidityHelperError::Paused {}.into());
+        }
+        Ok(())
+    }
+
+    fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, Self::Error> {
+        match msg.id {
+            WITHDRAW_LIQUIDITY_REPLY_ID => Self::reply_withdraw_liquidity(deps, env),
+            SWAP_TO_TARGET_ASSET_REPLY_ID => Self::reply_swap_to_target_asset(deps, env),
+            ZAP_REPLY_ID => Self::reply_zap(deps, env),
+            MIGRATE_LIQUIDITY_REPLY_ID => Self::reply_migrate_liquidity(deps, env),
+            VAULT_DEPOSIT_REPLY_ID => Self::reply_vault_deposit(deps, env),
+            _ => Self::reply_balancing_provide_liquidity(deps, env, msg),
+        }
+    }
+
+    /// Reply handler for the last routing message of a zap. Provides the
+    /// assets that were received or routed into pool assets, which the
+    /// contract now holds.
+    fn reply_zap(deps: DepsMut, env: Env) -> Result<Response, Self::Error> {
+        let state = ZAP_STATE.load(deps.storage)?;
+        ZAP_STATE.remove(deps.storage);
+
+        let assets = query_balance_increase(
+            deps.as_ref(),
+            &env,
+            &state.pool_assets,
+            &state.balances_before,
+        )?;
+        let info = MessageInfo {
+            sender: env.contract.address.clone(),
+            funds: vec![],
+        };
+        let args = BalancingProvideLiquidityArgs {
+            assets,
+            min_out: state.min_out,
+            pool: state.pool,
+            recipient: state.recipient,
+            max_swap_slippage: state.max_swap_slippage,
+            initial_price: state.initial_price,
+            max_price_deviation: state.max_price_deviation,
+            post_provide: state.post_provide,
+            assets_held: true,
+        };
+        Self::balancing_provide_liquidity(deps, env, info, args)
+    }
+
+    /// Reply handler for the withdraw liquidity message of a
+    /// `MigrateLiquidity`. Asserts that at least `min_withdrawn` was withdrawn
+    /// and provides the withdrawn assets, which the contract now holds.
+    fn reply_migrate_liquidity(deps: DepsMut, env: Env) -> Result<Response, Self::Error> {
+        let state = MIGRATE_LIQUIDITY_STATE.load(deps.storage)?;
+        MIGRATE_LIQUIDITY_STATE.remove(deps.storage);
+
+        let withdrawn = query_balance_increase(
+            deps.as_ref(),
+            &env,
+            &state.withdrawn_assets,
+            &state.balances_before,
+        )?;
+        for min in state.min_withdrawn.iter() {
+            let received = withdrawn
+                .find(&min.info)
+                .map_or_else(Uint128::zero, |x| x.amount);
+            if received < min.amount {
+                return Err(LiquidityHelperError::InsufficientWithdrawnAsset {
+                    asset: min.info.clone(),
+                    expected: min.amount,
+                    received,
+                }
+                .into());
+            }
+        }
+
+        let event = Self::event("reply_migrate_liquidity")
+            .add_attribute("withdrawn", withdrawn.to_string());
+
+        let info = MessageInfo {
+            sender: env.contract.address.clone(),
+            funds: vec![],
+        };
+        let args = BalancingProvideLiquidityArgs {
+            assets: withdrawn,
+            min_out: state.min_out,
+            pool: state.to_pool,
+            recipient: state.recipient,
+            max_swap_slippage: state.max_swap_slippage,
+            initial_price: None,
+            max_price_deviation: state.max_price_deviation,
+            post_provide: None,
+            assets_held: true,
+        };
+        Ok(Self::route_and_provide_liquidity(deps, env, info, args)?.add_event(event))
+    }
+
+    /// Reply handler for the withdraw liquidity message of a
+    /// `BalancingWithdrawLiquidity`. Swaps the withdrawn assets other than the
+    /// target asset into the target asset.
+    fn reply_withdraw_liquidity(deps: DepsMut, env: Env) -> Result<Response, Self::Error> {
+        let state: WithdrawLiquidityState<Self::Pool> =
+            withdraw_liquidity_state().load(deps.storage)?;
+
+        let pool_assets: Vec<AssetInfo> = state
+            .pool
+            .pool_assets(deps.as_ref())?
+            .into_iter()
+            .filter(|x| x != &state.target_asset)
+            .collect();
+        let swapped =
+            query_balance_increase(deps.as_ref(), &env, &pool_assets, &state.balances_before)?;
+
+        // If nothing needs to be swapped we can return the target asset right away
+        if swapped.len() == 0 {
+            return Self::reply_swap_to_target_asset(deps, env);
+        }
+
+        let mut responses = vec![];
+        for withdrawn in swapped.iter() {
+            responses.push(state.pool.swap(
+                deps.as_ref(),
+                &env,
+                withdrawn.clone(),
+                state.target_asset.clone(),
+                Uint128::one(),
+            )?);
+        }
+
+        let event = Self::event("reply_withdraw_liquidity")
+            .add_attribute("swapped", swapped.to_string())
+            .add_attribute("target_asset", state.target_asset.to_string());
+
+        Ok(
+            reply_on_last_message(merge_responses(responses), SWAP_TO_TARGET_ASSET_REPLY_ID)?
+                .add_event(event),
+        )
+    }
+
+    /// Reply handler for the swaps of a `BalancingWithdrawLiquidity`. Asserts
+    /// that at least `min_out` of the target asset was received and sends it
+    /// to the recipient.
+    fn reply_swap_to_target_asset(deps: DepsMut, env: Env) -> Result<Response, Self::Error> {
+        let WithdrawLiquidityState {
+            target_asset,
+            balances_before,
+            min_out,
+            recipient,
+            ..
+        } = withdraw_liquidity_state::<Self::Pool>().load(deps.storage)?;
+        withdraw_liquidity_state::<Self::Pool>().remove(deps.storage);
+
+        let balance = target_asset.query_balance(&deps.querier, env.contract.address)?;
+        let balance_before = balances_before
+            .find(&target_asset)
+            .map_or_else(Uint128::zero, |x| x.amount);
+        let return_amount = balance.checked_sub(balance_before)?;
+
+        // Assert return_amount is greater than min_out
+        if return_amount < min_out {
+            return Err(LiquidityHelperError::InsufficientTargetAsset {
+                target_asset,
+                expected: min_out,
+                received: return_amount,
+            }
+            .into());
+        }
+
+        let return_asset = Asset::new(target_asset, return_amount);
+        let msg = return_asset.transfer_msg(&recipient)?;
+
+        let event = Self::event("reply_swap_to_target_asset")
+            .add_attribute("return_asset", return_asset.to_string())
+            .add_attribute("recipient", recipient);
+
+        Ok(Response::new().add_message(msg).add_event(event))
+    }
+
+    /// Reply handler for the deposit into the vault of a `VaultDeposit` post
+    /// provide action. Asserts that at least `min_vault_tokens` were received
+    /// and sends them to the recipient.
+    fn reply_vault_deposit(deps: DepsMut, env: Env) -> Result<Response, Self::Error> {
+        let VaultDepositState {
+            vault_token,
+            balance_before,
+            min_vault_tokens,
+            recipient,
+            mut response,
+        } = VAULT_DEPOSIT_STATE.load(deps.storage)?;
+        VAULT_DEPOSIT_STATE.remove(deps.storage);
+
+        let balance = vault_token.query_balance(&deps.querier, &env.contract.address)?;
+        let received = balance.checked_sub(balance_before)?;
+        if received < min_vault_tokens {
+            return Err(LiquidityHelperError::InsufficientVaultTokens {
+                expected: min_vault_tokens,
+                received,
+            }
+            .into());
+        }
+
+        let vault_tokens = Asset::new(vault_token, received);
+        let msg = vault_tokens.transfer_msg(&recipient)?;
+
+        let event = Self::event("reply_vault_deposit")
+            .add_attribute("vault_tokens", vault_tokens.to_string())
+            .add_attribute("recipient", recipient);
+
+        response.vault_tokens = Some(vault_tokens);
+
+        Ok(Response::new()
+            .add_message(msg)
+            .add_event(event)
+            .set_data(to_json_binary(&response)?))
+    }
+
+    fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+        match msg {
+            QueryMsg::Ownership {} => to_json_binary(&cw_ownable::get_ownership(deps.storage)?),
+            QueryMsg::PauseInfo {} => to_json_binary(&Self::query_pause_info(deps)?),
+            QueryMsg::Router {} => to_json_binary(&ROUTER.may_load(deps.storage)?),
+            QueryMsg::SimulateBalancingProvideLiquidity {
+                assets,
+                pool,
+                initial_price,
+            } => {
+                let assets = assets.check(deps.api)?;
+                let pool = Self::parse_pool(deps, pool).map_err(Into::into)?;
+                to_json_binary(
+                    &Self::simulate_balancing_provide_liquidity(
+                        deps,
+                        env,
+                        assets,
+                        pool,
+                        initial_price,
+                    )
+                    .map_err(Into::into)?,
+                )
+            }
+        }
+    }
+
+    fn query_pause_info(deps: Deps) -> StdResult<PauseInfoResponse> {
+        let pools = PAUSED_POOLS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|x| x.map(|(_, identifier)| identifier))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(PauseInfoResponse {
+            guardian: GUARDIAN.may_load(deps.storage)?,
+            global: PAUSED.may_load(deps.storage)?.unwrap_or_default(),
+            pools,
+        })
+    }
+
+    /// Checks that the contract is migrated from an older version of the same
+    /// contract, stores the new version and sets the owner if one is given and
+    /// the contract has none. To be called from the `migrate` entry point.
+    fn migrate(deps: DepsMut, owner: Option<String>) -> Result<Response, Self::Error> {
+        let contract_name = format!("crates.io:{}", Self::CONTRACT_NAME);
+
+        // Read current cw2 version info
+        let cw2_data = cw2::get_contract_version(deps.storage)?;
+
+        // Only allow migrating to the same contract name
+        if cw2_data.contract != contract_name {
+            return Err(LiquidityHelperError::InvalidContractName {
+                expected: contract_name,
+                received: cw2_data.contract,
+            }
+            .into());
+        }
+
+        // Only allow migrating if the new version is greater than the current one
+        let old_version =
+            semver::Version::parse(&cw2_data.version).map_err(LiquidityHelperError::from)?;
+        let new_version =
+            semver::Version::parse(Self::CONTRACT_VERSION).map_err(LiquidityHelperError::from)?;
+        if new_version <= old_version {
+            return Err(LiquidityHelperError::InvalidContractVersion {
+                old_version,
+                new_version,
+            }
+            .into());
+        }
+
+        // Only contracts migrated from a version without ownership may set an
+        // owner here. Others transfer ownership with `UpdateOwnership`. The
+        // ownership isn't stored at all for contracts without it.
+        if let Some(owner) = owner {
+            if cw_ownable::get_ownership(deps.storage).map_or(false, |x| x.owner.is_some()) {
+                return Err(LiquidityHelperError::OwnerAlreadySet {}.into());
+            }
+            cw_ownable::initialize_owner(deps.storage, deps.api, Some(&owner))?;
+        }
+
+        // Store new cw2 version info
+        set_contract_version(deps.storage, contract_name, Self::CONTRACT_VERSION)?;
+
+        Ok(Response::default())
+    }
+}
+
+/// Validates the recipient, or uses the sender if none is given
+pub fn resolve_recipient(
+    api: &dyn Api,
+    info: &MessageInfo,
+    recipient: Option<String>,
+) -> StdResult<Addr> {
+    recipient.map_or(Ok(info.sender.clone()), |x| api.addr_validate(&x))
+}
+
+/// Makes the last message of the response reply to this contract with the
+/// given id on success.
+pub fn reply_on_last_message(mut res: Response, id: u64) -> StdResult<Response> {
+    let msg = res
+        .messages
+        .last_mut()
+        .ok_or_else(|| StdError::generic_err("No message to reply on"))?;
+    msg.id = id;
+    msg.reply_on = ReplyOn::Success;
+    Ok(res)
+}
+
+/// Queries the balances of the given assets held by this contract
+pub fn query_balances(deps: Deps, env: &Env, asset_infos: &[AssetInfo]) -> StdResult<AssetList> {
+    Ok(asset_infos
+        .iter()
+        .map(|x| {
+            Ok(Asset::new(
+                x.clone(),
+                x.query_balance(&deps.querier, &env.contract.address)?,
+            ))
+        })
+        .collect::<StdResult<Vec<_>>>()?
+        .into())
+}
+
+/// Returns how much the contract's balances of `asset_infos` have increased
+/// since `balances_before` was queried. Assets missing from `balances_before`
+/// had a zero balance, as `AssetList` leaves out zero amounts.
+pub fn query_balance_increase(
+    deps: Deps,
+    env: &Env,
+    asset_infos: &[AssetInfo],
+    balances_before: &AssetList,
+) -> StdResult<AssetList> {
+    let mut increase = AssetList::new();
+    for asset_info in asset_infos {
+        let balance = asset_info.query_balance(&deps.querier, &env.contract.address)?;
+        let balance_before = balances_before
+            .find(asset_info)
+            .map_or_else(Uint128::zero, |x| x.amount);
+        increase.add(&Asset::new(
+            asset_info.clone(),
+            balance.saturating_sub(balance_before),
+        ))?;
+    }
+
+    Ok(increase)
+}
@@ -0,0 +1,102 @@
+use apollo_cw_asset::{Asset, AssetInfo};
+use cosmwasm_std::{Addr, StdError, Uint128};
+use thiserror::Error;
+
+use crate::msg::{PoolIdentifier, PostProvideAction};
+
+/// Errors returned by the handlers that `LiquidityHelperContract` provides.
+/// The error type of each contract converts from it.
+#[derive(Error, Debug)]
+pub enum LiquidityHelperError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Semver(#[from] semver::Error),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("The contract already has an owner, which can only be changed with UpdateOwnership")]
+    OwnerAlreadySet {},
+
+    #[error("Providing liquidity is paused")]
+    Paused {},
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("Pool identifier {identifier:?} is not supported by this contract")]
+    UnsupportedPoolIdentifier { identifier: PoolIdentifier },
+
+    #[error("Post provide action {action:?} is not supported by this contract")]
+    UnsupportedPostProvideAction { action: PostProvideAction },
+
+    #[error("The router has no route from {asset} into any of the pool assets")]
+    NoRoute { asset: AssetInfo },
+
+    #[error("Target asset {target_asset} is not in the pool")]
+    TargetAssetNotInPool { target_asset: AssetInfo },
+
+    #[error("Asset {asset} of min_withdrawn is not in the pool")]
+    MinWithdrawnAssetNotInPool { asset: AssetInfo },
+
+    #[error(
+        "Insufficient {target_asset} received. Expected a minumum of {expected} but got {received}"
+    )]
+    InsufficientTargetAsset {
+        target_asset: AssetInfo,
+        expected: Uint128,
+        received: Uint128,
+    },
+
+    #[error("Insufficient {asset} withdrawn. Expected a minimum of {expected} but got {received}")]
+    InsufficientWithdrawnAsset {
+        asset: AssetInfo,
+        expected: Uint128,
+        received: Uint128,
+    },
+
+    #[error("The base token of vault {vault} is {base_token}, not the LP token {lp_token}")]
+    VaultBaseTokenMismatch {
+        vault: Addr,
+        base_token: AssetInfo,
+        lp_token: AssetInfo,
+    },
+
+    #[error(
+        "Insufficient vault tokens received. Expected a minimum of {expected} but got {received}"
+    )]
+    InsufficientVaultTokens {
+        expected: Uint128,
+        received: Uint128,
+    },
+
+    #[error("Received funds don't match input assets. Expected {expected:?} but got {received:?}")]
+    InputTokenMismatch {
+        expected: Vec<Asset>,
+        received: Vec<Asset>,
+    },
+
+    #[error("Can only migrate to a codeID with the correct name. Expected: {expected}, received: {received}")]
+    InvalidContractName {
+        /// The expected contract name
+        expected: String,
+        /// The actual contract name
+        received: String,
+    },
+
+    #[error("Can only migrate to a codeID with a newer version. Old version: {old_version}, new version: {new_version}")]
+    InvalidContractVersion {
+        /// The current contract version
+        old_version: semver::Version,
+        /// The version that the user is trying to migrate to
+        new_version: semver::Version,
+    },
+}
+
+impl From<LiquidityHelperError> for StdError {
+    fn from(e: LiquidityHelperError) -> Self {
+        StdError::generic_err(e.to_string())
+    }
+}
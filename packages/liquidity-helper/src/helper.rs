@@ -1,14 +1,18 @@
-use apollo_cw_asset::AssetList;
+use apollo_cw_asset::{Asset, AssetInfo, AssetList};
 use apollo_utils::assets::separate_natives_and_cw20s;
 use cosmwasm_schema::cw_serde;
-use cosmwasm_schema::serde::Serialize;
 use cw20::Cw20ExecuteMsg;
+use cw_utils::parse_execute_response_data;
 
 use cosmwasm_std::{
-    to_json_binary, Addr, Api, Binary, Coin, CosmosMsg, Empty, StdResult, Uint128, WasmMsg,
+    from_json, to_json_binary, Addr, Api, Coin, CosmosMsg, Decimal, QuerierWrapper, Reply,
+    StdError, StdResult, Uint128, WasmMsg,
 };
 
-use crate::msg::ExecuteMsg;
+use crate::msg::{
+    ExecuteMsg, PoolInput, PostProvideAction, ProvideLiquidityResponse, QueryMsg,
+    SimulateBalancingProvideLiquidityResponse,
+};
 
 /// LiquidityHelper is a wrapper around Addr that provides a lot of helpers
 /// for working with this contract. It can be imported by other contracts
@@ -28,11 +32,7 @@ impl LiquidityHelper {
         self.0.clone()
     }
 
-    pub fn call<C: Serialize, T: Into<ExecuteMsg<C>>>(
-        &self,
-        msg: T,
-        funds: Vec<Coin>,
-    ) -> StdResult<CosmosMsg> {
+    pub fn call<T: Into<ExecuteMsg>>(&self, msg: T, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
         let msg = to_json_binary(&msg.into())?;
         Ok(WasmMsg::Execute {
             contract_addr: self.addr().into(),
@@ -42,12 +42,17 @@ impl LiquidityHelper {
         .into())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn balancing_provide_liquidity(
         &self,
         assets: AssetList,
         min_out: Uint128,
-        pool: Binary,
+        pool: impl Into<PoolInput>,
         recipient: Option<String>,
+        max_swap_slippage: Option<Decimal>,
+        initial_price: Option<Decimal>,
+        max_price_deviation: Option<Decimal>,
+        post_provide: Option<PostProvideAction>,
     ) -> StdResult<Vec<CosmosMsg>> {
         let (funds, cw20s) = separate_natives_and_cw20s(&assets);
 
@@ -68,17 +73,122 @@ impl LiquidityHelper {
             .collect::<StdResult<Vec<_>>>()?;
 
         msgs.push(self.call(
-            ExecuteMsg::<Empty>::BalancingProvideLiquidity {
+            ExecuteMsg::BalancingProvideLiquidity {
                 assets: assets.into(),
                 min_out,
-                pool,
+                pool: pool.into(),
                 recipient,
+                max_swap_slippage,
+                initial_price,
+                max_price_deviation,
+                post_provide,
             },
             funds,
         )?);
 
         Ok(msgs)
     }
+
+    /// Parses the `ProvideLiquidityResponse` from the reply to a
+    /// `BalancingProvideLiquidity` message that was sent as a submessage.
+    pub fn parse_provide_liquidity_response(reply: Reply) -> StdResult<ProvideLiquidityResponse> {
+        let data = reply
+            .result
+            .into_result()
+            .map_err(StdError::generic_err)?
+            .data
+            .ok_or_else(|| StdError::generic_err("No data in reply"))?;
+        let execute_data = parse_execute_response_data(&data)
+            .map_err(|e| StdError::generic_err(e.to_string()))?
+            .data
+            .ok_or_else(|| StdError::generic_err("No data in execute response"))?;
+        from_json(execute_data)
+    }
+
+    pub fn balancing_withdraw_liquidity(
+        &self,
+        lp_token: Asset,
+        pool: impl Into<PoolInput>,
+        target_asset: AssetInfo,
+        min_out: Uint128,
+        recipient: Option<String>,
+    ) -> StdResult<Vec<CosmosMsg>> {
+        let msg = ExecuteMsg::BalancingWithdrawLiquidity {
+            lp_token: lp_token.clone().into(),
+            pool: pool.into(),
+            target_asset: target_asset.into(),
+            min_out,
+            recipient,
+        };
+        self.call_with_lp_token(msg, lp_token)
+    }
+
+    /// Returns the messages to move `lp_token` of `from_pool` into `to_pool`
+    /// with a `MigrateLiquidity`
+    #[allow(clippy::too_many_arguments)]
+    pub fn migrate_liquidity(
+        &self,
+        lp_token: Asset,
+        from_pool: impl Into<PoolInput>,
+        to_pool: impl Into<PoolInput>,
+        min_out: Uint128,
+        recipient: Option<String>,
+        min_withdrawn: Option<AssetList>,
+        max_swap_slippage: Option<Decimal>,
+        max_price_deviation: Option<Decimal>,
+    ) -> StdResult<Vec<CosmosMsg>> {
+        let msg = ExecuteMsg::MigrateLiquidity {
+            from_pool: from_pool.into(),
+            to_pool: to_pool.into(),
+            lp_amount: lp_token.amount,
+            min_out,
+            recipient,
+            min_withdrawn: min_withdrawn.map(Into::into),
+            max_swap_slippage,
+            max_price_deviation,
+        };
+        self.call_with_lp_token(msg, lp_token)
+    }
+
+    /// Calls the contract with `lp_token` as funds if it is native, or after
+    /// increasing the allowance of the contract if it is a Cw20
+    fn call_with_lp_token(&self, msg: ExecuteMsg, lp_token: Asset) -> StdResult<Vec<CosmosMsg>> {
+        match &lp_token.info {
+            AssetInfo::Native(_) => Ok(vec![self.call(msg, vec![lp_token.try_into()?])?]),
+            AssetInfo::Cw20(addr) => {
+                // Increase allowance for the cw20 LP token
+                let allowance_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: addr.to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: self.addr().into(),
+                        amount: lp_token.amount,
+                        expires: None,
+                    })?,
+                    funds: vec![],
+                });
+                Ok(vec![allowance_msg, self.call(msg, vec![])?])
+            }
+        }
+    }
+
+    /// Queries the contract for what a `BalancingProvideLiquidity` with the
+    /// given assets would currently do.
+    pub fn simulate_balancing_provide_liquidity(
+        &self,
+        querier: &QuerierWrapper,
+        assets: AssetList,
+        pool: impl Into<PoolInput>,
+        initial_price: Option<Decimal>,
+    ) -> StdResult<SimulateBalancingProvideLiquidityResponse> {
+        querier.query_wasm_smart(
+            self.addr(),
+            &QueryMsg::SimulateBalancingProvideLiquidity {
+                assets: assets.into(),
+                pool: pool.into(),
+                initial_price,
+            },
+        )
+    }
 }
 
 impl LiquidityHelperUnchecked {
@@ -1,4 +1,9 @@
+pub mod contract;
+pub mod error;
 pub mod helper;
 pub mod msg;
+pub mod state;
 
+pub use contract::LiquidityHelperContract;
+pub use error::LiquidityHelperError;
 pub use helper::{LiquidityHelper, LiquidityHelperBase, LiquidityHelperUnchecked};
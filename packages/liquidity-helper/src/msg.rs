@@ -1,14 +1,250 @@
-use apollo_cw_asset::AssetListUnchecked;
-use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Binary, Uint128};
+use apollo_cw_asset::{
+    Asset, AssetInfo, AssetInfoUnchecked, AssetList, AssetListUnchecked, AssetUnchecked,
+};
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Addr, Binary, Decimal, Uint128};
+use cw_ownable::{cw_ownable_execute, cw_ownable_query};
 
+#[cw_ownable_execute]
 #[cw_serde]
-pub enum ExecuteMsg<C> {
+pub enum ExecuteMsg {
+    /// Provides liquidity with the given assets, swapping some of them first
+    /// so that they are in the ratio of the pool. If a router is set with
+    /// `UpdateRouter`, assets that are not in the pool are first routed into
+    /// pool assets, which are then balanced and provided together with the
+    /// other assets. `max_swap_slippage` does not apply to the routes, only
+    /// `min_out` guards their result.
     BalancingProvideLiquidity {
         assets: AssetListUnchecked,
         min_out: Uint128,
-        pool: Binary,
+        pool: PoolInput,
         recipient: Option<String>,
+        /// The maximum slippage allowed on the swaps done to balance the
+        /// assets before providing liquidity. Each swap must return at least
+        /// its simulated return amount minus this. The swaps are not protected
+        /// if this is `None`, in which case only `min_out` guards the result.
+        max_swap_slippage: Option<Decimal>,
+        /// The price of the first pool asset in units of the second, used only
+        /// if the pool has no liquidity yet. The first provide to an empty
+        /// pool is done without a balancing swap, with the assets trimmed to
+        /// this price and the rest returned to the recipient. If `None` the
+        /// assets are provided in the ratio they are given. Ignored for pools
+        /// that already have liquidity.
+        initial_price: Option<Decimal>,
+        /// The maximum relative deviation of the spot price of the pool from
+        /// its time weighted average price. Providing fails if it is exceeded,
+        /// which guards the balancing swap against a pool manipulated in the
+        /// same block. Ignored for pools without liquidity. DEXes without a
+        /// TWAP oracle, like Astroport, need a price observation recorded
+        /// with `RecordPriceObservation` between 10 minutes and an hour
+        /// before, otherwise providing fails with `NoPriceObservation`.
+        /// Successful provides with this set record another observation.
+        max_price_deviation: Option<Decimal>,
+        /// What to do with the LP tokens on behalf of the recipient instead of
+        /// sending them to it. Fails with `UnsupportedPostProvideAction` if
+        /// the DEX doesn't support it.
+        post_provide: Option<PostProvideAction>,
     },
-    Callback(C),
+    /// Withdraws liquidity from the pool and swaps all withdrawn assets other
+    /// than `target_asset` into `target_asset`, so that the position is exited
+    /// into a single asset.
+    BalancingWithdrawLiquidity {
+        lp_token: AssetUnchecked,
+        pool: PoolInput,
+        target_asset: AssetInfoUnchecked,
+        min_out: Uint128,
+        recipient: Option<String>,
+    },
+    /// Moves liquidity from `from_pool` to `to_pool` in one transaction.
+    /// Withdraws `lp_amount` of the LP token of `from_pool`, which must be
+    /// sent with the message or approved, and provides the withdrawn assets
+    /// to `to_pool` like `BalancingProvideLiquidity`, including routing assets
+    /// not in `to_pool` if a router is set. The LP tokens received are sent to
+    /// `recipient`.
+    MigrateLiquidity {
+        from_pool: PoolInput,
+        to_pool: PoolInput,
+        lp_amount: Uint128,
+        /// The minimum amount of LP tokens of `to_pool` to receive
+        min_out: Uint128,
+        recipient: Option<String>,
+        /// The minimum amounts of the assets to withdraw from `from_pool`.
+        /// Fails with `MinWithdrawnAssetNotInPool` if it contains an asset
+        /// that is not in `from_pool`.
+        min_withdrawn: Option<AssetListUnchecked>,
+        /// Same as `max_swap_slippage` of `BalancingProvideLiquidity`
+        max_swap_slippage: Option<Decimal>,
+        /// Same as `max_price_deviation` of `BalancingProvideLiquidity`
+        max_price_deviation: Option<Decimal>,
+    },
+    /// Records a price observation of `pool`, which the time weighted average
+    /// price that `max_price_deviation` is checked against is calculated from
+    /// on DEXes without a TWAP oracle. Callable by anyone. Does nothing on
+    /// Osmosis, which has one.
+    RecordPriceObservation { pool: PoolInput },
+    /// Sends `assets` held by the contract, such as tokens sent to it by
+    /// mistake, to `recipient`. Defaults to the sender. Only callable by the
+    /// owner.
+    Sweep {
+        assets: AssetListUnchecked,
+        recipient: Option<String>,
+    },
+    /// Pauses or unpauses providing liquidity to `pool`, or to all pools if
+    /// `pool` is `None`. Withdrawing is never paused. The owner and the
+    /// guardian can pause, but only the owner can unpause.
+    UpdatePause {
+        pool: Option<PoolIdentifier>,
+        paused: bool,
+    },
+    /// Sets the guardian, who can pause providing liquidity, or removes it if
+    /// `guardian` is `None`. Only callable by the owner.
+    UpdateGuardian { guardian: Option<String> },
+    /// Sets the cw-dex-router that `BalancingProvideLiquidity` routes assets
+    /// not in the pool through, or removes it if `router` is `None`. Only
+    /// callable by the owner.
+    UpdateRouter { router: Option<String> },
+}
+
+/// An action done with the LP tokens on behalf of the recipient after
+/// providing liquidity
+#[cw_serde]
+pub enum PostProvideAction {
+    /// Deposits the LP tokens in the Astroport incentives contract for the
+    /// recipient
+    Stake {},
+    /// Locks the LP tokens of an Osmosis pool for `duration` seconds. Locks
+    /// are owned by the account that creates them, so the lock is created
+    /// with an authz `MsgExec` for the recipient, which must have granted the
+    /// contract a `GenericAuthorization` for `/osmosis.lockup.MsgLockTokens`.
+    LockTokens { duration: u64 },
+    /// Locks the LP tokens of an Osmosis pool and superfluid delegates them to
+    /// `validator`. Like `LockTokens` it is done for the recipient with an
+    /// authz `MsgExec`, which needs a `GenericAuthorization` for
+    /// `/osmosis.superfluid.MsgLockAndSuperfluidDelegate`.
+    SuperfluidDelegate { validator: String },
+    /// Deposits the LP tokens in the cw-vault-standard `vault`, whose base
+    /// token must be the LP token, and sends the vault tokens to the
+    /// recipient. Fails with `InsufficientVaultTokens` if less than
+    /// `min_vault_tokens` are received.
+    VaultDeposit {
+        vault: String,
+        min_vault_tokens: Uint128,
+    },
+}
+
+/// The pool to use. Either the DEX specific pool struct (`AstroportPool`,
+/// `OsmosisPool`, ...) serialised as `Binary`, or a `PoolIdentifier` that the
+/// contract resolves into the pool struct by querying the chain.
+#[cw_serde]
+#[serde(untagged)]
+pub enum PoolInput {
+    Serialized(Binary),
+    Identifier(PoolIdentifier),
+}
+
+/// Identifies a pool by its on-chain address or ID
+#[cw_serde]
+pub enum PoolIdentifier {
+    /// The address of an Astroport pair contract
+    AstroportPair { address: String },
+    /// The ID of an Osmosis pool. Liquidity is provided in the full range of
+    /// concentrated liquidity pools.
+    OsmosisPool { pool_id: u64 },
+}
+
+impl PoolIdentifier {
+    /// Returns the address or ID of the pool, which is unique across DEXes
+    pub fn key(&self) -> String {
+        match self {
+            PoolIdentifier::AstroportPair { address } => address.clone(),
+            PoolIdentifier::OsmosisPool { pool_id } => pool_id.to_string(),
+        }
+    }
+}
+
+impl From<Binary> for PoolInput {
+    fn from(pool: Binary) -> Self {
+        PoolInput::Serialized(pool)
+    }
+}
+
+impl From<PoolIdentifier> for PoolInput {
+    fn from(id: PoolIdentifier) -> Self {
+        PoolInput::Identifier(id)
+    }
+}
+
+#[cw_ownable_query]
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    /// Simulates a `BalancingProvideLiquidity` with the given assets against
+    /// the current state of the pool, without moving any funds. Not supported
+    /// for Osmosis concentrated liquidity pools, whose positions are not LP
+    /// tokens.
+    #[returns(SimulateBalancingProvideLiquidityResponse)]
+    SimulateBalancingProvideLiquidity {
+        assets: AssetListUnchecked,
+        pool: PoolInput,
+        /// Same as `initial_price` of `ExecuteMsg::BalancingProvideLiquidity`
+        initial_price: Option<Decimal>,
+    },
+    /// Returns the guardian and what providing liquidity is paused for
+    #[returns(PauseInfoResponse)]
+    PauseInfo {},
+    /// Returns the cw-dex-router that assets not in the pool are routed
+    /// through, if one is set
+    #[returns(Option<Addr>)]
+    Router {},
+}
+
+/// A swap done by the liquidity helper to balance the provided assets.
+#[cw_serde]
+pub struct BalancingSwap {
+    /// The asset sent into the pool
+    pub offer_asset: Asset,
+    /// The asset expected to be received from the pool
+    pub return_asset: Asset,
+}
+
+#[cw_serde]
+pub struct SimulateBalancingProvideLiquidityResponse {
+    /// The swap that would be done before providing liquidity, if any
+    pub swap: Option<BalancingSwap>,
+    /// The assets that would be used to provide liquidity
+    pub provide_assets: AssetList,
+    /// The expected amount of LP tokens to be received
+    pub lp_tokens: Asset,
+    /// Any assets that would not be used to provide liquidity
+    pub leftover: AssetList,
+}
+
+/// Returned as `Response::data` from `BalancingProvideLiquidity`. Use
+/// `LiquidityHelper::parse_provide_liquidity_response` to read it in a reply.
+#[cw_serde]
+pub struct ProvideLiquidityResponse {
+    /// The LP token of the pool
+    pub lp_token: AssetInfo,
+    /// The amount of LP tokens sent to the recipient
+    pub lp_amount: Uint128,
+    /// The swap done before providing liquidity, if any
+    pub swapped: Option<BalancingSwap>,
+    /// Any assets that were returned instead of being used to provide liquidity
+    pub leftover: AssetList,
+    /// The ID of the Osmosis lock created by a `LockTokens` or
+    /// `SuperfluidDelegate` post provide action
+    pub lock_id: Option<u64>,
+    /// The vault tokens sent to the recipient by a `VaultDeposit` post provide
+    /// action
+    pub vault_tokens: Option<Asset>,
+}
+
+#[cw_serde]
+pub struct PauseInfoResponse {
+    /// The guardian, who can pause providing liquidity besides the owner
+    pub guardian: Option<Addr>,
+    /// Whether providing liquidity is paused for all pools
+    pub global: bool,
+    /// The pools that providing liquidity to is paused for
+    pub pools: Vec<PoolIdentifier>,
 }
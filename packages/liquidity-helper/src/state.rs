@@ -0,0 +1,90 @@
+use apollo_cw_asset::{AssetInfo, AssetList};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw_storage_plus::{Item, Map};
+
+use crate::msg::{PoolIdentifier, PoolInput, PostProvideAction, ProvideLiquidityResponse};
+
+/// The guardian, who can pause providing liquidity besides the owner
+pub const GUARDIAN: Item<Addr> = Item::new("guardian");
+/// Whether providing liquidity is paused for all pools
+pub const PAUSED: Item<bool> = Item::new("paused");
+/// The pools that providing liquidity to is paused for, keyed by
+/// `PoolIdentifier::key`
+pub const PAUSED_POOLS: Map<String, PoolIdentifier> = Map::new("paused_pools");
+/// The cw-dex-router that assets not in the pool are routed through
+pub const ROUTER: Item<Addr> = Item::new("router");
+
+/// State of a `BalancingWithdrawLiquidity` that is waiting for the replies to
+/// the withdraw liquidity and swap submessages. Removed again in the last
+/// reply.
+#[cw_serde]
+pub struct WithdrawLiquidityState<P> {
+    pub pool: P,
+    pub target_asset: AssetInfo,
+    /// The balances of the pool assets held by the contract before withdrawing
+    pub balances_before: AssetList,
+    pub min_out: Uint128,
+    pub recipient: Addr,
+}
+
+/// The storage item of the `WithdrawLiquidityState`, which is generic over the
+/// pool type of the contract
+pub const fn withdraw_liquidity_state<P>() -> Item<'static, WithdrawLiquidityState<P>> {
+    Item::new("withdraw_liquidity_state")
+}
+
+/// State of a `BalancingProvideLiquidity` that is waiting for the reply to the
+/// last submessage routing the assets not in the pool into pool assets.
+/// Removed again in the reply.
+#[cw_serde]
+pub struct ZapState {
+    pub pool: PoolInput,
+    pub pool_assets: Vec<AssetInfo>,
+    /// The balances of the pool assets held by the contract before the assets
+    /// were received
+    pub balances_before: AssetList,
+    pub min_out: Uint128,
+    pub recipient: Addr,
+    pub max_swap_slippage: Option<Decimal>,
+    pub initial_price: Option<Decimal>,
+    pub max_price_deviation: Option<Decimal>,
+    pub post_provide: Option<PostProvideAction>,
+}
+
+pub const ZAP_STATE: Item<ZapState> = Item::new("zap_state");
+
+/// State of a `MigrateLiquidity` that is waiting for the reply to the withdraw
+/// liquidity submessage. Removed again in the reply.
+#[cw_serde]
+pub struct MigrateLiquidityState {
+    pub to_pool: PoolInput,
+    /// The assets of the pool that liquidity is withdrawn from
+    pub withdrawn_assets: Vec<AssetInfo>,
+    /// The balances of `withdrawn_assets` held by the contract before
+    /// withdrawing
+    pub balances_before: AssetList,
+    pub min_withdrawn: AssetList,
+    pub min_out: Uint128,
+    pub recipient: Addr,
+    pub max_swap_slippage: Option<Decimal>,
+    pub max_price_deviation: Option<Decimal>,
+}
+
+pub const MIGRATE_LIQUIDITY_STATE: Item<MigrateLiquidityState> =
+    Item::new("migrate_liquidity_state");
+
+/// State of a `VaultDeposit` post provide action that is waiting for the reply
+/// to the deposit into the vault. Removed again in the reply.
+#[cw_serde]
+pub struct VaultDepositState {
+    pub vault_token: AssetInfo,
+    /// The balance of the vault token held by the contract before depositing
+    pub balance_before: Uint128,
+    pub min_vault_tokens: Uint128,
+    pub recipient: Addr,
+    /// The data of the provide, which is set with the vault tokens in the reply
+    pub response: ProvideLiquidityResponse,
+}
+
+pub const VAULT_DEPOSIT_STATE: Item<VaultDepositState> = Item::new("vault_deposit_state");